@@ -0,0 +1,104 @@
+//! Building model instances field by field, validating them upon `build`.
+//!
+//! A [`ModelBuilder`](./struct.ModelBuilder.html) — given by
+//! [`Model::builder`](../model/trait.Model.html#method.builder) — collects field values by their
+//! serialized name, & its [`build`](./struct.ModelBuilder.html#method.build) deserializes them
+//! into the model & then validates the new instance, so that an invalid instance is caught when
+//! it is constructed rather than when it is written:
+//!
+//! ```rust,ignore
+//! let user = User::builder()
+//!     .field("email", "test@test.com")
+//!     .field("age", 42)
+//!     .build()?;
+//! ```
+//!
+//! Fields which serde can not default — those without `#[serde(default)]`, other than `Option`s —
+//! must be set, or `build` returns an error naming the first missing field.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use bson::{self, Bson, Document};
+
+use error::WitherError;
+use model::{check_field_ranges, check_required_fields, Model};
+
+/// A reason a model instance given to a `ModelBuilder` is invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// The serialized name of the invalid field, when the error concerns a single field.
+    pub field: Option<String>,
+
+    /// A description of the error.
+    pub message: String,
+}
+
+impl ValidationError {
+    /// Describe the given error returned by validation.
+    fn from_error(err: WitherError) -> ValidationError {
+        let field = match &err {
+            &WitherError::MissingRequiredField{ref field} => Some(field.clone()),
+            &WitherError::OutOfRange{ref field, ..} => Some(field.clone()),
+            _ => None,
+        };
+        ValidationError{field, message: err.to_string()}
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+/// A builder of instances of the model `M`, which validates each instance it builds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelBuilder<M> {
+    doc: Document,
+    marker: PhantomData<M>,
+}
+
+impl<'a, M: Model<'a>> ModelBuilder<M> {
+    /// Create a new builder, having no fields set.
+    pub fn new() -> ModelBuilder<M> {
+        ModelBuilder{doc: Document::new(), marker: PhantomData}
+    }
+
+    /// Set the field having the given serialized name to the given value, replacing any value
+    /// already set.
+    pub fn field<V: Into<Bson>>(mut self, field: &str, value: V) -> ModelBuilder<M> {
+        self.doc.insert(field, value.into());
+        self
+    }
+
+    /// Build the instance from the fields set, & validate it.
+    ///
+    /// Every violation of the model's [`required_fields`](../model/trait.Model.html#method.required_fields)
+    /// & [`field_ranges`](../model/trait.Model.html#method.field_ranges) is returned. When there
+    /// are none, the model's [`validate`](../model/trait.Model.html#method.validate) is called,
+    /// & any error it returns is returned alone. An error deserializing the fields, such as for a
+    /// missing field, is also returned alone.
+    pub fn build(self) -> Result<M, Vec<ValidationError>> {
+        let model = bson::from_bson::<M>(Bson::Document(self.doc))
+            .map_err(|err| vec![ValidationError{field: None, message: err.to_string()}])?;
+        let doc = model.document_from_instance().map_err(|err| vec![ValidationError::from_error(err)])?;
+
+        let mut errors: Vec<ValidationError> = vec![];
+        for field in M::required_fields() {
+            if let Err(err) = check_required_fields(&doc, &[field]) {
+                errors.push(ValidationError::from_error(err));
+            }
+        }
+        for range in M::field_ranges() {
+            if let Err(err) = check_field_ranges(&doc, &[range]) {
+                errors.push(ValidationError::from_error(err));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        model.validate().map_err(|err| vec![ValidationError::from_error(err)])?;
+        Ok(model)
+    }
+}
@@ -0,0 +1,75 @@
+//! Connecting to MongoDB with bounded latency.
+//!
+//! The driver connects lazily, so a misconfigured URI is normally only discovered by the first
+//! operation, which may then hang for the driver's default server selection timeout of 30
+//! seconds. [`connect`](./fn.connect.html) instead verifies the connection up front, failing
+//! within the configured timeouts, which suits boot sequences & readiness probes alike:
+//!
+//! ```rust,ignore
+//! let client = wither::connect("mongodb://localhost:27017/", wither::ConnectOptions::default())?;
+//!
+//! // Later, from a readiness probe.
+//! let latency = wither::ping(&client, Duration::from_secs(1))?;
+//! ```
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mongodb::{Client, ClientOptions, CommandType, ThreadedClient};
+use mongodb::db::ThreadedDatabase;
+use mongodb::error::Error::DefaultError;
+
+use error::Result;
+use migration::duration_ms;
+
+/// Timeouts bounding how long connecting to MongoDB may take.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectOptions {
+    /// How long to wait for the initial connection to be verified via a `ping`, including
+    /// server selection. Defaults to 10 seconds.
+    pub connect_timeout: Duration,
+
+    /// How long each operation of the client may wait for a suitable server to become
+    /// available. Defaults to 5 seconds, rather than the driver's 30, so that an unavailable
+    /// cluster fails operations fast.
+    pub server_selection_timeout: Duration,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> ConnectOptions {
+        ConnectOptions{connect_timeout: Duration::from_secs(10), server_selection_timeout: Duration::from_secs(5)}
+    }
+}
+
+/// Connect to MongoDB via the given URI, verifying the connection within the given timeouts.
+///
+/// The client is configured with the given `server_selection_timeout`, & is then pinged. If the
+/// ping does not succeed within the `connect_timeout`, an error is returned.
+pub fn connect(uri: &str, options: ConnectOptions) -> Result<Client> {
+    let mut client_options = ClientOptions::new();
+    client_options.server_selection_timeout_ms = duration_ms(options.server_selection_timeout);
+    let client = Client::with_uri_and_options(uri, client_options)?;
+    ping(&client, options.connect_timeout)?;
+    Ok(client)
+}
+
+/// Ping the server via the given client, returning the round trip time.
+///
+/// Returns an error if the ping fails, or does not complete within the given timeout. The
+/// driver offers no means of cancelling an operation, so a ping which times out is abandoned
+/// to the client's server selection timeout in the background.
+pub fn ping(client: &Client, timeout: Duration) -> Result<Duration> {
+    let (sender, receiver) = mpsc::channel();
+    let client = client.clone();
+    let started = Instant::now();
+    thread::spawn(move || {
+        let res = client.db("admin").command(doc!{"ping": 1}, CommandType::Suppressed, None);
+        let _ = sender.send(res.map(|_| ()));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(())) => Ok(started.elapsed()),
+        Ok(Err(err)) => Err(err.into()),
+        Err(_) => Err(DefaultError(format!("Timed out after {:?} pinging MongoDB.", timeout)).into()),
+    }
+}
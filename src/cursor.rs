@@ -0,0 +1,222 @@
+//! Typed cursors over model instances.
+
+use std::cmp;
+use std::marker::PhantomData;
+use std::thread;
+use std::time::Duration;
+
+use bson::{Bson, Document};
+use mongodb::coll::options::FindOptions;
+use mongodb::cursor::Cursor;
+use mongodb::db::Database;
+
+use error::Result;
+use model::Model;
+
+/// A cursor which lazily deserializes each document it yields into an instance of `T`.
+///
+/// Documents are fetched from the server in batches as the cursor is iterated, so only the
+/// current batch is held in memory at any one time.
+pub struct ModelCursor<T> {
+    cursor: Cursor,
+    marker: PhantomData<T>,
+}
+
+impl<T> ModelCursor<T> {
+    /// Wrap the given driver cursor.
+    pub fn new(cursor: Cursor) -> ModelCursor<T> {
+        ModelCursor{cursor, marker: PhantomData}
+    }
+
+    /// Consume this cursor, returning the underlying driver cursor.
+    pub fn into_inner(self) -> Cursor {
+        self.cursor
+    }
+}
+
+impl<'a, T> Iterator for ModelCursor<T> where T: Model<'a> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        match self.cursor.next() {
+            Some(Ok(doc)) => Some(T::instance_from_document(doc)),
+            Some(Err(err)) => Some(Err(err.into())),
+            None => None,
+        }
+    }
+}
+
+/// A cursor which yields raw documents, without deserializing them into any model.
+///
+/// This differs from the driver's cursor only in yielding this crate's error type.
+pub struct DocumentCursor {
+    cursor: Cursor,
+}
+
+impl DocumentCursor {
+    /// Wrap the given driver cursor.
+    pub fn new(cursor: Cursor) -> DocumentCursor {
+        DocumentCursor{cursor}
+    }
+
+    /// Consume this cursor, returning the underlying driver cursor.
+    pub fn into_inner(self) -> Cursor {
+        self.cursor
+    }
+}
+
+impl Iterator for DocumentCursor {
+    type Item = Result<Document>;
+
+    fn next(&mut self) -> Option<Result<Document>> {
+        self.cursor.next().map(|res| res.map_err(Into::into))
+    }
+}
+
+/// A cursor which yields instances of `T` in chunks, holding no more than a fixed number of
+/// deserialized instances in memory at once.
+///
+/// Each chunk is only read from the underlying cursor when it is requested, & the driver only
+/// fetches a further batch from the server once the current batch has been consumed, so a slow
+/// consumer applies backpressure all the way back to the server. Each chunk is passed through
+/// the model's [`after_load_batch`](../model/trait.Model.html#method.after_load_batch) hook.
+pub struct CappedModelCursor<T> {
+    cursor: ModelCursor<T>,
+    max_buffered: usize,
+}
+
+impl<T> CappedModelCursor<T> {
+    /// Wrap the given cursor, yielding chunks of at most `max_buffered` instances.
+    ///
+    /// A `max_buffered` of `0` is treated as `1`.
+    pub fn new(cursor: ModelCursor<T>, max_buffered: usize) -> CappedModelCursor<T> {
+        CappedModelCursor{cursor, max_buffered: max_buffered.max(1)}
+    }
+
+    /// The maximum number of instances in each chunk.
+    pub fn max_buffered(&self) -> usize {
+        self.max_buffered
+    }
+
+    /// Consume this cursor, returning the underlying model cursor.
+    pub fn into_inner(self) -> ModelCursor<T> {
+        self.cursor
+    }
+}
+
+impl<'a, T> Iterator for CappedModelCursor<T> where T: Model<'a> {
+    type Item = Result<Vec<T>>;
+
+    fn next(&mut self) -> Option<Result<Vec<T>>> {
+        let mut chunk = Vec::with_capacity(self.max_buffered);
+        while chunk.len() < self.max_buffered {
+            match self.cursor.next() {
+                Some(Ok(instance)) => chunk.push(instance),
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+        if chunk.is_empty() {
+            return None;
+        }
+        Some(T::after_load_batch(&mut chunk).map(|_| chunk))
+    }
+}
+
+/// The interval, in milliseconds, to wait before first reopening a tailable cursor which has died.
+const TAIL_REOPEN_INTERVAL_MS: u64 = 1000;
+
+/// The longest interval, in milliseconds, to wait before reopening a tailable cursor, to which
+/// the wait backs off while the cursor keeps dying or failing to reopen.
+const TAIL_REOPEN_MAX_INTERVAL_MS: u64 = 30_000;
+
+/// A cursor which follows a capped collection, blocking for new documents rather than ending at
+/// the current end of the collection.
+///
+/// The underlying driver cursor is tailable-await, so the server holds each request open for a
+/// while when no new documents are available. Should the driver cursor die — due to a network
+/// error, or because the collection was empty when it was opened — it is transparently reopened
+/// after a pause, resuming after the value of the resume field of the last document yielded.
+/// The pause doubles, up to 30 seconds, each time the cursor dies or fails to reopen without
+/// yielding a document in between. As such, this iterator never ends. An error is only yielded
+/// when a cursor can not be reopened, & iteration may continue after one to try again.
+///
+/// The resume field's values must increase in insertion order, else documents inserted around
+/// the time the cursor dies are skipped — see
+/// [`Model::tail_resume_field`](../model/trait.Model.html#method.tail_resume_field).
+pub struct TailableCursor<T> {
+    db: Database,
+    filter: Document,
+    options: FindOptions,
+    cursor: Option<Cursor>,
+    resume_field: String,
+    last_value: Option<Bson>,
+    reopen_interval_ms: u64,
+    marker: PhantomData<T>,
+}
+
+impl<T> TailableCursor<T> {
+    /// Create a cursor tailing the given query, with the given tailable find options, which
+    /// resumes by the given field when reopened.
+    pub fn new(db: Database, filter: Document, options: FindOptions, cursor: Cursor, resume_field: &str) -> TailableCursor<T> {
+        TailableCursor{
+            db, filter, options,
+            cursor: Some(cursor),
+            resume_field: resume_field.to_owned(),
+            last_value: None,
+            reopen_interval_ms: TAIL_REOPEN_INTERVAL_MS,
+            marker: PhantomData,
+        }
+    }
+
+    /// The filter to reopen the cursor with, resuming after the last document yielded.
+    fn resume_filter(&self) -> Document {
+        match self.last_value {
+            Some(ref value) => {
+                let mut resume = Document::new();
+                resume.insert(self.resume_field.clone(), doc!{"$gt": value.clone()});
+                doc!{"$and": [self.filter.clone(), resume]}
+            },
+            None => self.filter.clone(),
+        }
+    }
+
+    /// Pause before reopening the cursor, backing off the next pause.
+    fn back_off(&mut self) {
+        thread::sleep(Duration::from_millis(self.reopen_interval_ms));
+        self.reopen_interval_ms = cmp::min(self.reopen_interval_ms * 2, TAIL_REOPEN_MAX_INTERVAL_MS);
+    }
+}
+
+impl<'a, T> Iterator for TailableCursor<T> where T: Model<'a> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        loop {
+            if self.cursor.is_none() {
+                let coll = T::collection(&self.db);
+                match coll.find(Some(self.resume_filter()), Some(self.options.clone())) {
+                    Ok(cursor) => self.cursor = Some(cursor),
+                    Err(err) => {
+                        self.back_off();
+                        return Some(Err(err.into()));
+                    },
+                }
+            }
+            let next = self.cursor.as_mut().and_then(|cursor| cursor.next());
+            match next {
+                Some(Ok(doc)) => {
+                    if let Some(value) = doc.get(&self.resume_field) {
+                        self.last_value = Some(value.clone());
+                    }
+                    self.reopen_interval_ms = TAIL_REOPEN_INTERVAL_MS;
+                    return Some(T::instance_from_document(doc));
+                },
+                Some(Err(err)) => warn!("Tailable cursor over '{}' died, reopening: {:?}", T::COLLECTION_NAME, err),
+                None => debug!("Tailable cursor over '{}' ended, reopening.", T::COLLECTION_NAME),
+            }
+            self.cursor = None;
+            self.back_off();
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Error types used throughout this crate.
+//!
+//! Errors from the underlying driver are wrapped in
+//! [`WitherError::Mongo`](./enum.WitherError.html#variant.Mongo). Other variants describe
+//! conditions which callers will commonly want to handle distinctly, such as a missing document,
+//! which a web layer may want to map onto a `404`.
+
+use std::error::Error;
+use std::fmt;
+
+use bson;
+use bson::oid::ObjectId;
+use mongodb;
+
+/// A result type using `WitherError` as its error type.
+pub type Result<T> = ::std::result::Result<T, WitherError>;
+
+/// The error type of this crate.
+#[derive(Debug)]
+pub enum WitherError {
+    /// An error from the underlying driver.
+    Mongo(mongodb::error::Error),
+
+    /// No document exists in `collection` having the given ID.
+    NotFound {
+        /// The name of the collection which was searched.
+        collection: String,
+
+        /// The ID which was not found.
+        id: ObjectId,
+    },
+
+    /// The document in `collection` having the given ID was modified since it was loaded, so a
+    /// conditional write was not applied.
+    Stale {
+        /// The name of the collection which was written to.
+        collection: String,
+
+        /// The ID of the stale document.
+        id: ObjectId,
+    },
+
+    /// A write violated a unique index, as another document already has the same key.
+    ///
+    /// Driver errors having the duplicate key error code are converted into this variant, so
+    /// that a web layer may map it onto a `409` without inspecting error messages.
+    DuplicateKey {
+        /// The name of the unique index which was violated.
+        index: String,
+
+        /// The duplicated key value, as reported by the server, e.g. `{ email: "a@b.com" }`.
+        key_value: String,
+    },
+
+    /// A field which the model requires to be non-empty was empty when an instance was written.
+    MissingRequiredField {
+        /// The serialized name of the empty field.
+        field: String,
+    },
+
+    /// A numeric field was outside of the range the model allows when an instance was written.
+    OutOfRange {
+        /// The serialized name of the field.
+        field: String,
+
+        /// The inclusive lower bound of the range, if any.
+        min: Option<f64>,
+
+        /// The inclusive upper bound of the range, if any.
+        max: Option<f64>,
+
+        /// The field's value.
+        actual: f64,
+    },
+}
+
+/// The server error code for a duplicate key.
+pub(crate) const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// The server error code for a write conflict.
+const WRITE_CONFLICT_CODE: i32 = 112;
+
+/// The server error codes of errors which are transient, such as during a replica set failover.
+const TRANSIENT_CODES: &[i32] = &[
+    6,     // HostUnreachable
+    7,     // HostNotFound
+    89,    // NetworkTimeout
+    91,    // ShutdownInProgress
+    189,   // PrimarySteppedDown
+    9001,  // SocketException
+    10107, // NotMaster
+    11600, // InterruptedAtShutdown
+    11602, // InterruptedDueToReplStateChange
+    13435, // NotMasterNoSlaveOk
+    13436, // NotMasterOrSecondary
+];
+
+impl WitherError {
+    /// The server error code of this error, if any.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            &WitherError::DuplicateKey{..} => Some(DUPLICATE_KEY_CODE),
+            &WitherError::Mongo(mongodb::error::Error::WriteError(ref exc)) => exc.write_error.as_ref().map(|err| err.code),
+            &WitherError::Mongo(mongodb::error::Error::BulkWriteError(ref exc)) => exc.write_errors.first().map(|err| err.code),
+            _ => None,
+        }
+    }
+
+    /// Whether this error is a transient write conflict, which may be retried.
+    pub fn is_write_conflict(&self) -> bool {
+        if self.code() == Some(WRITE_CONFLICT_CODE) {
+            return true;
+        }
+        match self {
+            &WitherError::Mongo(mongodb::error::Error::OperationError(ref msg)) => msg.contains("WriteConflict"),
+            _ => false,
+        }
+    }
+
+    /// Whether this error is transient, such as a network error or a failover in progress, so
+    /// that the operation may succeed if retried. Write conflicts are transient too.
+    pub fn is_transient(&self) -> bool {
+        if self.is_write_conflict() || self.code().map(|code| TRANSIENT_CODES.contains(&code)).unwrap_or(false) {
+            return true;
+        }
+        match self {
+            &WitherError::Mongo(mongodb::error::Error::IoError(_)) => true,
+            &WitherError::Mongo(mongodb::error::Error::OperationError(ref msg)) => msg.contains("not master") || msg.contains("node is recovering"),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for WitherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &WitherError::Mongo(ref err) => err.fmt(f),
+            &WitherError::NotFound{ref collection, ref id} => write!(f, "No document found in '{}' having ID '{}'.", collection, id),
+            &WitherError::Stale{ref collection, ref id} => write!(f, "Document in '{}' having ID '{}' was modified since it was loaded.", collection, id),
+            &WitherError::DuplicateKey{ref index, ref key_value} => write!(f, "Duplicate key {} violates unique index '{}'.", key_value, index),
+            &WitherError::MissingRequiredField{ref field} => write!(f, "Required field '{}' is empty.", field),
+            &WitherError::OutOfRange{ref field, min, max, actual} => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "Field '{}' is {}, but must be between {} & {}.", field, actual, min, max),
+                (Some(min), None) => write!(f, "Field '{}' is {}, but must be at least {}.", field, actual, min),
+                (None, Some(max)) => write!(f, "Field '{}' is {}, but must be at most {}.", field, actual, max),
+                (None, None) => write!(f, "Field '{}' is {}, outside of its range.", field, actual),
+            },
+        }
+    }
+}
+
+impl Error for WitherError {
+    fn description(&self) -> &str {
+        match self {
+            &WitherError::Mongo(ref err) => err.description(),
+            &WitherError::NotFound{..} => "No document found having the given ID.",
+            &WitherError::Stale{..} => "Document was modified since it was loaded.",
+            &WitherError::DuplicateKey{..} => "Duplicate key violates a unique index.",
+            &WitherError::MissingRequiredField{..} => "A required field is empty.",
+            &WitherError::OutOfRange{..} => "A field is outside of its allowed range.",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match self {
+            &WitherError::Mongo(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for WitherError {
+    fn from(err: mongodb::error::Error) -> WitherError {
+        let duplicate = match &err {
+            &mongodb::error::Error::WriteError(ref exc) => match exc.write_error {
+                Some(ref err) if err.code == DUPLICATE_KEY_CODE => Some(duplicate_key(&err.message)),
+                _ => None,
+            },
+            &mongodb::error::Error::BulkWriteError(ref exc) => exc.write_errors.iter()
+                .find(|err| err.code == DUPLICATE_KEY_CODE)
+                .map(|err| duplicate_key(&err.message)),
+            &mongodb::error::Error::OperationError(ref msg) if msg.contains("E11000") => Some(duplicate_key(msg)),
+            _ => None,
+        };
+        match duplicate {
+            Some((index, key_value)) => WitherError::DuplicateKey{index, key_value},
+            None => WitherError::Mongo(err),
+        }
+    }
+}
+
+/// Parse the index name & key value out of a server duplicate key error message.
+///
+/// Messages take the form `E11000 duplicate key error collection: db.users index: unique-email
+/// dup key: { email: "a@b.com" }`, or on older servers `index: db.users.$unique-email`. Parts
+/// which can not be found are left empty.
+fn duplicate_key(message: &str) -> (String, String) {
+    let index = message.split("index: ").nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(|index| index.rsplit(".$").next().unwrap_or(index))
+        .unwrap_or("");
+    let key_value = message.split("dup key: ").nth(1).unwrap_or("");
+    (index.to_string(), key_value.trim().to_string())
+}
+
+impl From<bson::EncoderError> for WitherError {
+    fn from(err: bson::EncoderError) -> WitherError {
+        WitherError::Mongo(mongodb::error::Error::from(err))
+    }
+}
+
+impl From<bson::DecoderError> for WitherError {
+    fn from(err: bson::DecoderError) -> WitherError {
+        WitherError::Mongo(mongodb::error::Error::from(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_key_parses_index_and_key_value() {
+        let message = r#"E11000 duplicate key error collection: witherTestDB.users index: unique-email dup key: { email: "test@test.com" }"#;
+
+        let output = duplicate_key(message);
+
+        assert_eq!(output, ("unique-email".to_string(), r#"{ email: "test@test.com" }"#.to_string()));
+    }
+
+    #[test]
+    fn operation_errors_with_duplicate_key_convert_to_duplicate_key() {
+        let err = mongodb::error::Error::OperationError(r#"E11000 duplicate key error index: witherTestDB.users.$unique-email dup key: { : "test@test.com" }"#.to_string());
+
+        let output = WitherError::from(err);
+
+        match output {
+            WitherError::DuplicateKey{index, key_value} => {
+                assert_eq!(index, "unique-email".to_string());
+                assert_eq!(key_value, r#"{ : "test@test.com" }"#.to_string());
+            },
+            other => panic!("Expected a duplicate key error, got {:?}.", other),
+        }
+    }
+
+    #[test]
+    fn is_transient_recognizes_failover_and_network_errors() {
+        let not_master = WitherError::from(mongodb::error::Error::OperationError("not master".to_string()));
+        let io = WitherError::from(mongodb::error::Error::IoError(::std::io::Error::new(::std::io::ErrorKind::ConnectionReset, "reset")));
+        let other = WitherError::from(mongodb::error::Error::OperationError("bad query".to_string()));
+
+        assert!(not_master.is_transient());
+        assert!(io.is_transient());
+        assert!(!other.is_transient());
+    }
+}
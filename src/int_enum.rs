@@ -0,0 +1,164 @@
+//! Storing enums as integer codes, with a migration path from their string representation.
+//!
+//! Implement [`IntEnum`](./trait.IntEnum.html) for your enum, declaring each variant's legacy
+//! string name & its integer code in one place. Then use this module with serde's `with`
+//! attribute on the corresponding model field:
+//!
+//! ```rust,ignore
+//! #[derive(Clone, Copy, Debug, PartialEq)]
+//! pub enum Status { Active, Banned }
+//!
+//! impl wither::int_enum::IntEnum for Status {
+//!     fn variants() -> Vec<(Status, &'static str, i32)> {
+//!         vec![(Status::Active, "Active", 1), (Status::Banned, "Banned", 2)]
+//!     }
+//! }
+//!
+//! // Within the model.
+//! #[serde(with = "wither::int_enum")]
+//! pub status: Status,
+//! ```
+//!
+//! Values are always written as their integer code. When reading, both integer codes & legacy
+//! string names are accepted, so the model keeps working while documents are being converted.
+//! Finally, add the migration generated by [`migration`](./fn.migration.html) to your model's
+//! `Model::migrations`, so that stored strings converge on their integer codes, driven by the
+//! very same mapping.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use bson::Bson;
+use chrono;
+use serde::{Deserializer, Serializer};
+use serde::de::{self, Visitor};
+
+use migration::ReplaceFieldValueMigration;
+
+/// A trait for enums which are stored as integer codes.
+pub trait IntEnum: Sized + Copy + PartialEq {
+    /// Each variant of the enum, along with its legacy string name & its integer code.
+    fn variants() -> Vec<(Self, &'static str, i32)>;
+
+    /// Get the integer code of this variant.
+    fn code(&self) -> Option<i32> {
+        Self::variants().into_iter().find(|&(variant, _, _)| variant == *self).map(|(_, _, code)| code)
+    }
+
+    /// Get the variant having the given integer code.
+    fn from_code(code: i32) -> Option<Self> {
+        Self::variants().into_iter().find(|&(_, _, c)| c == code).map(|(variant, _, _)| variant)
+    }
+
+    /// Get the variant having the given legacy string name.
+    fn from_name(name: &str) -> Option<Self> {
+        Self::variants().into_iter().find(|&(_, n, _)| n == name).map(|(variant, _, _)| variant)
+    }
+}
+
+/// Serialize the given variant as its integer code.
+pub fn serialize<T: IntEnum, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    match value.code() {
+        Some(code) => serializer.serialize_i32(code),
+        None => Err(::serde::ser::Error::custom("enum variant has no integer code declared")),
+    }
+}
+
+/// Deserialize a variant from either its integer code or its legacy string name.
+pub fn deserialize<'de, T: IntEnum, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    deserializer.deserialize_any(IntEnumVisitor(PhantomData))
+}
+
+struct IntEnumVisitor<T>(PhantomData<T>);
+
+impl<'de, T: IntEnum> Visitor<'de> for IntEnumVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an integer code or a string name of an enum variant")
+    }
+
+    fn visit_i32<E: de::Error>(self, code: i32) -> Result<T, E> {
+        T::from_code(code).ok_or_else(|| E::custom(format!("unknown enum code {}", code)))
+    }
+
+    fn visit_i64<E: de::Error>(self, code: i64) -> Result<T, E> {
+        if code < i32::min_value() as i64 || code > i32::max_value() as i64 {
+            return Err(E::custom(format!("unknown enum code {}", code)));
+        }
+        self.visit_i32(code as i32)
+    }
+
+    fn visit_u64<E: de::Error>(self, code: u64) -> Result<T, E> {
+        if code > i32::max_value() as u64 {
+            return Err(E::custom(format!("unknown enum code {}", code)));
+        }
+        self.visit_i32(code as i32)
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<T, E> {
+        T::from_name(name).ok_or_else(|| E::custom(format!("unknown enum variant '{}'", name)))
+    }
+}
+
+/// Generate a migration converting the legacy string names of `T` stored in `field` into their
+/// integer codes, until the given `threshold`.
+pub fn migration<T: IntEnum>(name: &str, field: &str, threshold: chrono::DateTime<chrono::Utc>) -> ReplaceFieldValueMigration {
+    ReplaceFieldValueMigration{
+        name: name.to_owned(),
+        threshold,
+        field: field.to_owned(),
+        replacements: T::variants().into_iter()
+            .map(|(_, name, code)| (Bson::from(name), Bson::from(code)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson;
+    use chrono::TimeZone;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Status { Active, Banned }
+
+    impl IntEnum for Status {
+        fn variants() -> Vec<(Status, &'static str, i32)> {
+            vec![(Status::Active, "Active", 1), (Status::Banned, "Banned", 2)]
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Account {
+        #[serde(with = "super")]
+        status: Status,
+    }
+
+    #[test]
+    fn int_enum_serializes_as_code() {
+        let output = bson::to_bson(&Account{status: Status::Banned}).expect("Expected serialization.");
+
+        assert_eq!(output, Bson::Document(doc!{"status": 2}));
+    }
+
+    #[test]
+    fn int_enum_deserializes_codes_and_legacy_names() {
+        let from_code: Account = bson::from_bson(Bson::Document(doc!{"status": 1})).expect("Expected deserialization.");
+        let from_name: Account = bson::from_bson(Bson::Document(doc!{"status": "Banned"})).expect("Expected deserialization.");
+
+        assert_eq!(from_code.status, Status::Active);
+        assert_eq!(from_name.status, Status::Banned);
+    }
+
+    #[test]
+    fn int_enum_migration_maps_names_to_codes() {
+        let output = migration::<Status>("status-to-int", "status", chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0));
+
+        assert_eq!(output.field, "status".to_string());
+        assert_eq!(output.replacements, vec![
+            (Bson::from("Active"), Bson::from(1)),
+            (Bson::from("Banned"), Bson::from(2)),
+        ]);
+    }
+}
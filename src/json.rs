@@ -0,0 +1,212 @@
+//! Converting BSON documents to JSON, for passing data through to API consumers.
+//!
+//! Only available with the `json` feature. The conversion favors what JSON consumers expect over
+//! MongoDB's extended JSON, & is lossy in places:
+//!
+//! - `ObjectId`s become their hex string, e.g. `"5a1d7ddf8d1b43e5a1b2c3d4"`.
+//! - datetimes become RFC 3339 strings, e.g. `"2018-01-01T00:00:00+00:00"`.
+//! - 32 & 64 bit integers & timestamps become numbers. Non-finite floats become `null`.
+//! - binary data becomes its lowercase hex string.
+//! - regular expressions become `"/pattern/options"`.
+//! - JavaScript code & symbols become their source string, any scope being dropped.
+//! - documents & arrays are converted recursively, preserving field order.
+//!
+//! JSON coming in, such as a [merge patch](./fn.merge_patch.html) from an HTTP `PATCH` request,
+//! is converted by [`json_to_bson`](./fn.json_to_bson.html), which can not recover the BSON types
+//! lost above: strings stay strings, so a patch can not set an `ObjectId` or datetime field.
+
+use bson::{Bson, Document};
+use mongodb::error::Error::ArgumentError;
+use serde_json::{Map, Number, Value};
+
+use error::Result;
+use update::Update;
+
+/// Convert the given BSON value into a JSON value.
+pub fn bson_to_json(value: Bson) -> Value {
+    match value {
+        Bson::FloatingPoint(val) => Number::from_f64(val).map(Value::Number).unwrap_or(Value::Null),
+        Bson::String(val) => Value::String(val),
+        Bson::Array(vals) => Value::Array(vals.into_iter().map(bson_to_json).collect()),
+        Bson::Document(doc) => document_to_json(doc),
+        Bson::Boolean(val) => Value::Bool(val),
+        Bson::Null => Value::Null,
+        Bson::RegExp(pattern, options) => Value::String(format!("/{}/{}", pattern, options)),
+        Bson::JavaScriptCode(code) => Value::String(code),
+        Bson::JavaScriptCodeWithScope(code, _) => Value::String(code),
+        Bson::I32(val) => Value::from(val),
+        Bson::I64(val) => Value::from(val),
+        Bson::TimeStamp(val) => Value::from(val),
+        Bson::Binary(_, bytes) => Value::String(bytes.iter().map(|byte| format!("{:02x}", byte)).collect()),
+        Bson::ObjectId(oid) => Value::String(oid.to_hex()),
+        Bson::UtcDatetime(datetime) => Value::String(datetime.to_rfc3339()),
+        Bson::Symbol(symbol) => Value::String(symbol),
+    }
+}
+
+/// Convert the given BSON document into a JSON object.
+pub fn document_to_json(doc: Document) -> Value {
+    let mut map = Map::new();
+    for (key, val) in doc {
+        map.insert(key, bson_to_json(val));
+    }
+    Value::Object(map)
+}
+
+/// Convert the given JSON value into a BSON value.
+///
+/// Integers become 32 bit integers where they fit, & 64 bit integers otherwise. Numbers beyond
+/// the range of a 64 bit integer become floats. Objects are converted recursively, preserving
+/// field order.
+pub fn json_to_bson(value: Value) -> Bson {
+    match value {
+        Value::Null => Bson::Null,
+        Value::Bool(val) => Bson::Boolean(val),
+        Value::Number(val) => match val.as_i64() {
+            Some(val) if val >= i64::from(i32::min_value()) && val <= i64::from(i32::max_value()) => Bson::I32(val as i32),
+            Some(val) => Bson::I64(val),
+            None => Bson::FloatingPoint(val.as_f64().unwrap_or(::std::f64::NAN)),
+        },
+        Value::String(val) => Bson::String(val),
+        Value::Array(vals) => Bson::Array(vals.into_iter().map(json_to_bson).collect()),
+        Value::Object(map) => {
+            let mut doc = Document::new();
+            for (key, val) in map {
+                doc.insert(key, json_to_bson(val));
+            }
+            Bson::Document(doc)
+        },
+    }
+}
+
+/// Apply the given RFC 7396 JSON Merge Patch to the `target` document, returning the equivalent
+/// update document of `$set` & `$unset` operators, or `None` if the patch changes nothing.
+///
+/// A `null` removes its field, an object is merged recursively into an embedded document, & any
+/// other value — including an array — replaces its field. An object patching a field which is
+/// not an embedded document replaces it, less any `null`s within. The patch must be an object,
+/// & its field names must not be empty, contain `.` or start with `$`. The top level `_id` can
+/// not be patched. Each of these returns an `ArgumentError`.
+pub fn merge_patch(target: &mut Document, patch: Value) -> Result<Option<Document>> {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => return Err(ArgumentError("A merge patch of a document must be a JSON object.".to_owned()).into()),
+    };
+    if patch.contains_key("_id") {
+        return Err(ArgumentError("A merge patch may not change a document's '_id'.".to_owned()).into());
+    }
+    let mut changes = vec![];
+    merge_patch_fields(target, "", patch, &mut changes)?;
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    let update = changes.into_iter().fold(Update::new(), |update, (path, value)| match value {
+        Some(value) => update.set(&path, value),
+        None => update.unset(&path),
+    });
+    update.into_document().map(Some)
+}
+
+/// Merge the given patch fields into `target`, whose path is `prefix`, recording the path of each
+/// field to set, with its value, or to unset.
+fn merge_patch_fields(target: &mut Document, prefix: &str, patch: Map<String, Value>, changes: &mut Vec<(String, Option<Bson>)>) -> Result<()> {
+    for (key, val) in patch {
+        if key.is_empty() || key.contains('.') || key.starts_with('$') {
+            return Err(ArgumentError(format!("Merge patch field '{}' must not be empty, contain '.' or start with '$'.", key)).into());
+        }
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match val {
+            Value::Null => {
+                if target.remove(&key).is_some() {
+                    changes.push((path, None));
+                }
+            },
+            Value::Object(fields) => {
+                if let Some(&mut Bson::Document(ref mut inner)) = target.get_mut(&key) {
+                    merge_patch_fields(inner, &path, fields, changes)?;
+                    continue;
+                }
+                let mut inner = Document::new();
+                merge_patch_fields(&mut inner, &path, fields, &mut vec![])?;
+                changes.push((path, Some(Bson::Document(inner.clone()))));
+                target.insert(key, inner);
+            },
+            val => {
+                let val = json_to_bson(val);
+                changes.push((path, Some(val.clone())));
+                target.insert(key, val);
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::oid::ObjectId;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn document_to_json_applies_conversion_rules() {
+        let oid = ObjectId::with_string("5a1d7ddf8d1b43e5a1b2c3d4").expect("Expected a valid ObjectId.");
+        let input = doc!{
+            "_id": oid,
+            "created_at": Bson::UtcDatetime(Utc.ymd(2018, 1, 1).and_hms(0, 0, 0)),
+            "count": 3i64,
+            "ratio": ::std::f64::NAN,
+            "tags": ["a", "b"],
+        };
+
+        let output = document_to_json(input);
+
+        assert_eq!(output, json!({
+            "_id": "5a1d7ddf8d1b43e5a1b2c3d4",
+            "created_at": "2018-01-01T00:00:00+00:00",
+            "count": 3,
+            "ratio": null,
+            "tags": ["a", "b"],
+        }));
+    }
+
+    #[test]
+    fn merge_patch_merges_objects_and_deletes_nulls() {
+        let mut target = doc!{
+            "_id": 1,
+            "name": "a",
+            "address": doc!{"city": "x", "zip": "1"},
+            "nickname": "b",
+            "tags": ["c"],
+        };
+        let patch = json!({
+            "name": "d",
+            "address": {"zip": null, "street": "e"},
+            "nickname": {"short": "f", "long": null},
+            "tags": ["g", 2],
+            "age": null,
+        });
+
+        let update = merge_patch(&mut target, patch).expect("Expected a valid patch.");
+
+        assert_eq!(update, Some(doc!{
+            "$set": doc!{"address.street": "e", "name": "d", "nickname": doc!{"short": "f"}, "tags": ["g", 2]},
+            "$unset": doc!{"address.zip": ""},
+        }));
+        assert_eq!(target, doc!{
+            "_id": 1,
+            "name": "d",
+            "address": doc!{"city": "x", "street": "e"},
+            "nickname": doc!{"short": "f"},
+            "tags": ["g", 2],
+        });
+    }
+
+    #[test]
+    fn merge_patch_rejects_invalid_patches() {
+        assert!(merge_patch(&mut doc!{}, json!([1])).is_err());
+        assert!(merge_patch(&mut doc!{}, json!({"_id": 1})).is_err());
+        assert!(merge_patch(&mut doc!{}, json!({"a": {"b.c": 1}})).is_err());
+        assert!(merge_patch(&mut doc!{}, json!({"$set": {}})).is_err());
+        assert_eq!(merge_patch(&mut doc!{"a": 1}, json!({"b": null})).unwrap(), None);
+    }
+}
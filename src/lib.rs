@@ -12,6 +12,10 @@
 //! MongoDB based on data models. If at any point this system might get in your way, you have
 //! direct access to the underlying driver.
 //!
+//! This crate has no derive macro. Configuration which an ODM would often take as field
+//! attributes — such as indexes, required & lazy fields, or field ranges — is instead declared by
+//! overriding the methods of the [`Model`](./model/trait.Model.html) trait.
+//!
 //! This project makes use of `associated constants` as of `0.2.0`, so you will need to be
 //! running rust `>= 1.20`.
 //!
@@ -79,20 +83,85 @@
 #[macro_use(doc, bson)]
 pub extern crate bson;
 extern crate chrono;
+#[cfg(feature = "async")]
+extern crate futures;
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
 pub extern crate mongodb;
+#[macro_use]
 extern crate serde;
+#[cfg_attr(not(test), macro_use(Serialize))]
+#[cfg_attr(test, macro_use(Serialize, Deserialize))]
+extern crate serde_derive;
+#[cfg(feature = "json")]
+#[cfg_attr(test, macro_use(json))]
+extern crate serde_json;
 
+pub mod builder;
+pub mod connect;
+pub mod cursor;
+pub mod error;
+pub mod int_enum;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod migration;
 pub mod model;
+pub mod new_model;
+pub mod oid_as_string;
+pub mod pipeline;
+pub mod query;
+pub mod query_cache;
+pub mod registry;
+pub mod schema;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod tenant;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tracked;
+pub mod update;
 
 // Expose lower symbols in the top level module.
+pub use builder::{ModelBuilder, ValidationError};
+pub use connect::{connect, ping, ConnectOptions};
+pub use cursor::{CappedModelCursor, DocumentCursor, ModelCursor, TailableCursor};
+pub use error::WitherError;
 pub use migration::{
+    BatchedMigration,
     IntervalMigration,
     Migration,
+    MigrationResult,
+    MigrationSpec,
+    ReplaceFieldValueMigration,
+    StreamingTransformMigration,
 };
 pub use model::{
     basic_index_options,
+    FieldChange,
+    FieldRange,
+    index_min_server_version,
+    IndexBuildProgress,
+    IndexUsage,
     Model,
+    Page,
+    random_object_id,
+    SchemaDescriptor,
+    ServerVersion,
+    Slice,
+    SoftDelete,
+    SoftDeleteCascade,
+    SyncReport,
+    Timeseries,
+    TimeseriesGranularity,
+    Timestamps,
+    UpsertOutcome,
 };
+pub use new_model::NewModel;
+pub use pipeline::{OutputStage, Pipeline};
+pub use query::{Filter, Sort};
+pub use schema::JsonSchema;
+pub use tenant::TenantScoped;
+pub use tracked::Tracked;
+pub use update::Update;
@@ -11,20 +11,24 @@
 //! // You should never have to remove these from your source code.
 //! fn migrations() -> Vec<Box<wither::Migration>> {
 //!     return vec![
-//!         Box::new(wither::IntervalMigration{
-//!             name: String::from("remove-oldfield"),
+//!         Box::new(wither::IntervalMigration::new(
+//!             "remove-oldfield",
 //!             // NOTE: use a logical time here. A day after your deployment date, or the like.
-//!             threshold: chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0),
-//!             filter: doc!{"oldfield": doc!{"$exists": true}},
-//!             set: None,
-//!             unset: Some(doc!{"oldfield": ""}),
-//!         }),
+//!             chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0),
+//!             doc!{"oldfield": doc!{"$exists": true}},
+//!         ).with_unset(doc!{"oldfield": ""})),
 //!     ];
 //! }
 //!
 //! // snip ...
 //! ```
 //!
+//! As this migration `$unset`s a field, it is destructive, & `Model::sync` will refuse to execute
+//! it unless the [`ALLOW_DESTRUCTIVE_ENV`](./constant.ALLOW_DESTRUCTIVE_ENV.html) environment
+//! variable is set as `WITHER_ALLOW_DESTRUCTIVE=1` — see
+//! [`Migration::is_destructive`](./trait.Migration.html#method.is_destructive). A migration
+//! which only `$set`s fields needs no such opt in.
+//!
 //! **Remember, MongoDB is not a SQL based system.** There is no true database level schema
 //! enforcement. `IntervalMigration`s bridge this gap quite nicely.
 //!
@@ -63,21 +67,241 @@
 //! which you find yourself in need of, [please open an issue](https://github.com/thedodd/wither)!
 //!
 //! - [IntervalMigration](./struct.IntervalMigration.html)
+//! - [BatchedMigration](./struct.BatchedMigration.html)
+//! - [ReplaceFieldValueMigration](./struct.ReplaceFieldValueMigration.html)
+//! - [StreamingTransformMigration](./struct.StreamingTransformMigration.html)
 
+use std::cmp;
+use std::env;
 use std::error::Error;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use bson::{Bson, Document};
+use bson::{encode_document, Bson, Document};
+use bson::oid::ObjectId;
 use chrono;
 use mongodb::coll::Collection;
-use mongodb::coll::options::UpdateOptions;
+use mongodb::coll::options::{AggregateOptions, FindOptions, UpdateOptions, WriteModel};
 use mongodb::common::WriteConcern;
-use mongodb::error::Error::{DefaultError, WriteError};
+use mongodb::db::ThreadedDatabase;
+use mongodb::error::Error::{BulkWriteError, DefaultError, OIDError, OperationError, WriteError};
 use mongodb::error::Result;
 
+use error::DUPLICATE_KEY_CODE;
+
+/// The name of the collection used to track executed migrations, within each model's database.
+pub const MIGRATIONS_COLLECTION: &str = "wither_migrations";
+
+/// The name of the collection holding the advisory locks taken while migrating, within each
+/// model's database. Each lock is keyed by the namespace of the collection being migrated.
+pub const MIGRATION_LOCKS_COLLECTION: &str = "wither_migration_locks";
+
+/// The default name of the collection to which migration executions are audited, within each
+/// model's database. See `Model::audit_migrations`.
+pub const MIGRATION_AUDIT_COLLECTION: &str = "wither_migration_audit";
+
+/// The outcome of executing a migration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationResult {
+    /// The name of the executed migration.
+    pub name: String,
+
+    /// The number of documents matched by the migration.
+    pub matched_count: i32,
+
+    /// The number of documents modified by the migration.
+    pub modified_count: i32,
+
+    /// How long the migration took to execute.
+    ///
+    /// Migrations need not measure this themselves, as it is set by
+    /// [`Model::sync`](../model/trait.Model.html#method.sync) around each `execute` call.
+    pub duration: Duration,
+}
+
+impl MigrationResult {
+    /// Create a result for a migration which did not touch any documents.
+    pub fn noop(name: &str) -> MigrationResult {
+        MigrationResult{name: name.to_owned(), matched_count: 0, modified_count: 0, duration: Duration::from_secs(0)}
+    }
+
+    /// Whether the migration is a no-op, having matched no documents.
+    pub fn is_noop(&self) -> bool {
+        self.matched_count == 0
+    }
+}
+
 /// A trait definition for objects which can be used to manage schema migrations.
 pub trait Migration {
     /// The function which is to execute this migration.
-    fn execute<'c>(&self, coll: &'c Collection) -> Result<()>;
+    ///
+    /// Implementations should return a description of what the migration did, which
+    /// [`Model::sync`](../model/trait.Model.html#method.sync) will then log.
+    fn execute<'c>(&self, coll: &'c Collection) -> Result<MigrationResult>;
+
+    /// The name of this migration. Must be non-empty & unique per collection.
+    ///
+    /// [`Model::sync`](../model/trait.Model.html#method.sync) returns an error, before executing
+    /// any migration, when a model declares a migration with an empty name or two migrations
+    /// sharing a name, as their tracking records would collide.
+    fn name(&self) -> &str;
+
+    /// A checksum of this migration's definition. Defaults to `None`.
+    ///
+    /// When present, the checksum is stored in the
+    /// [`MIGRATIONS_COLLECTION`](./constant.MIGRATIONS_COLLECTION.html) the first time the
+    /// migration is executed. If a migration having the same name later presents a different
+    /// checksum, a warning will be logged, as editing the body of a migration after it has been
+    /// executed may cause instances to diverge. Give the edited migration a new name instead.
+    fn checksum(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether this migration must be executed within a transaction. Defaults to `false`.
+    ///
+    /// Transactions are subject to size & time limits on the server, so this is only appropriate
+    /// for smaller, targeted migrations which must be all-or-nothing. Large scale migrations
+    /// should remain non-transactional & idempotent.
+    ///
+    /// **NOTE:** the underlying driver does not yet expose sessions or transactions. Until it
+    /// does, [`Model::sync`](../model/trait.Model.html#method.sync) will refuse to execute a
+    /// migration which returns `true` here, rather than silently executing it without the
+    /// guarantees it asked for.
+    fn transactional(&self) -> bool {
+        false
+    }
+
+    /// The range of the collection's documents which this process is responsible for migrating,
+    /// as a filter which is ANDed into the migration's own filter. Defaults to `None`, migrating
+    /// all matching documents.
+    ///
+    /// In a horizontally partitioned deployment, where each process owns a range of shard keys,
+    /// this allows a very large migration to be parallelized across the fleet, with each process
+    /// only migrating its own range — such as `{"tenant_id": {"$gte": 0, "$lt": 1000}}`. The
+    /// range is supplied per process, typically from its configuration, & is not part of the
+    /// migration's checksum. Coordinating the ranges is the responsibility of the deployment:
+    /// the ranges of all processes must together cover every document, or some will never be
+    /// migrated, & each range must be owned by a process which runs before the migration's
+    /// threshold passes. Overlapping ranges are safe, as migrations must be idempotent, but
+    /// duplicate the work.
+    fn shard_range(&self) -> Option<Document> {
+        None
+    }
+
+    /// The fraction of matching documents, within `(0, 1]`, which this migration should touch
+    /// per execution, for a staged rollout of a risky migration. Defaults to `None`, touching
+    /// every matching document.
+    ///
+    /// When set, each execution picks a fresh random sample of about this fraction of the
+    /// documents matching its filter, via `$sample`, & only migrates those. Convergence
+    /// relies upon the filter excluding documents which have already been migrated: each boot
+    /// then migrates about this fraction of the *remaining* documents, so the whole set
+    /// converges over repeated boots — with a fraction of `0.1`, about 65% after 10 boots.
+    /// Raise the fraction over successive deploys to ramp up, & ensure the threshold leaves
+    /// room for enough boots. As documents may be sampled again, the update must be idempotent.
+    /// The sampled IDs are sent to the server as a single `$in` list, so keep the sample well
+    /// below a million documents per execution.
+    fn sample_fraction(&self) -> Option<f64> {
+        None
+    }
+
+    /// Whether this migration destroys data, such as by removing fields or documents.
+    /// Defaults to `false`.
+    ///
+    /// [`Model::sync`](../model/trait.Model.html#method.sync) will refuse to execute a
+    /// destructive migration unless the [`ALLOW_DESTRUCTIVE_ENV`](./constant.ALLOW_DESTRUCTIVE_ENV.html)
+    /// environment variable is set to `1`, so that such a migration never runs silently during a
+    /// routine deploy. Of the built in migration types, an `IntervalMigration` having an `unset`
+    /// document is destructive until its threshold has passed; a `ReplaceFieldValueMigration`
+    /// is not.
+    fn is_destructive(&self) -> bool {
+        false
+    }
+
+    /// The environments in which this migration should execute, such as `production` for a
+    /// production-only data fix. Defaults to `None`, meaning all environments.
+    ///
+    /// [`Model::sync`](../model/trait.Model.html#method.sync) skips the migration when the
+    /// current environment is not among these, or when there is no current environment. The
+    /// current environment is given by the model's
+    /// [`environment`](../model/trait.Model.html#method.environment), which by default reads the
+    /// `WITHER_ENV` environment variable.
+    fn environments(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// A serializable description of this migration, for review as part of a
+    /// [migration manifest](../model/trait.Model.html#method.migration_manifest).
+    ///
+    /// Defaults to describing only what the `Migration` trait itself exposes: the name, checksum,
+    /// destructiveness & environments. The built in migration types also describe their
+    /// threshold & update documents. Overriding implementations may start from
+    /// [`base_spec`](./fn.base_spec.html), as in `MigrationSpec{threshold, ..base_spec(self)}`.
+    fn spec(&self) -> MigrationSpec {
+        base_spec(self)
+    }
+}
+
+/// A serializable description of a migration, so that pending schema changes can be reviewed
+/// as data, such as in a pull request or deploy artifact.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationSpec {
+    /// The name of the migration.
+    pub name: String,
+
+    /// The checksum of the migration's definition, if it has one.
+    pub checksum: Option<String>,
+
+    /// Whether the migration is currently destructive.
+    pub destructive: bool,
+
+    /// The environments in which the migration executes, or `None` for all environments.
+    pub environments: Option<Vec<String>>,
+
+    /// The threshold after which the migration no longer executes, as an RFC 3339 datetime.
+    pub threshold: Option<String>,
+
+    /// The filter selecting the documents to migrate.
+    pub filter: Option<Document>,
+
+    /// The `$set` document of the migration's update.
+    pub set: Option<Document>,
+
+    /// The `$unset` document of the migration's update.
+    pub unset: Option<Document>,
+}
+
+/// The spec of the given migration as described by the `Migration` trait alone, for implementations
+/// overriding [`Migration::spec`](./trait.Migration.html#method.spec) to extend.
+pub fn base_spec<M: Migration + ?Sized>(migration: &M) -> MigrationSpec {
+    MigrationSpec{
+        name: migration.name().to_owned(),
+        checksum: migration.checksum(),
+        destructive: migration.is_destructive(),
+        environments: migration.environments(),
+        threshold: None,
+        filter: None,
+        set: None,
+        unset: None,
+    }
+}
+
+/// Whether a migration declaring the given environments should execute in the current one.
+pub(crate) fn runs_in_environment(environments: Option<Vec<String>>, current: Option<&str>) -> bool {
+    match (environments, current) {
+        (None, _) => true,
+        (Some(environments), Some(current)) => environments.iter().any(|env| env == current),
+        (Some(_), None) => false,
+    }
+}
+
+/// The environment variable which must be set to `1` to allow destructive migrations to execute.
+pub const ALLOW_DESTRUCTIVE_ENV: &str = "WITHER_ALLOW_DESTRUCTIVE";
+
+/// Whether destructive migrations have been explicitly allowed via the environment.
+pub(crate) fn destructive_migrations_allowed() -> bool {
+    env::var(ALLOW_DESTRUCTIVE_ENV).map(|val| val == "1").unwrap_or(false)
 }
 
 /// A migration type which allows execution until the specifed `threshold` date. Then will no-op.
@@ -106,15 +330,127 @@ pub struct IntervalMigration {
 
     /// The document to be used for the `$unset` operation of the update.
     pub unset: Option<Document>,
+
+    /// The range of documents this process is responsible for migrating, if partitioned.
+    ///
+    /// See [`Migration::shard_range`](./trait.Migration.html#method.shard_range).
+    pub shard_range: Option<Document>,
+
+    /// The fraction of matching documents to migrate per execution, for a staged rollout.
+    ///
+    /// See [`Migration::sample_fraction`](./trait.Migration.html#method.sample_fraction).
+    pub sample_fraction: Option<f64>,
+
+    /// The environments in which to execute this migration, or `None` for all environments.
+    ///
+    /// See [`Migration::environments`](./trait.Migration.html#method.environments).
+    pub environments: Option<Vec<String>>,
+}
+
+impl IntervalMigration {
+    /// Create a migration of the documents matching `filter` until the given `threshold`,
+    /// with no update & every other option unset.
+    ///
+    /// Give the update via one or both of [`with_set`](#method.with_set) &
+    /// [`with_unset`](#method.with_unset). Constructing a migration this way, rather than as a
+    /// struct literal, is unaffected by options added in future releases.
+    pub fn new(name: &str, threshold: chrono::DateTime<chrono::Utc>, filter: Document) -> IntervalMigration {
+        IntervalMigration{
+            name: name.to_owned(),
+            threshold,
+            filter,
+            set: None,
+            unset: None,
+            shard_range: None,
+            sample_fraction: None,
+            environments: None,
+        }
+    }
+
+    /// Use the given document for the `$set` operation of the update.
+    pub fn with_set(mut self, set: Document) -> IntervalMigration {
+        self.set = Some(set);
+        self
+    }
+
+    /// Use the given document for the `$unset` operation of the update.
+    pub fn with_unset(mut self, unset: Document) -> IntervalMigration {
+        self.unset = Some(unset);
+        self
+    }
+
+    /// Only migrate the given range of documents. See [`shard_range`](#structfield.shard_range).
+    pub fn with_shard_range(mut self, shard_range: Document) -> IntervalMigration {
+        self.shard_range = Some(shard_range);
+        self
+    }
+
+    /// Only migrate the given fraction of matching documents per execution. See
+    /// [`sample_fraction`](#structfield.sample_fraction).
+    pub fn with_sample_fraction(mut self, sample_fraction: f64) -> IntervalMigration {
+        self.sample_fraction = Some(sample_fraction);
+        self
+    }
+
+    /// Only execute in the given environments. See [`environments`](#structfield.environments).
+    pub fn with_environments(mut self, environments: Vec<String>) -> IntervalMigration {
+        self.environments = Some(environments);
+        self
+    }
 }
 
 impl Migration for IntervalMigration {
-    fn execute<'c>(&self, coll: &'c Collection) -> Result<()> {
-        info!("Executing migration '{}' against '{}'.", &self.name, coll.namespace);
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A checksum of this migration's `filter`, `set` & `unset` documents.
+    ///
+    /// The `threshold` is not included, as extending a migration's threshold is routine.
+    fn checksum(&self) -> Option<String> {
+        let definition = doc!{
+            "filter": self.filter.clone(),
+            "set": self.set.clone().map(Bson::Document).unwrap_or(Bson::Null),
+            "unset": self.unset.clone().map(Bson::Document).unwrap_or(Bson::Null),
+        };
+        let mut buf = vec![];
+        match encode_document(&mut buf, &definition) {
+            Ok(_) => Some(format!("{:016x}", fnv1a64(&buf))),
+            Err(_) => None,
+        }
+    }
+
+    /// Destructive when an `unset` document is given, until the threshold has passed.
+    fn is_destructive(&self) -> bool {
+        self.unset.is_some() && chrono::Utc::now() <= self.threshold
+    }
+
+    fn shard_range(&self) -> Option<Document> {
+        self.shard_range.clone()
+    }
+
+    fn sample_fraction(&self) -> Option<f64> {
+        self.sample_fraction
+    }
+
+    fn environments(&self) -> Option<Vec<String>> {
+        self.environments.clone()
+    }
+
+    fn spec(&self) -> MigrationSpec {
+        MigrationSpec{
+            threshold: Some(self.threshold.to_rfc3339()),
+            filter: Some(self.filter.clone()),
+            set: self.set.clone(),
+            unset: self.unset.clone(),
+            ..base_spec(self)
+        }
+    }
+
+    fn execute<'c>(&self, coll: &'c Collection) -> Result<MigrationResult> {
         // If the migrations threshold has been passed, then no-op.
         if chrono::Utc::now() > self.threshold {
-            info!("Successfully executed migration '{}' against '{}'. No-op.", &self.name, coll.namespace);
-            return Ok(());
+            return Ok(MigrationResult::noop(&self.name));
         };
 
         // Build update document.
@@ -131,14 +467,843 @@ impl Migration for IntervalMigration {
 
         // Build up & execute the migration.
         let options = UpdateOptions{upsert: Some(false), write_concern: Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false})};
-        let res = coll.update_many(self.filter.clone(), update, Some(options))?;
+        let filter = sampled_filter(coll, scoped_filter(&self.filter, self.shard_range()), self.sample_fraction())?;
+        let res = coll.update_many(filter, update, Some(options))?;
 
         // Handle nested error condition.
         if let Some(err) = res.write_exception {
             error!("Error executing migration: {:?}", err.description());
             return Err(WriteError(err));
         }
-        info!("Successfully executed migration '{}' against '{}'. {} matched. {} modified.", &self.name, coll.namespace, res.matched_count, res.modified_count);
-        Ok(())
+        Ok(MigrationResult{name: self.name.clone(), matched_count: res.matched_count, modified_count: res.modified_count, duration: Duration::from_secs(0)})
+    }
+}
+
+/// A migration type which, like an [`IntervalMigration`](./struct.IntervalMigration.html), allows
+/// execution until the specified `threshold` date, but which updates matching documents in
+/// batches of `batch_size`.
+///
+/// Migrating a large collection with a single update can hold resources on the server for many
+/// minutes. This instead walks the matching documents in `_id` order, updating one batch at a
+/// time, & logs its progress after each batch — "processed N of ~M documents" — against a total
+/// estimated by an upfront count of the `filter`. As with all migrations, the update should be
+/// idempotent; ideally, the `filter` should exclude documents which have already been migrated.
+///
+/// After each batch, the `_id` of its last document is saved as a checkpoint in the migration's
+/// record of the [`MIGRATIONS_COLLECTION`](./constant.MIGRATIONS_COLLECTION.html), so that a
+/// migration interrupted by a deploy or crash resumes after the last completed batch, rather than
+/// rescanning from the beginning. The checkpoint is cleared once the migration completes, & is
+/// ignored if the migration's [`checksum`](./trait.Migration.html#method.checksum) has changed
+/// since it was saved. **A batch interrupted before its checkpoint was saved is processed again
+/// on resume**, so the update must be idempotent.
+pub struct BatchedMigration {
+    /// The name for this migration. Must be unique per collection.
+    pub name: String,
+
+    /// The UTC datetime when this migration should no longer execute.
+    pub threshold: chrono::DateTime<chrono::Utc>,
+
+    /// The filter to be used for selecting the documents to update.
+    pub filter: Document,
+
+    /// The document to be used for the `$set` operation of the update.
+    pub set: Option<Document>,
+
+    /// The document to be used for the `$unset` operation of the update.
+    pub unset: Option<Document>,
+
+    /// The range of documents this process is responsible for migrating, if partitioned.
+    ///
+    /// See [`Migration::shard_range`](./trait.Migration.html#method.shard_range).
+    pub shard_range: Option<Document>,
+
+    /// The fraction of matching documents to migrate per execution, for a staged rollout.
+    ///
+    /// See [`Migration::sample_fraction`](./trait.Migration.html#method.sample_fraction).
+    pub sample_fraction: Option<f64>,
+
+    /// The environments in which to execute this migration, or `None` for all environments.
+    ///
+    /// See [`Migration::environments`](./trait.Migration.html#method.environments).
+    pub environments: Option<Vec<String>>,
+
+    /// The maximum number of documents to update per batch. Must be positive.
+    pub batch_size: i64,
+}
+
+impl BatchedMigration {
+    /// Create a migration of the documents matching `filter` until the given `threshold` in
+    /// batches of `batch_size`, with no update & every other option unset.
+    ///
+    /// Give the update via one or both of [`with_set`](#method.with_set) &
+    /// [`with_unset`](#method.with_unset). Constructing a migration this way, rather than as a
+    /// struct literal, is unaffected by options added in future releases.
+    pub fn new(name: &str, threshold: chrono::DateTime<chrono::Utc>, filter: Document, batch_size: i64) -> BatchedMigration {
+        BatchedMigration{
+            name: name.to_owned(),
+            threshold,
+            filter,
+            set: None,
+            unset: None,
+            shard_range: None,
+            sample_fraction: None,
+            environments: None,
+            batch_size,
+        }
+    }
+
+    /// Use the given document for the `$set` operation of the update.
+    pub fn with_set(mut self, set: Document) -> BatchedMigration {
+        self.set = Some(set);
+        self
+    }
+
+    /// Use the given document for the `$unset` operation of the update.
+    pub fn with_unset(mut self, unset: Document) -> BatchedMigration {
+        self.unset = Some(unset);
+        self
+    }
+
+    /// Only migrate the given range of documents. See [`shard_range`](#structfield.shard_range).
+    pub fn with_shard_range(mut self, shard_range: Document) -> BatchedMigration {
+        self.shard_range = Some(shard_range);
+        self
+    }
+
+    /// Only migrate the given fraction of matching documents per execution. See
+    /// [`sample_fraction`](#structfield.sample_fraction).
+    pub fn with_sample_fraction(mut self, sample_fraction: f64) -> BatchedMigration {
+        self.sample_fraction = Some(sample_fraction);
+        self
+    }
+
+    /// Only execute in the given environments. See [`environments`](#structfield.environments).
+    pub fn with_environments(mut self, environments: Vec<String>) -> BatchedMigration {
+        self.environments = Some(environments);
+        self
+    }
+}
+
+impl Migration for BatchedMigration {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A checksum of this migration's `filter`, `set` & `unset` documents.
+    ///
+    /// Neither the `threshold` nor the `batch_size` are included, as tuning either is routine.
+    fn checksum(&self) -> Option<String> {
+        let definition = doc!{
+            "filter": self.filter.clone(),
+            "set": self.set.clone().map(Bson::Document).unwrap_or(Bson::Null),
+            "unset": self.unset.clone().map(Bson::Document).unwrap_or(Bson::Null),
+        };
+        let mut buf = vec![];
+        match encode_document(&mut buf, &definition) {
+            Ok(_) => Some(format!("{:016x}", fnv1a64(&buf))),
+            Err(_) => None,
+        }
+    }
+
+    /// Destructive when an `unset` document is given, until the threshold has passed.
+    fn is_destructive(&self) -> bool {
+        self.unset.is_some() && chrono::Utc::now() <= self.threshold
+    }
+
+    fn shard_range(&self) -> Option<Document> {
+        self.shard_range.clone()
+    }
+
+    fn sample_fraction(&self) -> Option<f64> {
+        self.sample_fraction
+    }
+
+    fn environments(&self) -> Option<Vec<String>> {
+        self.environments.clone()
+    }
+
+    fn spec(&self) -> MigrationSpec {
+        MigrationSpec{
+            threshold: Some(self.threshold.to_rfc3339()),
+            filter: Some(self.filter.clone()),
+            set: self.set.clone(),
+            unset: self.unset.clone(),
+            ..base_spec(self)
+        }
+    }
+
+    fn execute<'c>(&self, coll: &'c Collection) -> Result<MigrationResult> {
+        // If the migrations threshold has been passed, then no-op.
+        if chrono::Utc::now() > self.threshold {
+            return Ok(MigrationResult::noop(&self.name));
+        };
+
+        // Build update document.
+        if self.batch_size < 1 {
+            return Err(DefaultError(format!("Batch size must be positive, got {}.", self.batch_size)));
+        }
+        let mut update = doc!{};
+        if self.set.is_none() && self.unset.is_none() {
+            return Err(DefaultError(String::from("One of '$set' or '$unset' must be specified.")));
+        };
+        if let Some(set) = self.set.clone() {
+            update.insert_bson(String::from("$set"), Bson::from(set));
+        }
+        if let Some(unset) = self.unset.clone() {
+            update.insert_bson(String::from("$unset"), Bson::from(unset));
+        }
+
+        // Resume after the checkpoint of an interrupted execution, if any.
+        let checksum = self.checksum();
+        let mut last_id = load_checkpoint(coll, &self.name, checksum.as_ref().map(String::as_str))?;
+        if let Some(ref last_id) = last_id {
+            info!("Resuming batched migration '{}' against '{}' after _id {}.", self.name, coll.namespace, last_id);
+        }
+        let after = |filter: &Document, last_id: &Option<Bson>| match last_id {
+            &Some(ref last_id) => doc!{"$and": [filter.clone(), {"_id": {"$gt": last_id.clone()}}]},
+            &None => filter.clone(),
+        };
+
+        // Estimate the total upfront, for progress reporting.
+        let base_filter = sampled_filter(coll, scoped_filter(&self.filter, self.shard_range()), self.sample_fraction())?;
+        let total = coll.count(Some(after(&base_filter, &last_id)), None)?;
+        info!("Executing batched migration '{}' against '{}', ~{} documents to process.", self.name, coll.namespace, total);
+
+        let mut output = MigrationResult::noop(&self.name);
+        let mut processed: i64 = 0;
+        loop {
+            // Fetch the IDs of the next batch, resuming after the last batch.
+            let filter = after(&base_filter, &last_id);
+            let mut options = FindOptions::new();
+            options.sort = Some(doc!{"_id": 1});
+            options.limit = Some(self.batch_size);
+            options.projection = Some(doc!{"_id": 1});
+            let mut ids = vec![];
+            for doc in coll.find(Some(filter), Some(options))? {
+                if let Some(id) = doc?.get("_id") {
+                    ids.push(id.clone());
+                }
+            }
+            if ids.is_empty() {
+                break;
+            }
+            last_id = ids.last().cloned();
+            let batch_len = ids.len() as i64;
+
+            // Update the batch.
+            let options = UpdateOptions{upsert: Some(false), write_concern: Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false})};
+            let res = coll.update_many(doc!{"_id": {"$in": ids}}, update.clone(), Some(options))?;
+            if let Some(err) = res.write_exception {
+                error!("Error executing migration: {:?}", err.description());
+                return Err(WriteError(err));
+            }
+            output.matched_count += res.matched_count;
+            output.modified_count += res.modified_count;
+            if let Some(ref last_id) = last_id {
+                save_checkpoint(coll, &self.name, checksum.clone(), last_id)?;
+            }
+
+            processed += batch_len;
+            info!("Batched migration '{}' against '{}' processed {} of ~{} documents ({:.1}%).", self.name, coll.namespace, processed, total, progress_percent(processed, total));
+            if batch_len < self.batch_size {
+                break;
+            }
+        }
+        clear_checkpoint(coll, &self.name)?;
+        Ok(output)
+    }
+}
+
+/// Constrain the given migration filter to the given shard range, if any.
+fn scoped_filter(filter: &Document, shard_range: Option<Document>) -> Document {
+    match shard_range {
+        Some(range) => doc!{"$and": [filter.clone(), range]},
+        None => filter.clone(),
+    }
+}
+
+/// Constrain the given migration filter to a random sample of the given fraction of its
+/// matching documents, if any.
+fn sampled_filter<'c>(coll: &'c Collection, filter: Document, fraction: Option<f64>) -> Result<Document> {
+    let fraction = match fraction {
+        Some(fraction) => fraction,
+        None => return Ok(filter),
+    };
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(DefaultError(format!("Sample fraction must be within (0, 1], got {}.", fraction)));
+    }
+    let count = coll.count(Some(filter.clone()), None)?;
+    let size = sample_size(count, fraction);
+    if size == 0 {
+        return Ok(filter);
+    }
+
+    let pipeline = vec![doc!{"$match": filter.clone()}, doc!{"$sample": {"size": size}}, doc!{"$project": {"_id": 1}}];
+    let mut ids = vec![];
+    for doc in coll.aggregate(pipeline, Some(AggregateOptions::new()))? {
+        if let Some(id) = doc?.get("_id") {
+            ids.push(id.clone());
+        }
+    }
+    Ok(doc!{"$and": [filter, {"_id": {"$in": ids}}]})
+}
+
+/// The number of documents to sample of `count` documents for the given fraction, sampling at
+/// least one document whenever any exist.
+fn sample_size(count: i64, fraction: f64) -> i64 {
+    if count <= 0 {
+        return 0;
+    }
+    ((count as f64 * fraction).ceil() as i64).max(1).min(count)
+}
+
+/// The percentage of the estimated `total` which `processed` represents, capped at 100, as the
+/// estimate may be exceeded by concurrent writes.
+fn progress_percent(processed: i64, total: i64) -> f64 {
+    if total <= 0 {
+        return 100.0;
+    }
+    (processed as f64 / total as f64 * 100.0).min(100.0)
+}
+
+/// A migration type which replaces specific values of a single field, until the specified
+/// `threshold` date. Then will no-op.
+///
+/// Each `(from, to)` pair of `replacements` is applied as an update of all documents where
+/// `field` equals `from`, setting `field` to `to`. This is useful for converting the stored
+/// representation of a field, such as converting enum names to integer codes — see the
+/// [`int_enum`](../int_enum/index.html) module. As with all migrations, the replacements should
+/// be idempotent: no `to` value should also appear as a `from` value.
+pub struct ReplaceFieldValueMigration {
+    /// The name for this migration. Must be unique per collection.
+    pub name: String,
+
+    /// The UTC datetime when this migration should no longer execute.
+    pub threshold: chrono::DateTime<chrono::Utc>,
+
+    /// The field whose values are to be replaced.
+    pub field: String,
+
+    /// The `(from, to)` pairs of values to be replaced.
+    pub replacements: Vec<(Bson, Bson)>,
+}
+
+impl Migration for ReplaceFieldValueMigration {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A checksum of this migration's `field` & `replacements`.
+    fn checksum(&self) -> Option<String> {
+        let mut definition = doc!{"field": self.field.clone()};
+        let replacements: Vec<Bson> = self.replacements.iter()
+            .map(|&(ref from, ref to)| Bson::Array(vec![from.clone(), to.clone()]))
+            .collect();
+        definition.insert("replacements", replacements);
+        let mut buf = vec![];
+        match encode_document(&mut buf, &definition) {
+            Ok(_) => Some(format!("{:016x}", fnv1a64(&buf))),
+            Err(_) => None,
+        }
+    }
+
+    fn spec(&self) -> MigrationSpec {
+        MigrationSpec{threshold: Some(self.threshold.to_rfc3339()), ..base_spec(self)}
+    }
+
+    fn execute<'c>(&self, coll: &'c Collection) -> Result<MigrationResult> {
+        // If the migrations threshold has been passed, then no-op.
+        if chrono::Utc::now() > self.threshold {
+            return Ok(MigrationResult::noop(&self.name));
+        };
+
+        let mut output = MigrationResult::noop(&self.name);
+        for &(ref from, ref to) in self.replacements.iter() {
+            let mut filter = Document::new();
+            filter.insert_bson(self.field.clone(), from.clone());
+            let mut set = Document::new();
+            set.insert_bson(self.field.clone(), to.clone());
+
+            let options = UpdateOptions{upsert: Some(false), write_concern: Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false})};
+            let res = coll.update_many(filter, doc!{"$set": set}, Some(options))?;
+            if let Some(err) = res.write_exception {
+                error!("Error executing migration: {:?}", err.description());
+                return Err(WriteError(err));
+            }
+            output.matched_count += res.matched_count;
+            output.modified_count += res.modified_count;
+        }
+        Ok(output)
+    }
+}
+
+/// A migration type which transforms each matching document with arbitrary Rust code, until the
+/// specified `threshold` date. Then will no-op.
+///
+/// This is for per document logic which can not be expressed declaratively, such as reformatting
+/// a field's value. Matching documents are read in batches of `batch_size`, in `_id` order, &
+/// each is passed to the `transform` closure, which mutates it in place & returns whether it
+/// changed. The changed documents of each batch are then written back by ID, as a single
+/// unordered bulk write of replacements. The closure must not change a document's `_id`.
+///
+/// As with all migrations, this will be executed on every sync until its threshold has passed,
+/// potentially by many instances at once, so **the transform must be idempotent**: transforming
+/// an already transformed document must return `false`, leaving it untouched. If the `filter`
+/// also excludes transformed documents, then a migration interrupted part way through simply
+/// resumes where it left off on the next sync, & converged collections are not rescanned.
+///
+/// ```rust,ignore
+/// Box::new(wither::StreamingTransformMigration{
+///     name: String::from("lowercase-emails"),
+///     threshold: chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0),
+///     filter: doc!{"email": {"$regex": "[A-Z]"}},
+///     batch_size: 500,
+///     transform: Box::new(|doc| {
+///         let email = doc.get_str("email").unwrap_or("").to_owned();
+///         let lower = email.to_lowercase();
+///         if lower == email { return Ok(false); }
+///         doc.insert("email", lower);
+///         Ok(true)
+///     }),
+/// })
+/// ```
+///
+/// As a closure can not be checksummed, this migration has no checksum.
+pub struct StreamingTransformMigration {
+    /// The name for this migration. Must be unique per collection.
+    pub name: String,
+
+    /// The UTC datetime when this migration should no longer execute.
+    pub threshold: chrono::DateTime<chrono::Utc>,
+
+    /// The filter to be used for selecting the documents to transform.
+    pub filter: Document,
+
+    /// The maximum number of documents to read & write per batch. Must be positive.
+    pub batch_size: i64,
+
+    /// The transform to apply to each document, returning whether the document changed.
+    pub transform: Box<Fn(&mut Document) -> Result<bool>>,
+}
+
+impl Migration for StreamingTransformMigration {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn spec(&self) -> MigrationSpec {
+        MigrationSpec{threshold: Some(self.threshold.to_rfc3339()), filter: Some(self.filter.clone()), ..base_spec(self)}
+    }
+
+    fn execute<'c>(&self, coll: &'c Collection) -> Result<MigrationResult> {
+        // If the migrations threshold has been passed, then no-op.
+        if chrono::Utc::now() > self.threshold {
+            return Ok(MigrationResult::noop(&self.name));
+        };
+        if self.batch_size < 1 {
+            return Err(DefaultError(format!("Batch size must be positive, got {}.", self.batch_size)));
+        }
+
+        let mut output = MigrationResult::noop(&self.name);
+        let mut last_id: Option<Bson> = None;
+        loop {
+            // Fetch the next batch, resuming after the last batch.
+            let mut filter = self.filter.clone();
+            if let Some(last_id) = last_id.clone() {
+                filter = doc!{"$and": [filter, {"_id": {"$gt": last_id}}]};
+            }
+            let mut options = FindOptions::new();
+            options.sort = Some(doc!{"_id": 1});
+            options.limit = Some(self.batch_size);
+            let mut batch_len = 0;
+            let mut writes = vec![];
+            for doc in coll.find(Some(filter), Some(options))? {
+                let mut doc = doc?;
+                batch_len += 1;
+                let id = match doc.get("_id") {
+                    Some(id) => id.clone(),
+                    None => continue,
+                };
+                last_id = Some(id.clone());
+                output.matched_count += 1;
+                if (self.transform)(&mut doc)? {
+                    writes.push(WriteModel::ReplaceOne{filter: doc!{"_id": id}, replacement: doc, upsert: Some(false)});
+                }
+            }
+            if batch_len == 0 {
+                break;
+            }
+
+            // Write back the changed documents of the batch.
+            if !writes.is_empty() {
+                let res = coll.bulk_write(writes, false);
+                if let Some(err) = res.bulk_write_exception {
+                    error!("Error executing migration: {:?}", err.description());
+                    return Err(BulkWriteError(err));
+                }
+                output.modified_count += res.modified_count;
+            }
+            if batch_len < self.batch_size {
+                break;
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// The `_id` of the tracking record of the given collection's migration having the given name.
+fn tracking_id<'c>(coll: &'c Collection, name: &str) -> Document {
+    doc!{"namespace": coll.namespace.clone(), "name": name}
+}
+
+/// Delete the tracking records of all migrations of the given collection, including their
+/// recorded checksums & checkpoints, returning the number deleted.
+pub(crate) fn clear_migration_tracking<'c>(coll: &'c Collection) -> Result<i32> {
+    let tracking = coll.db.collection(MIGRATIONS_COLLECTION);
+    let res = tracking.delete_many(doc!{"_id.namespace": coll.namespace.clone()}, None)?;
+    Ok(res.deleted_count)
+}
+
+/// The field of a tracking record holding the checkpoint of an interrupted `BatchedMigration`.
+const CHECKPOINT_FIELD: &str = "checkpoint";
+
+/// Load the `_id` after which the named migration of the given collection should resume, if it
+/// was interrupted. Checkpoints saved under a different checksum are ignored.
+fn load_checkpoint<'c>(coll: &'c Collection, name: &str, checksum: Option<&str>) -> Result<Option<Bson>> {
+    let tracking = coll.db.collection(MIGRATIONS_COLLECTION);
+    let record = match tracking.find_one(Some(doc!{"_id": tracking_id(coll, name)}), None)? {
+        Some(record) => record,
+        None => return Ok(None),
+    };
+    let checkpoint = match record.get_document(CHECKPOINT_FIELD) {
+        Ok(checkpoint) => checkpoint,
+        Err(_) => return Ok(None),
+    };
+    if checkpoint.get_str("checksum").ok() != checksum {
+        warn!("Discarding checkpoint of migration '{}' against '{}', as the migration has changed since it was saved.", name, coll.namespace);
+        return Ok(None);
+    }
+    Ok(checkpoint.get("last_id").cloned())
+}
+
+/// Save the `_id` of the last document processed by the named migration of the given collection.
+fn save_checkpoint<'c>(coll: &'c Collection, name: &str, checksum: Option<String>, last_id: &Bson) -> Result<()> {
+    let mut checkpoint = doc!{
+        "last_id": last_id.clone(),
+        "checksum": checksum.map(Bson::String).unwrap_or(Bson::Null),
+        "saved_at": Bson::UtcDatetime(chrono::Utc::now()),
+    };
+    checkpoint.insert("instance_id", instance_id());
+    let mut set = Document::new();
+    set.insert(CHECKPOINT_FIELD, checkpoint.clone());
+    let mut update = doc!{"$set": set};
+    // The checkpoint may create the record, which would otherwise be given its checksum by `track_migration`.
+    if let Ok(checksum) = checkpoint.get_str("checksum") {
+        update.insert("$setOnInsert", doc!{"checksum": checksum});
+    }
+    write_tracking_record(coll, name, update, true)
+}
+
+/// Clear the checkpoint of the named migration of the given collection, as it has completed.
+fn clear_checkpoint<'c>(coll: &'c Collection, name: &str) -> Result<()> {
+    let mut unset = Document::new();
+    unset.insert(CHECKPOINT_FIELD, "");
+    write_tracking_record(coll, name, doc!{"$unset": unset}, false)
+}
+
+/// Apply the given update to the tracking record of the named migration of the given collection,
+/// creating the record if it does not exist & `upsert` is set.
+fn write_tracking_record<'c>(coll: &'c Collection, name: &str, update: Document, upsert: bool) -> Result<()> {
+    let tracking = coll.db.collection(MIGRATIONS_COLLECTION);
+    let options = UpdateOptions{upsert: Some(upsert), write_concern: Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false})};
+    let res = tracking.update_one(doc!{"_id": tracking_id(coll, name)}, update, Some(options))?;
+    if let Some(err) = res.write_exception {
+        error!("Error tracking migration: {:?}", err.description());
+        return Err(WriteError(err));
+    }
+    Ok(())
+}
+
+/// Warn of each of the given migrations whose checksum differs from the one first recorded for it,
+/// ahead of executing them.
+pub(crate) fn verify_migration_checksums<'c>(coll: &'c Collection, migrations: &[Box<Migration>]) -> Result<()> {
+    let tracking = coll.db.collection(MIGRATIONS_COLLECTION);
+    for migration in migrations {
+        let current = match migration.checksum() {
+            Some(current) => current,
+            None => continue,
+        };
+        if let Some(record) = tracking.find_one(Some(doc!{"_id": tracking_id(coll, migration.name())}), None)? {
+            match record.get_str("checksum").ok() {
+                Some(recorded) if recorded != current => {
+                    warn!("Migration '{}' against '{}' has changed since it was first executed. Recorded checksum '{}', current checksum '{}'. Edited migrations should be given a new name.", migration.name(), coll.namespace, recorded, current);
+                },
+                _ => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Record the execution of the given migration in its tracking record.
+///
+/// The migration's checksum is only recorded on its first execution — see
+/// [`verify_migration_checksums`](./fn.verify_migration_checksums.html).
+pub(crate) fn track_migration<'c>(coll: &'c Collection, migration: &Migration, result: &MigrationResult) -> Result<()> {
+    let tracking = coll.db.collection(MIGRATIONS_COLLECTION);
+    let id = tracking_id(coll, migration.name());
+    let checksum = migration.checksum();
+
+    // Record this execution, along with its cost. The checksum is only set on the first execution.
+    let mut update = doc!{"$set": {
+        "last_executed_at": Bson::UtcDatetime(chrono::Utc::now()),
+        "last_duration_ms": duration_ms(result.duration),
+        "last_matched_count": result.matched_count,
+        "last_modified_count": result.modified_count,
+    }};
+    if let Some(checksum) = checksum {
+        update.insert("$setOnInsert", doc!{"checksum": checksum});
+    }
+    let options = UpdateOptions{upsert: Some(true), write_concern: Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false})};
+    let res = tracking.update_one(doc!{"_id": id}, update, Some(options))?;
+    if let Some(err) = res.write_exception {
+        error!("Error tracking migration: {:?}", err.description());
+        return Err(WriteError(err));
+    }
+    Ok(())
+}
+
+/// Append an audit record of the given migration execution to the given audit collection.
+///
+/// The record holds the migration's name & namespace, its matched & modified counts, its
+/// duration, when it finished & the [`instance_id`](./fn.instance_id.html) of the executing
+/// process.
+pub(crate) fn audit_migration<'c>(coll: &'c Collection, audit_collection: &str, result: &MigrationResult) -> Result<()> {
+    let audit = coll.db.collection(audit_collection);
+    let record = doc!{
+        "name": result.name.clone(),
+        "namespace": coll.namespace.clone(),
+        "matched_count": result.matched_count,
+        "modified_count": result.modified_count,
+        "duration_ms": duration_ms(result.duration),
+        "executed_at": Bson::UtcDatetime(chrono::Utc::now()),
+        "instance_id": instance_id(),
+    };
+    let res = audit.insert_one(record, Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false}))?;
+    if let Some(err) = res.write_exception {
+        error!("Error auditing migration: {:?}", err.description());
+        return Err(WriteError(err));
+    }
+    Ok(())
+}
+
+/// An identifier of the current process, recorded in migration audit records: the value of the
+/// `HOSTNAME` environment variable, if set, & the process ID, e.g. `web-1:4242`.
+pub fn instance_id() -> String {
+    let host = env::var("HOSTNAME").unwrap_or_else(|_| String::from("unknown"));
+    format!("{}:{}", host, process::id())
+}
+
+/// How often, in milliseconds, a held migration lock is checked for release while waiting to
+/// acquire it.
+const MIGRATION_LOCK_POLL_INTERVAL_MS: u64 = 1000;
+
+/// Acquire the advisory migration lock of the given collection, returning the token which must
+/// be given to release it.
+///
+/// A lock which is held is waited for, polling until it is released or expires, at most for the
+/// given timeout. A held lock which will not expire within the timeout causes an error to be
+/// returned straight away, as does one still held once the timeout has passed. A lock which has
+/// expired — such as one left behind by a crashed instance — is taken over.
+pub(crate) fn acquire_migration_lock<'c>(coll: &'c Collection, timeout: Duration) -> Result<String> {
+    let held = || DefaultError(format!("Migrations against '{}' are locked by another instance. If that instance is known to be dead, the lock may be cleared with `Model::force_unlock_migrations`.", coll.namespace));
+    let deadline = Instant::now() + timeout;
+    let wait_until = chrono::Utc::now() + chrono::Duration::milliseconds(duration_ms(timeout));
+    let mut waiting = false;
+    loop {
+        if let Some(token) = try_acquire_migration_lock(coll, timeout)? {
+            return Ok(token);
+        }
+        let now = Instant::now();
+        if now >= deadline || migration_lock_expires_at(coll)?.map(|expires_at| expires_at > wait_until).unwrap_or(false) {
+            return Err(held());
+        }
+        if !waiting {
+            info!("Migrations against '{}' are locked by another instance. Waiting for the lock to be released.", coll.namespace);
+            waiting = true;
+        }
+        thread::sleep(cmp::min(Duration::from_millis(MIGRATION_LOCK_POLL_INTERVAL_MS), deadline - now));
+    }
+}
+
+/// Attempt to acquire the advisory migration lock of the given collection, returning the token
+/// which must be given to release it, or `None` if the lock is held & has not yet expired.
+fn try_acquire_migration_lock<'c>(coll: &'c Collection, timeout: Duration) -> Result<Option<String>> {
+    let locks = coll.db.collection(MIGRATION_LOCKS_COLLECTION);
+    let token = ObjectId::new().map_err(OIDError)?.to_hex();
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::milliseconds(duration_ms(timeout));
+
+    // Only an expired lock matches, so a held lock makes the upsert collide on its `_id`.
+    let filter = doc!{"_id": coll.namespace.clone(), "expires_at": doc!{"$lte": Bson::UtcDatetime(now)}};
+    let update = doc!{"$set": {
+        "token": token.clone(),
+        "acquired_at": Bson::UtcDatetime(now),
+        "expires_at": Bson::UtcDatetime(expires_at),
+    }};
+    let options = UpdateOptions{upsert: Some(true), write_concern: Some(WriteConcern{w: 1, w_timeout: 0, j: true, fsync: false})};
+    let res = match locks.update_one(filter, update, Some(options)) {
+        Ok(res) => res,
+        Err(OperationError(ref msg)) if msg.contains("E11000") => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if let Some(err) = res.write_exception {
+        if err.write_error.as_ref().map(|err| err.code) == Some(DUPLICATE_KEY_CODE) {
+            return Ok(None);
+        }
+        error!("Error acquiring migration lock: {:?}", err.description());
+        return Err(WriteError(err));
+    }
+    Ok(Some(token))
+}
+
+/// Get when the advisory migration lock of the given collection expires, if it is held.
+fn migration_lock_expires_at<'c>(coll: &'c Collection) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    let locks = coll.db.collection(MIGRATION_LOCKS_COLLECTION);
+    Ok(locks.find_one(Some(doc!{"_id": coll.namespace.clone()}), None)?
+        .and_then(|lock| lock.get_utc_datetime("expires_at").ok().cloned()))
+}
+
+/// Release the advisory migration lock of the given collection, if it is still held under the
+/// given token.
+pub(crate) fn release_migration_lock<'c>(coll: &'c Collection, token: &str) -> Result<()> {
+    let locks = coll.db.collection(MIGRATION_LOCKS_COLLECTION);
+    locks.delete_one(doc!{"_id": coll.namespace.clone(), "token": token}, None)?;
+    Ok(())
+}
+
+/// Clear the advisory migration lock of the given collection, regardless of who holds it.
+///
+/// Returns `true` if a lock was cleared.
+pub(crate) fn force_migration_unlock<'c>(coll: &'c Collection) -> Result<bool> {
+    let locks = coll.db.collection(MIGRATION_LOCKS_COLLECTION);
+    let res = locks.delete_one(doc!{"_id": coll.namespace.clone()}, None)?;
+    Ok(res.deleted_count > 0)
+}
+
+/// The given duration in whole milliseconds.
+pub(crate) fn duration_ms(duration: Duration) -> i64 {
+    (duration.as_secs() * 1000) as i64 + (duration.subsec_nanos() / 1_000_000) as i64
+}
+
+/// A 64-bit FNV-1a hash of the given bytes, which is stable across platforms & releases.
+pub(crate) fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn interval_migration(set: Document) -> IntervalMigration {
+        IntervalMigration::new("test-migration", chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0), doc!{"email": doc!{"$exists": true}})
+            .with_set(set)
+    }
+
+    #[test]
+    fn batched_migration_new_leaves_options_unset() {
+        let migration = BatchedMigration::new("test-migration", chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0), doc!{}, 100)
+            .with_unset(doc!{"testfield": ""})
+            .with_environments(vec!["dev".to_string()]);
+
+        assert_eq!(migration.set, None);
+        assert_eq!(migration.unset, Some(doc!{"testfield": ""}));
+        assert_eq!(migration.shard_range, None);
+        assert_eq!(migration.sample_fraction, None);
+        assert_eq!(migration.environments, Some(vec!["dev".to_string()]));
+        assert_eq!(migration.batch_size, 100);
+    }
+
+    #[test]
+    fn interval_migration_checksum_is_stable() {
+        let first = interval_migration(doc!{"testfield": "test"}).checksum();
+        let second = interval_migration(doc!{"testfield": "test"}).checksum();
+
+        assert!(first.is_some());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn interval_migration_checksum_changes_with_definition() {
+        let first = interval_migration(doc!{"testfield": "test"}).checksum();
+        let second = interval_migration(doc!{"testfield": "changed"}).checksum();
+
+        assert!(first != second);
+    }
+
+    #[test]
+    fn scoped_filter_ands_shard_range_into_filter() {
+        let filter = doc!{"email": {"$exists": true}};
+
+        assert_eq!(scoped_filter(&filter, None), filter.clone());
+        assert_eq!(scoped_filter(&filter, Some(doc!{"tenant_id": {"$lt": 1000}})), doc!{
+            "$and": [{"email": {"$exists": true}}, {"tenant_id": {"$lt": 1000}}],
+        });
+    }
+
+    #[test]
+    fn sample_size_samples_at_least_one_document() {
+        assert_eq!(sample_size(1000, 0.1), 100);
+        assert_eq!(sample_size(5, 0.01), 1);
+        assert_eq!(sample_size(5, 1.0), 5);
+        assert_eq!(sample_size(0, 0.5), 0);
+    }
+
+    #[test]
+    fn progress_percent_is_bounded() {
+        assert_eq!(progress_percent(50, 200), 25.0);
+        assert_eq!(progress_percent(250, 200), 100.0);
+        assert_eq!(progress_percent(0, 0), 100.0);
+    }
+
+    #[test]
+    fn interval_migration_is_destructive_only_when_unsetting_before_threshold() {
+        let mut migration = interval_migration(doc!{"testfield": "test"});
+        assert!(!migration.is_destructive());
+
+        migration.unset = Some(doc!{"oldfield": ""});
+        assert!(migration.is_destructive());
+
+        migration.threshold = chrono::Utc.ymd(2000, 1, 1).and_hms(1, 0, 0);
+        assert!(!migration.is_destructive());
+    }
+
+    #[test]
+    fn runs_in_environment_matches_declared_environments() {
+        let production = Some(vec!["production".to_string()]);
+
+        assert!(runs_in_environment(None, None));
+        assert!(runs_in_environment(None, Some("dev")));
+        assert!(runs_in_environment(production.clone(), Some("production")));
+        assert!(!runs_in_environment(production.clone(), Some("dev")));
+        assert!(!runs_in_environment(production, None));
+    }
+
+    #[test]
+    fn interval_migration_spec_describes_definition() {
+        let output = interval_migration(doc!{"testfield": "test"}).spec();
+
+        assert_eq!(output.name, "test-migration".to_string());
+        assert_eq!(output.threshold, Some("2100-01-01T01:00:00+00:00".to_string()));
+        assert_eq!(output.filter, Some(doc!{"email": {"$exists": true}}));
+        assert_eq!(output.set, Some(doc!{"testfield": "test"}));
+        assert_eq!(output.unset, None);
+        assert!(output.checksum.is_some());
     }
 }
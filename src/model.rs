@@ -61,30 +61,51 @@
 //! See the documentation on the [migration](../migration/index.html) module.
 
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::RandomState;
+use std::env;
 use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::result;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use bson;
-use bson::Document;
+use bson::{Bson, Document};
 use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use log::Level;
 use mongodb::error::Error::{
     ArgumentError,
+    BulkWriteError,
     DecoderError,
     DefaultError,
     OIDError,
+    OperationError,
     ResponseError,
+    WriteError,
 };
-use mongodb::error::Result;
 use mongodb::coll::Collection;
+use mongodb::coll::results::{DeleteResult, UpdateResult};
 use mongodb::coll::options::{
+    AggregateOptions,
     CountOptions,
+    CursorType,
     FindOneAndUpdateOptions,
     FindOptions,
     IndexModel,
     IndexOptions,
+    InsertManyOptions,
     ReturnDocument,
+    UpdateOptions,
 };
-use mongodb::common::WriteConcern;
+use mongodb::CommandType;
+use mongodb::ThreadedClient;
+use mongodb::common::{ReadMode, ReadPreference, WriteConcern};
+use mongodb::cursor::Cursor;
 use mongodb::db::{
     Database,
     ThreadedDatabase,
@@ -92,13 +113,45 @@ use mongodb::db::{
 use serde::{
     Serialize,
     Deserialize,
+    Deserializer,
 };
+use serde::de::{self, DeserializeOwned, Visitor};
+#[cfg(feature = "json")]
+use serde_json::Value;
 
-use migration::Migration;
+use builder::ModelBuilder;
+use cursor::{CappedModelCursor, DocumentCursor, ModelCursor, TailableCursor};
+use error::{Result, WitherError};
+#[cfg(feature = "json")]
+use json::{document_to_json, merge_patch};
+use migration::{acquire_migration_lock, audit_migration, clear_migration_tracking, destructive_migrations_allowed, duration_ms, fnv1a64, force_migration_unlock, release_migration_lock, runs_in_environment, track_migration, verify_migration_checksums, Migration, MigrationResult, MigrationSpec, ALLOW_DESTRUCTIVE_ENV, MIGRATIONS_COLLECTION, MIGRATION_AUDIT_COLLECTION, MIGRATION_LOCKS_COLLECTION};
+use pipeline::{OutputStage, Pipeline};
+use query_cache;
+use registry::route_database;
+#[cfg(feature = "async")]
+use stream::{AggregateStream, ModelStream};
+use tenant::{self, TenantScoped};
+
+/// The environment variable naming the current environment, such as `dev` or `production`.
+///
+/// It must be set to `dev` to allow `Model::sync_fresh` to execute, & is the default source of
+/// `Model::environment`.
+pub const WITHER_ENV_VAR: &str = "WITHER_ENV";
 
 /// The name of the default index created by MongoDB.
 pub const DEFAULT_INDEX: &str = "_id";
 
+/// The default maximum number of documents per batch used by `Model::insert_many`.
+pub const DEFAULT_INSERT_CHUNK_SIZE: usize = 1000;
+
+/// The maximum number of bytes of encoded documents per batch used by `Model::insert_many`.
+///
+/// This is the size limit MongoDB places on a single message (48MB).
+pub const MAX_INSERT_CHUNK_BYTES: usize = 48_000_000;
+
+/// The field under which `Model::find_orphans` looks up each document's references.
+const ORPHAN_LOOKUP_FIELD: &str = "_wither_refs";
+
 /// A convenience function for basic index options. Everything else will default to `None`.
 pub fn basic_index_options(name: &str, background: bool, unique: Option<bool>, expire_after_seconds: Option<i32>, sparse: Option<bool>) -> IndexOptions {
     return IndexOptions{
@@ -121,6 +174,596 @@ pub fn basic_index_options(name: &str, background: bool, unique: Option<bool>, e
     };
 }
 
+/// A trait for types embedded within models which declare indexes on their own fields.
+///
+/// Index keys are declared relative to the embedded type. Use
+/// [`embedded_indexes`](./fn.embedded_indexes.html) from the parent's `Model::indexes`
+/// implementation in order to lift them onto the parent with the correct dotted prefix.
+///
+/// ```rust,ignore
+/// impl wither::model::EmbeddedIndexes for Address {
+///     fn indexes() -> Vec<IndexModel> {
+///         vec![IndexModel{
+///             keys: doc!{"city": 1},
+///             options: wither::basic_index_options("city", true, None, None, None),
+///         }]
+///     }
+/// }
+///
+/// // snip ...
+///
+/// // Within the parent's `Model` impl, where the `Address` is stored under `address`.
+/// fn indexes() -> Vec<IndexModel> {
+///     wither::model::embedded_indexes::<Address>("address")
+/// }
+/// ```
+pub trait EmbeddedIndexes {
+    /// Get the vector of index models for this type, with keys relative to this type.
+    fn indexes() -> Vec<IndexModel>;
+}
+
+/// Get the indexes declared by the embedded type `T`, with keys prefixed by the given field path.
+///
+/// Index names & text index weights are prefixed as well, in order to avoid collisions with the
+/// parent's own indexes. Nested embedded types compose naturally by prefixing in their own
+/// `EmbeddedIndexes` implementation.
+pub fn embedded_indexes<T: EmbeddedIndexes>(prefix: &str) -> Vec<IndexModel> {
+    T::indexes().into_iter().map(|model| prefix_index(prefix, model)).collect()
+}
+
+/// Prefix the keys, name & weights of the given index model with the given field path.
+///
+/// A wildcard key of `$**`, covering every field of the embedded type, becomes `prefix.$**`,
+/// covering every field under the prefix. A wildcard *text* index can not be scoped this way, as
+/// the server only supports one on `$**` itself, so its prefixed form is rejected by
+/// [`check_index_limits`](./fn.check_index_limits.html) when the parent model is synced.
+fn prefix_index(prefix: &str, mut model: IndexModel) -> IndexModel {
+    let prefix_doc = |doc: Document| {
+        let mut prefixed = Document::new();
+        for (key, val) in doc {
+            prefixed.insert_bson(format!("{}.{}", prefix, key), val);
+        }
+        prefixed
+    };
+    model.keys = prefix_doc(model.keys);
+    model.options.weights = model.options.weights.map(&prefix_doc);
+    model.options.name = model.options.name.map(|name| format!("{}.{}", prefix, name));
+    model
+}
+
+/// Generate an `ObjectId` made of random bytes alone, embedding neither its creation time nor
+/// any machine or process identifier.
+///
+/// For use by [`Model::generate_id`](./trait.Model.html#method.generate_id). The bytes come from
+/// the standard library's randomly keyed hasher, mixed with the current time & a process wide
+/// counter; this is not a cryptographically secure generator, so IDs which must be unguessable
+/// should come from a CSPRNG instead.
+pub fn random_object_id() -> ObjectId {
+    let mut bytes = [0u8; 12];
+    let seq = RANDOM_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|dur| dur.as_secs() ^ dur.subsec_nanos() as u64).unwrap_or(0);
+    for (chunk, salt) in bytes.chunks_mut(8).zip(0u64..) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(nanos);
+        hasher.write_usize(seq);
+        hasher.write_u64(salt);
+        let hash = hasher.finish();
+        for (idx, byte) in chunk.iter_mut().enumerate() {
+            *byte = (hash >> (idx * 8)) as u8;
+        }
+    }
+    ObjectId::with_bytes(bytes)
+}
+
+/// A process wide counter mixed into each random ID, so that no two are hashed from the same input.
+static RANDOM_ID_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Build an index on a raw dotted path, such as a known key within a map of dynamic attributes.
+///
+/// Keys of a `HashMap` field are not struct fields, but may still be indexed by path:
+///
+/// ```rust,ignore
+/// // Where the model has `attributes: HashMap<String, Bson>`.
+/// fn indexes() -> Vec<IndexModel> {
+///     vec![
+///         wither::model::raw_path_index("attributes.priority", 1, basic_index_options("priority", true, None, None, None))
+///             .expect("Expected a valid index path."),
+///     ]
+/// }
+/// ```
+///
+/// When the map is `#[serde(flatten)]`ed its keys are stored at the top level of the document, so
+/// the path is the key alone, e.g. `priority`. The path is checked to be non-empty & free of
+/// empty or `$` prefixed segments, but as it can not be checked against the model's fields, a
+/// warning is logged to that effect; a typo will silently index nothing.
+pub fn raw_path_index(path: &str, direction: i32, options: IndexOptions) -> Result<IndexModel> {
+    validate_index_path(path)?;
+    warn!("Index on raw path '{}' can not be checked against the model's fields.", path);
+    let mut keys = Document::new();
+    keys.insert(path, direction);
+    Ok(IndexModel{keys, options})
+}
+
+/// The maximum number of indexes MongoDB allows on a single collection, including `_id`.
+pub const MAX_INDEXES_PER_COLLECTION: usize = 64;
+
+/// The maximum number of fields MongoDB allows in a single compound index.
+pub const MAX_COMPOUND_INDEX_FIELDS: usize = 32;
+
+/// Check that the given index declarations for the named collection are within MongoDB's limits.
+///
+/// [`Model::sync`](./trait.Model.html#method.sync) calls this with the model's own indexes only:
+/// it does not know of other models sharing its collection, so their combined count is **not**
+/// checked by `sync`. When several models share one collection, call this with all of their
+/// indexes together, such as in a test. Indexes are told apart by their full key document —
+/// field names, their order & their directions — so declarations sharing the same keys count
+/// once, & the `_id` index is always counted. Wildcard text indexes on anything but `$**` itself,
+/// such as those of [`embedded_indexes`](./fn.embedded_indexes.html), are rejected too. Index key
+/// **size** is a limit on stored values, so it can only be enforced by the server.
+pub fn check_index_limits(collection: &str, indexes: &[IndexModel]) -> Result<()> {
+    for index in indexes {
+        if index.keys.len() > MAX_COMPOUND_INDEX_FIELDS {
+            return Err(ArgumentError(format!("Collection '{}' declares index '{}' with {} fields; MongoDB allows {}.", collection, index_name(index), index.keys.len(), MAX_COMPOUND_INDEX_FIELDS)).into());
+        }
+        let text = Bson::String("text".to_owned());
+        if let Some((key, _)) = index.keys.iter().find(|&(key, val)| key.ends_with(".$**") && val == &text) {
+            return Err(ArgumentError(format!("Collection '{}' declares index '{}' as a wildcard text index on '{}'; MongoDB only allows wildcard text indexes on '$**'.", collection, index_name(index), key)).into());
+        }
+    }
+    let mut id_keys = Document::new();
+    id_keys.insert(DEFAULT_INDEX, 1);
+    let mut distinct: Vec<&Document> = vec![&id_keys];
+    for index in indexes {
+        if !distinct.contains(&&index.keys) {
+            distinct.push(&index.keys);
+        }
+    }
+    if distinct.len() > MAX_INDEXES_PER_COLLECTION {
+        return Err(ArgumentError(format!("Collection '{}' declares {} indexes; MongoDB allows {}.", collection, distinct.len(), MAX_INDEXES_PER_COLLECTION)).into());
+    }
+    Ok(())
+}
+
+/// Ensure the given index path is non-empty, with no empty or operator segments.
+fn validate_index_path(path: &str) -> Result<()> {
+    if path.is_empty() {
+        return Err(ArgumentError("Index path must not be empty.".to_owned()).into());
+    }
+    if path.split('.').any(|segment| segment.is_empty() || segment.starts_with('$')) {
+        return Err(ArgumentError(format!("Index path '{}' must not contain empty or '$' prefixed segments.", path)).into());
+    }
+    Ok(())
+}
+
+/// The interval, in milliseconds, at which index builds are polled for progress.
+const INDEX_PROGRESS_POLL_INTERVAL_MS: u64 = 1000;
+
+/// The progress of an index build, as reported by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexBuildProgress {
+    /// The namespace of the collection the index is being built on.
+    pub namespace: String,
+
+    /// The name of the index being built.
+    pub index: String,
+
+    /// The number of units of work done so far.
+    pub done: i64,
+
+    /// The total number of units of work to be done.
+    pub total: i64,
+}
+
+impl IndexBuildProgress {
+    /// The percentage of the index build which is complete.
+    pub fn percent(&self) -> f64 {
+        if self.total <= 0 {
+            return 0.0;
+        }
+        (self.done as f64 / self.total as f64) * 100.0
+    }
+}
+
+/// The usage of an index since it was created, or since its server last started, as reported by
+/// `$indexStats`.
+///
+/// See [`Model::index_usage`](./trait.Model.html#method.index_usage).
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexUsage {
+    /// The name of the index.
+    pub name: String,
+
+    /// The key pattern of the index.
+    pub keys: Document,
+
+    /// The `host:port` of the server reporting this usage.
+    pub host: String,
+
+    /// The number of operations which have used the index.
+    pub accesses: i64,
+
+    /// When the server began counting accesses.
+    pub since: DateTime<Utc>,
+
+    /// Whether the index is declared by the model's `indexes`.
+    pub declared: bool,
+}
+
+/// A MongoDB server version, such as `3.6.4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServerVersion {
+    /// The major version.
+    pub major: u32,
+
+    /// The minor version.
+    pub minor: u32,
+
+    /// The patch version.
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    /// Create a new server version.
+    pub fn new(major: u32, minor: u32, patch: u32) -> ServerVersion {
+        ServerVersion{major, minor, patch}
+    }
+
+    /// Parse a version string as reported by `buildInfo`, such as `3.6.4` or `4.0.0-rc1`.
+    ///
+    /// Missing components are taken to be `0`, & any pre-release suffix is ignored.
+    pub fn parse(version: &str) -> Option<ServerVersion> {
+        let mut parts = version.split(|ch: char| ch == '-' || ch == '+').next().unwrap_or("").split('.');
+        let mut next = || parts.next().map(|part| part.parse::<u32>().ok());
+        let major = next()??;
+        let minor = next().unwrap_or(Some(0))?;
+        let patch = next().unwrap_or(Some(0))?;
+        Some(ServerVersion{major, minor, patch})
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The minimum server version needed to support the features of the given index which gate on
+/// it, if any. See [`Model::index_min_server_version`](./trait.Model.html#method.index_min_server_version).
+pub fn index_min_server_version(index: &IndexModel) -> Option<ServerVersion> {
+    if index.keys.keys().any(|key| key == "$**" || key.ends_with(".$**")) {
+        return Some(ServerVersion::new(4, 2, 0));
+    }
+    let versioned = |kind: &str, version: Option<i32>| version.map(|ver| ver >= 3).unwrap_or(false)
+        && index.keys.values().any(|val| val == &Bson::String(kind.to_owned()));
+    if versioned("2dsphere", index.options.sphere_version) || versioned("text", index.options.text_version) {
+        return Some(ServerVersion::new(3, 2, 0));
+    }
+    None
+}
+
+/// Fetch the version of the server behind the given database via `buildInfo`.
+fn server_version(db: &Database) -> Result<ServerVersion> {
+    let res = db.command(doc!{"buildInfo": 1}, CommandType::Suppressed, None)?;
+    let version = res.get_str("version").unwrap_or("");
+    ServerVersion::parse(version)
+        .ok_or_else(|| ResponseError(format!("Server reported an invalid version '{}'.", version)).into())
+}
+
+/// A serializable snapshot of everything a model declares about its collection.
+///
+/// See [`Model::schema_descriptor`](./trait.Model.html#method.schema_descriptor).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaDescriptor {
+    /// The name of the model's collection.
+    pub collection: String,
+
+    /// The model's time-series configuration, if any.
+    pub timeseries: Option<Timeseries>,
+
+    /// The model's validator, if any.
+    pub validator: Option<Document>,
+
+    /// The `validationLevel` applied along with the validator.
+    pub validation_level: String,
+
+    /// The `validationAction` applied along with the validator.
+    pub validation_action: String,
+
+    /// The model's shard key, if any.
+    pub shard_key: Option<Document>,
+
+    /// Each index maintained by `Model::sync`, as its name, `key` & options, ordered by name.
+    pub indexes: Vec<Document>,
+
+    /// The description of each of the model's migrations, in order.
+    pub migrations: Vec<MigrationSpec>,
+}
+
+/// A report of what `Model::sync` did to a model's collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncReport {
+    /// The namespace of the synchronized collection.
+    pub namespace: String,
+
+    /// The names of the indexes which were created.
+    pub indexes_created: Vec<String>,
+
+    /// The names of the indexes which were dropped, as they are no longer declared.
+    pub indexes_dropped: Vec<String>,
+
+    /// The results of each executed migration, in order.
+    pub migrations: Vec<MigrationResult>,
+
+    /// The number of seeds which were upserted.
+    pub seeds_upserted: usize,
+}
+
+/// The field used by `Timestamps` models to record when a document was created.
+pub const CREATED_AT_FIELD: &str = "created_at";
+
+/// The field used by `Timestamps` models to record when a document was last updated.
+pub const UPDATED_AT_FIELD: &str = "updated_at";
+
+/// A trait for models which track when they were created & last updated.
+///
+/// Implement this trait for your model, storing the timestamps under the
+/// [`CREATED_AT_FIELD`](./constant.CREATED_AT_FIELD.html) & [`UPDATED_AT_FIELD`](./constant.UPDATED_AT_FIELD.html)
+/// names, and return `Some(self)` from [`Model::timestamps`](./trait.Model.html#method.timestamps)
+/// so that the CRUD methods of the model will manage the timestamps automatically. Store them as
+/// `bson::UtcDateTime`, so that they are written as BSON datetimes — a `chrono::DateTime` field
+/// would be serialized as a string.
+///
+/// ```rust,ignore
+/// // With fields `created_at` & `updated_at` of type `Option<bson::UtcDateTime>`.
+/// impl wither::model::Timestamps for User {
+///     fn created_at(&self) -> Option<DateTime<Utc>> { self.created_at.as_ref().map(|ts| ts.0) }
+///     fn set_created_at(&mut self, ts: DateTime<Utc>) { self.created_at = Some(bson::UtcDateTime(ts)); }
+///     fn updated_at(&self) -> Option<DateTime<Utc>> { self.updated_at.as_ref().map(|ts| ts.0) }
+///     fn set_updated_at(&mut self, ts: DateTime<Utc>) { self.updated_at = Some(bson::UtcDateTime(ts)); }
+/// }
+///
+/// // Within the `Model` impl.
+/// fn timestamps(&mut self) -> Option<&mut wither::model::Timestamps> {
+///     Some(self)
+/// }
+/// ```
+pub trait Timestamps {
+    /// Get the time at which this instance was first saved.
+    fn created_at(&self) -> Option<DateTime<Utc>>;
+
+    /// Set the time at which this instance was first saved.
+    fn set_created_at(&mut self, DateTime<Utc>);
+
+    /// Get the time at which this instance was last saved.
+    fn updated_at(&self) -> Option<DateTime<Utc>>;
+
+    /// Set the time at which this instance was last saved.
+    fn set_updated_at(&mut self, DateTime<Utc>);
+}
+
+/// Stamp the given timestamps as being written now, setting the creation time if needed.
+pub(crate) fn touch_timestamps(timestamps: &mut Timestamps) {
+    touch_timestamps_at(timestamps, Utc::now());
+}
+
+/// Stamp the given timestamps as being written at the given time, setting the creation time if
+/// needed.
+pub(crate) fn touch_timestamps_at(timestamps: &mut Timestamps, now: DateTime<Utc>) {
+    if timestamps.created_at().is_none() {
+        timestamps.set_created_at(now);
+    }
+    timestamps.set_updated_at(now);
+}
+
+/// Stamp the given instance document as being written at the given time, as `touch_timestamps_at`
+/// would stamp its instance, replacing the timestamp fields in place.
+pub(crate) fn touch_document_timestamps(doc: &mut Document, now: DateTime<Utc>) {
+    let created = match doc.get(CREATED_AT_FIELD) {
+        Some(&Bson::Null) | None => false,
+        Some(_) => true,
+    };
+    if !created {
+        stamp_field(doc, CREATED_AT_FIELD, Bson::UtcDatetime(now));
+    }
+    stamp_field(doc, UPDATED_AT_FIELD, Bson::UtcDatetime(now));
+}
+
+/// Set the given field of the given document, in place if it is already present.
+fn stamp_field(doc: &mut Document, field: &str, value: Bson) {
+    if let Some(existing) = doc.get_mut(field) {
+        *existing = value;
+        return;
+    }
+    doc.insert(field, value);
+}
+
+/// Add a `$set` of the update time to the given update document, unless it already sets it.
+fn stamp_updated_at(update: &mut Document) {
+    let mut set = match update.remove("$set") {
+        Some(Bson::Document(set)) => set,
+        _ => Document::new(),
+    };
+    if !set.contains_key(UPDATED_AT_FIELD) {
+        set.insert(UPDATED_AT_FIELD, Bson::UtcDatetime(Utc::now()));
+    }
+    update.insert("$set", set);
+}
+
+/// The field used to record when a soft deleted document was deleted.
+pub const SOFT_DELETE_FIELD: &str = "deleted_at";
+
+/// The field under which `Model::text_search_scored` projects the text score of each document.
+pub const TEXT_SCORE_FIELD: &str = "_text_score";
+
+/// Soft delete configuration for a model.
+///
+/// See [`Model::soft_delete`](./trait.Model.html#method.soft_delete) for more details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftDelete {
+    /// The number of seconds a soft deleted document is retained before MongoDB physically
+    /// removes it. When `None`, soft deleted documents are retained indefinitely.
+    pub ttl_seconds: Option<i32>,
+}
+
+/// An inclusive range of values allowed for a numeric field of a model.
+///
+/// See [`Model::field_ranges`](./trait.Model.html#method.field_ranges) for more details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRange {
+    /// The serialized name of the field, which may be a dotted path into embedded documents.
+    pub field: &'static str,
+
+    /// The lowest allowed value, if any.
+    pub min: Option<f64>,
+
+    /// The highest allowed value, if any.
+    pub max: Option<f64>,
+}
+
+impl FieldRange {
+    /// A range allowing values of `field` from `min` to `max`, inclusive.
+    pub fn between(field: &'static str, min: f64, max: f64) -> FieldRange {
+        FieldRange{field, min: Some(min), max: Some(max)}
+    }
+
+    /// A range allowing values of `field` of at least `min`.
+    pub fn at_least(field: &'static str, min: f64) -> FieldRange {
+        FieldRange{field, min: Some(min), max: None}
+    }
+
+    /// A range allowing values of `field` of at most `max`.
+    pub fn at_most(field: &'static str, max: f64) -> FieldRange {
+        FieldRange{field, min: None, max: Some(max)}
+    }
+}
+
+/// Time-series collection configuration for a model.
+///
+/// See [`Model::timeseries`](./trait.Model.html#method.timeseries) for more details.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Timeseries {
+    /// The field holding the date of each measurement.
+    pub time_field: String,
+
+    /// The field holding the metadata — such as the sensor ID — identifying the series each
+    /// measurement belongs to, if any.
+    pub meta_field: Option<String>,
+
+    /// The expected interval between measurements of the same series. When `None`, the server
+    /// defaults to `seconds`.
+    pub granularity: Option<TimeseriesGranularity>,
+}
+
+/// The granularity of a time-series collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeseriesGranularity {
+    /// Measurements arrive seconds apart.
+    Seconds,
+
+    /// Measurements arrive minutes apart.
+    Minutes,
+
+    /// Measurements arrive hours apart.
+    Hours,
+}
+
+impl TimeseriesGranularity {
+    /// The name of this granularity, as accepted by `createCollection`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            &TimeseriesGranularity::Seconds => "seconds",
+            &TimeseriesGranularity::Minutes => "minutes",
+            &TimeseriesGranularity::Hours => "hours",
+        }
+    }
+}
+
+/// The first MongoDB version supporting time-series collections.
+const TIMESERIES_MIN_SERVER_VERSION: ServerVersion = ServerVersion{major: 5, minor: 0, patch: 0};
+
+/// Whether an upsert created a new document or matched an existing one.
+///
+/// See [`Model::find_one_and_upsert`](./trait.Model.html#method.find_one_and_upsert).
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpsertOutcome {
+    /// A new document was inserted, having the given ID.
+    Upserted(ObjectId),
+
+    /// An existing document was matched & updated.
+    Matched,
+}
+
+/// A change to a single top level field between two instances of a model.
+///
+/// See [`Model::diff`](./trait.Model.html#method.diff).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// The name of the changed field.
+    pub field: String,
+
+    /// The field's old value, or `None` if it was absent.
+    pub old: Option<Bson>,
+
+    /// The field's new value, or `None` if it is now absent.
+    pub new: Option<Bson>,
+}
+
+/// A related collection to which soft deletes cascade.
+///
+/// See [`Model::soft_delete_cascades`](./trait.Model.html#method.soft_delete_cascades).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftDeleteCascade {
+    /// The name of the related collection.
+    pub collection: String,
+
+    /// The field of the related collection's documents holding the ID of their parent.
+    pub field: String,
+}
+
+/// A slice of an array field, for projecting only part of the array via `Model::find_with_slice`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slice {
+    /// The first `n` elements when positive, or the last `-n` elements when negative.
+    Count(i32),
+
+    /// Up to `limit` elements, after skipping `skip` elements. A negative `skip` counts back
+    /// from the end of the array.
+    Range {
+        /// The number of elements to skip.
+        skip: i32,
+
+        /// The maximum number of elements to return. Must be positive.
+        limit: i32,
+    },
+}
+
+impl Slice {
+    /// The value of the `$slice` projection operator for this slice.
+    fn to_bson(&self) -> Bson {
+        match self {
+            &Slice::Count(n) => Bson::I32(n),
+            &Slice::Range{skip, limit} => Bson::Array(vec![Bson::I32(skip), Bson::I32(limit)]),
+        }
+    }
+}
+
+/// A single page of model instances, along with the total number of matching documents.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// The model instances of this page.
+    pub items: Vec<T>,
+
+    /// The total number of documents matching the query, across all pages.
+    pub total: i64,
+
+    /// The zero-based index of this page.
+    pub page: i64,
+
+    /// The maximum number of items per page.
+    pub per_page: i64,
+}
+
 /// Model provides data modeling behaviors for interacting with MongoDB database collections.
 pub trait Model<'a> where Self: Serialize + Deserialize<'a> {
 
@@ -131,8 +774,63 @@ pub trait Model<'a> where Self: Serialize + Deserialize<'a> {
     fn id(&self) -> Option<ObjectId>;
 
     /// Set the ID for this model.
+    ///
+    /// This assigns IDs uniformly across models, whatever the visibility or type of their ID
+    /// field, & is used throughout this crate — such as when `Model.save` generates an ID.
     fn set_id(&mut self, ObjectId);
 
+    /// Consume this instance, returning it with the given ID set, via
+    /// [`set_id`](#tymethod.set_id).
+    ///
+    /// This is handy for building an instance referencing a known ID, such as when
+    /// reconstructing one from an external reference. For models implementing `Default`:
+    ///
+    /// ```rust,ignore
+    /// let user = User::default().with_id(id);
+    /// ```
+    fn with_id(mut self, id: ObjectId) -> Self {
+        self.set_id(id);
+        self
+    }
+
+    ////////////////
+    // Connection //
+
+    /// The key of the registered client which this model's operations should be routed to, for
+    /// giving a group of models its own connection pool. Defaults to `None`, using the database
+    /// given to each operation.
+    ///
+    /// See the [`registry`](../registry/index.html) module for how clients are registered.
+    fn client_key() -> Option<&'static str> {
+        None
+    }
+
+    /// Get the database which this model's operations should use, given the database passed to
+    /// the operation.
+    ///
+    /// When the model has a [`client_key`](#method.client_key) with a registered client, this is
+    /// the database of the same name on that client. Otherwise, it is the given database.
+    fn database(db: Database) -> Database {
+        route_database(db, Self::client_key())
+    }
+
+    /// Get this model's collection, within the [routed](#method.database) database.
+    ///
+    /// Within the [`scope`](../tenant/struct.TenantScoped.html#method.scope) of a tenant, this is
+    /// the tenant's collection instead.
+    fn collection(db: &Database) -> Collection {
+        Self::database(db.clone()).collection(&tenant::scoped_collection_name(Self::COLLECTION_NAME))
+    }
+
+    /// Get a handle running this model's operations against the collection of the given tenant,
+    /// named `{COLLECTION_NAME}_{tenant}`.
+    ///
+    /// Returns an error if the tenant ID is unsafe for use within a collection name. See the
+    /// [`tenant`](../tenant/index.html) module for more details.
+    fn for_tenant(tenant: &str) -> Result<TenantScoped<Self>> {
+        TenantScoped::new(tenant)
+    }
+
     ///////////////////////////////
     // Write Concern Abstraction //
 
@@ -141,6 +839,14 @@ pub trait Model<'a> where Self: Serialize + Deserialize<'a> {
     /// The default implementation ensures that all writes block until they are journaled, which
     /// ensures that an ObjectId will be returned for the inserted document. For most cases,
     /// overriding this implementation should be unnecessary.
+    ///
+    /// This is the place to centralize the consistency policy of a model's collection, such as
+    /// requiring `majority` acknowledgement via [`write_concern_w`](#method.write_concern_w).
+    /// MongoDB has no collection level default write concern — `createCollection` only accepts a
+    /// write concern for the creation itself — so [`Model::sync`](#method.sync) can not apply
+    /// one to the collection. Instead, every write made through this model uses this write
+    /// concern, unless the caller passes a write concern in an operation's options, which always
+    /// takes precedence. Writes made to the collection by other means use their client's default.
     fn model_write_concern() -> WriteConcern {
         return WriteConcern{
             w: Self::write_concern_w(),
@@ -170,329 +876,3848 @@ pub trait Model<'a> where Self: Serialize + Deserialize<'a> {
         return false;
     }
 
-    //////////////////
-    // Static Layer //
+    ///////////////////
+    // Read Defaults //
 
-    /// Count the number of documents in this model's collection matching the given criteria.
-    fn count(db: Database, filter: Option<Document>, options: Option<CountOptions>) -> Result<i64> {
-        let coll = db.collection(Self::COLLECTION_NAME);
-        coll.count(filter, options)
+    /// The default sort order applied to `Model::find` when the caller does not specify a sort.
+    ///
+    /// Without a sort, MongoDB makes no guarantees about the order of returned documents, which
+    /// leads to subtle pagination bugs. Defaults to `{_id: 1}` for a stable ordering. Any sort
+    /// specified in the `FindOptions` given to `Model::find` will always take precedence. Return
+    /// `None` to disable default sorting for this model.
+    fn default_sort() -> Option<Document> {
+        return Some(doc!{"_id": 1});
     }
 
-    /// Find all instances of this model matching the given query.
-    fn find(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Vec<Self>> {
-        let coll = db.collection(Self::COLLECTION_NAME);
+    /// Whether reads of this model should allow partial results from a sharded cluster by default.
+    ///
+    /// When `true`, `Model::find` & `Model::find_one` will return whatever results are available
+    /// when one or more shards are unavailable, rather than returning an error. This favors
+    /// availability over completeness: **results may silently be missing documents**, so only
+    /// enable this for best-effort reads. Callers may also enable this per-query via the
+    /// `allow_partial_results` field of `FindOptions`. Defaults to `false`.
+    fn allow_partial_results() -> bool {
+        return false;
+    }
 
-        // Unwrap cursor.
-        let mut cursor = match coll.find(filter, options) {
-            Ok(cursor) => cursor,
-            Err(err) => return Err(err),
-        };
+    /// The field by which a [`TailableCursor`](../cursor/struct.TailableCursor.html) from
+    /// [`Model::tail`](#method.tail) resumes when reopened, yielding only documents whose value of
+    /// this field is greater than that of the last document yielded.
+    ///
+    /// **The field's values must increase in insertion order**, else documents are silently
+    /// skipped on resume. Defaults to `_id`, which only holds when IDs are assigned by a single
+    /// source: ObjectIds from different processes, or from one process within the same second,
+    /// are not ordered by insertion. Override this with a field such as a sequence number when
+    /// many writers insert into the collection.
+    fn tail_resume_field() -> &'static str {
+        return "_id";
+    }
 
-        // Collect all items in the cursor.
-        let bson_docs = match cursor.drain_current_batch() {
-            Ok(docs) => docs,
-            Err(err) => return Err(err),
-        };
+    /// The read preference of reads of this model, such as `Secondary` for a reporting service
+    /// which must keep load off of the primary.
+    ///
+    /// This is applied by every read of this model's instances, such as `Model::find`,
+    /// `Model::find_one`, `Model::export_stream` & `Model::near_sphere` — wherever
+    /// [`allow_partial_results`](#method.allow_partial_results) is — unless the caller specifies
+    /// a `read_preference` in their `FindOptions`. Use
+    /// [`verify_read_preference`](#method.verify_read_preference) at boot to check that it can be
+    /// satisfied. Defaults to `None`, reading per the client's default.
+    fn read_preference() -> Option<ReadPreference> {
+        return None;
+    }
 
-        // Deserialize bson docs onto struct models.
-        let mut instances: Vec<Self> = vec![];
-        for doc in bson_docs {
-            let inst = Self::instance_from_document(doc)?;
-            instances.push(inst);
-        }
-        Ok(instances)
+    /// Large fields of this model — such as a serialized blob or an embedded history array —
+    /// which are excluded from the default projection of `Model::find` & `Model::find_one`.
+    ///
+    /// This keeps list queries from shipping large fields which they do not need. Each lazy field
+    /// must be an `Option`, which is `None` when not loaded. Callers opt into loading lazy fields
+    /// via [`find_with_fields`](#method.find_with_fields), or by giving any explicit `projection`
+    /// in their `FindOptions`. Saving an instance whose lazy fields were not loaded keeps their
+    /// stored values: rather than replacing the stored document, `Model.save` then `$set`s the
+    /// instance's other fields, so fields the instance omits — such as `None`s skipped by serde —
+    /// are not removed either. As a lazy field which is `None` is taken to be not loaded, clear
+    /// a lazy field via `Model.update` with `$unset`. Defaults to no fields.
+    fn lazy_fields() -> Vec<&'static str> {
+        vec![]
     }
 
-    /// Delete any model instances matching the given query.
-    fn delete_many(db: Database, filter: Document) -> Result<()> {
-        let coll = db.collection(Self::COLLECTION_NAME);
-        coll.delete_many(filter, Some(Self::model_write_concern()))?;
-        Ok(())
+    /// Internal bookkeeping fields of this model — such as a version counter — which are kept in
+    /// storage but omitted from the public view given by [`to_public`](#method.to_public).
+    ///
+    /// Fields are given by their serialized name. The model's
+    /// [`content_hash_field`](#method.content_hash_field) & [`schema_version_field`](#method.schema_version_field)
+    /// are always omitted as well, when in use. Defaults to no fields.
+    fn internal_fields() -> Vec<&'static str> {
+        vec![]
     }
 
-    /// Find the one model record matching your query, returning a model instance.
-    fn find_one(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Option<Self>> {
-        let coll = db.collection(Self::COLLECTION_NAME);
+    /// Whether loading an instance of this model should warn of any fields of the stored document
+    /// which the model does not have, such as fields removed from the model but still held by old
+    /// documents. Defaults to `false`.
+    ///
+    /// serde silently ignores such fields. When enabled,
+    /// [`instance_from_document`](#method.instance_from_document) re-serializes each loaded
+    /// instance, & logs a warning naming any stored fields missing from it, without failing the
+    /// load. Stored fields which are `null` are not reported, as they are indistinguishable from
+    /// skipped `None` fields. As this costs a serialization per document, it is intended for
+    /// confirming that a field removal has converged; see also
+    /// [`count_unknown_fields`](#method.count_unknown_fields).
+    fn warn_unknown_fields() -> bool {
+        false
+    }
 
-        // Unwrap result.
-        let doc_option = match coll.find_one(filter, options) {
-            Ok(doc_option) => doc_option,
-            Err(err) => return Err(err),
-        };
+    /// The duration after which a `Model::find` of this model is considered slow.
+    ///
+    /// When a query exceeds this threshold, a warning is logged with the shape of the query —
+    /// its filter with all values elided — its duration & whether an index was used, as
+    /// determined by a follow-up `explain` of the query. Defaults to `None`, disabling detection.
+    ///
+    /// Only available with the `slow-query-log` feature.
+    #[cfg(feature = "slow-query-log")]
+    fn slow_query_threshold() -> Option<Duration> {
+        None
+    }
 
-        // Unwrap option.
-        let doc = match doc_option {
-            Some(doc) => doc,
-            None => return Ok(None),
-        };
+    ////////////////
+    // Timestamps //
 
-        // Deserialize bson onto struct model.
-        let instance = Self::instance_from_document(doc)?;
-        Ok(Some(instance))
+    /// Get this instance's timestamps, if this model implements
+    /// [`Timestamps`](./trait.Timestamps.html). Defaults to `None`.
+    ///
+    /// Models implementing `Timestamps` should return `Some(self)`. The creation & update times
+    /// will then be managed automatically by `Model.save` & `Model::insert_many`, and
+    /// `Model.update` will `$set` the update time of the document.
+    fn timestamps(&mut self) -> Option<&mut Timestamps> {
+        None
     }
 
-    ////////////////////
-    // Instance Layer //
+    /// Find all instances of this model updated after the given time, by their `updated_at`.
+    ///
+    /// This is the primitive for incremental export & sync jobs: record the greatest
+    /// `updated_at` of each batch, & pass it as `since` to fetch the next. It relies on
+    /// `updated_at` being maintained, which [`Timestamps`](./trait.Timestamps.html) guarantees
+    /// for writes made through this crate; **writes made by other means, such as via the driver
+    /// directly or by migrations, are missed** unless they set `updated_at` too. Unless the
+    /// given options specify a sort, results are sorted by `updated_at` then `_id`, ascending.
+    /// An index on `updated_at` is recommended.
+    fn find_changed_since(db: Database, since: DateTime<Utc>, options: Option<FindOptions>) -> Result<Vec<Self>>
+        where Self: Timestamps
+    {
+        let mut filter = Document::new();
+        filter.insert(UPDATED_AT_FIELD, doc!{"$gt": Bson::UtcDatetime(since)});
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        if options.sort.is_none() {
+            let mut sort = Document::new();
+            sort.insert(UPDATED_AT_FIELD, 1);
+            sort.insert("_id", 1);
+            options.sort = Some(sort);
+        }
+        Self::find(db, Some(filter), Some(options))
+    }
 
-    /// Delete this model instance by ID.
-    fn delete(&self, db: Database) -> Result<()> {
-        // Return an error if the instance was never saved.
-        let id = self.id().ok_or(DefaultError("This instance has no ID. Can not be deleted.".to_string()))?;
+    /////////////////
+    // Soft Delete //
 
-        let coll = db.collection(Self::COLLECTION_NAME);
-        coll.delete_one(doc!{"_id": id}, Some(Self::model_write_concern()))?;
-        Ok(())
+    /// The soft delete configuration for this model. Defaults to `None`, disabling soft delete.
+    ///
+    /// When enabled, [`Model::delete`](#method.delete) will not remove the document, but will
+    /// instead set its [`SOFT_DELETE_FIELD`](./constant.SOFT_DELETE_FIELD.html) (`deleted_at`) to
+    /// the current time. Models using soft delete must declare a corresponding field, such as
+    /// `pub deleted_at: Option<bson::UtcDateTime>`, which [`Model::sync`](#method.sync) checks
+    /// for, returning an error if it is missing. A `chrono::DateTime` would be serialized as a
+    /// string rather than as a BSON datetime. Reads are not filtered, so use a filter such as
+    /// `{"deleted_at": null}` — which also matches a missing `deleted_at` — to exclude soft
+    /// deleted documents.
+    ///
+    /// If `ttl_seconds` is given, [`Model::sync`](#method.sync) will also maintain a TTL index on
+    /// `deleted_at`, so that MongoDB physically removes each document once it has been soft
+    /// deleted for the retention period — such as `2592000` for a 30 day retention policy. The
+    /// TTL must be positive, otherwise `sync` will return an error.
+    fn soft_delete() -> Option<SoftDelete> {
+        return None;
     }
 
-    /// Save the current model instance.
+    /// The related collections whose documents are soft deleted along with an instance of this
+    /// model. Defaults to none.
     ///
-    /// In order to make this method as flexible as possible, its behavior varies a little based
-    /// on the input and the state of the instance.
+    /// Each [`SoftDeleteCascade`](./struct.SoftDeleteCascade.html) names a collection & the
+    /// field of its documents which holds the ID of their parent, such as the `user_id` of a
+    /// user's `orders`. Only applies when [`soft_delete`](#method.soft_delete) is enabled. See
+    /// [`soft_delete_cascade`](#method.soft_delete_cascade) for the semantics.
+    fn soft_delete_cascades() -> Vec<SoftDeleteCascade> {
+        vec![]
+    }
+
+    /// The number of times an atomic single document operation will be retried upon a write
+    /// conflict. Defaults to `3`.
+    fn write_conflict_retries() -> u32 {
+        return 3;
+    }
+
+    /// The number of times each batch of a bulk write, such as `Model::insert_many`, will be
+    /// retried upon a transient error — a network error or a replica set failover in progress.
+    /// Defaults to `3`.
     ///
-    /// When the instance already has an ID, this method will operate purely based on the instance
-    /// ID. If no ID is present, and no `filter` has been specified, then an ID will be generated.
+    /// Retried batches resume without double applying writes the server already committed. See
+    /// [`insert_many`](#method.insert_many) for the semantics.
+    fn bulk_write_retries() -> u32 {
+        3
+    }
+
+    /////////////
+    // Caching //
+
+    /// Get the instance having the given ID from an external cache, if present.
     ///
-    /// If a `filter` is specified, and no ID exists for the instance, then the filter will be used
-    /// and the first document matching the filter will be replaced by this instance. This is
-    /// useful when the model has unique indexes on fields which need to be the target of the save
-    /// operation.
-    fn save(&mut self, db: Database, filter: Option<Document>) -> Result<()> {
-        let coll = db.collection(Self::COLLECTION_NAME);
-        let instance_doc = match bson::to_bson(&self)? {
-            bson::Bson::Document(doc) => doc,
-            _ => return Err(DefaultError("Failed to convert struct to a bson document.".to_string())),
-        };
+    /// Together with [`cache_put`](#method.cache_put) & [`cache_invalidate`](#method.cache_invalidate),
+    /// this provides the integration points for a read-through cache — in process, Redis or
+    /// otherwise — of this model. This crate does not provide a cache itself.
+    /// [`Model::find_by_id`](#method.find_by_id) will consult this hook before querying the
+    /// database, & will `cache_put` any instance it then finds. Defaults to `None`, a cache miss.
+    fn cache_get(_id: &ObjectId) -> Option<Self> {
+        None
+    }
 
-        // Ensure that journaling is set to true for this call, as we need to be able to get an ID back.
-        let mut write_concern = Self::model_write_concern();
-        write_concern.j = true;
+    /// Put this instance into an external cache. Defaults to a no-op.
+    fn cache_put(&self) {}
 
-        // Handle case where instance already has an ID.
-        let mut id_needs_update = false;
-        let _filter = if let Some(id) = self.id() {
-            doc!{"_id": id}
+    /// Invalidate the cache entry of the instance having the given ID.
+    ///
+    /// Called by `Model.save`, `Model.update` & `Model.delete` once their write has been made,
+    /// so that a read racing with the write can not cache the instance from before it.
+    /// Defaults to a no-op.
+    fn cache_invalidate(_id: &ObjectId) {}
 
-        // Handle case where no filter and no ID exist.
-        } else if filter == None {
-            let new_id = match ObjectId::new() {
-                Ok(new) => new,
-                Err(err) => return Err(OIDError(err)),
-            };
-            self.set_id(new_id.clone());
-            doc!{"_id": new_id}
+    ////////////////////
+    // Schema Version //
 
-        // Handle case where no ID exists, and a filter has been provided.
-        } else {
-            id_needs_update = true;
-            filter.unwrap()
-        };
+    /// The current schema version of this model, which is stamped into every document written
+    /// from an instance. Defaults to `None`, disabling stamping.
+    ///
+    /// When set, [`document_from_instance`](#method.document_from_instance) — & so `Model.save`,
+    /// `Model::insert_many` & seeds — writes this version into the
+    /// [`schema_version_field`](#method.schema_version_field). Bump it whenever documents
+    /// written by the new code differ in shape. Documents at older versions can then be targeted
+    /// precisely by migrations via [`older_version_filter`](#method.older_version_filter), &
+    /// rollout progress measured via [`count_older_versions`](#method.count_older_versions).
+    /// Partial updates, such as via `Model.update`, do not stamp the version, as they do not
+    /// rewrite the whole document.
+    fn schema_version() -> Option<i32> {
+        None
+    }
 
-        // Save the record by replacing it entirely, or upserting if it doesn't already exist.
-        let opts = FindOneAndUpdateOptions{upsert: Some(true), write_concern: Some(write_concern), return_document: Some(ReturnDocument::After), sort: None, projection: None, max_time_ms: None};
-        let updated_doc = match coll.find_one_and_replace(_filter, instance_doc, Some(opts))? {
-            Some(doc) => doc,
-            None => return Err(ResponseError("Server failed to return the updated document. Update may have failed.".to_owned())),
-        };
+    /// The field in which the [`schema_version`](#method.schema_version) is stored. Defaults to
+    /// `_v`. The model itself need not declare this field, as it is ignored on load unless the
+    /// model denies unknown fields.
+    fn schema_version_field() -> &'static str {
+        "_v"
+    }
 
-        // Update instance ID if needed.
-        if id_needs_update {
-            let response_id = match updated_doc.get_object_id("_id") {
-                Ok(id) => id,
-                Err(_) => return Err(ResponseError("Server failed to return ObjectId of updated document.".to_owned())),
-            };
-            self.set_id(response_id.clone());
-        };
+    /// A filter matching documents written at a schema version older than the current one,
+    /// including documents written before versioning was enabled, which have no version. Returns
+    /// `None` if the model has no schema version.
+    fn older_version_filter() -> Option<Document> {
+        let version = Self::schema_version()?;
+        let mut filter = Document::new();
+        filter.insert(Self::schema_version_field(), doc!{"$not": {"$gte": version}});
+        Some(filter)
+    }
 
-        return Ok(());
+    /// Count the documents of this model written at a schema version older than the current
+    /// one, measuring the progress of a rollout. Always `0` if the model has no schema version.
+    fn count_older_versions(db: Database) -> Result<i64> {
+        match Self::older_version_filter() {
+            Some(filter) => Self::count(db, Some(filter), None),
+            None => Ok(0),
+        }
     }
 
-    /// Update the current model instance.
+    //////////////////
+    // Static Layer //
+
+    /// Count the number of documents in this model's collection matching the given criteria.
+    fn count(db: Database, filter: Option<Document>, options: Option<CountOptions>) -> Result<i64> {
+        let coll = Self::collection(&db);
+        Ok(coll.count(filter, options)?)
+    }
+
+    /// Count the number of documents in this model's collection matching the given filter,
+    /// forcing the use of the given index.
     ///
-    /// As this method is simply a wrapper around MongoDB's
-    /// [FindOneAndUpdate](https://docs.mongodb.com/v3.2/reference/method/db.collection.findOneAndUpdate/)
-    /// operation, the `update` argument must be a valid update document. This operation targets the model,
-    /// instance by the instance's ID. If its ID is `None`, this method will return an error.
-    /// All other aspects of this method's input are passthrough.
+    /// When every field of `filter` is covered by the index whose key pattern is given as `hint`
+    /// — such as `doc!{"status": 1, "created_at": -1}` — the count can be answered from the
+    /// index alone, without fetching any documents. The query planner will usually choose such
+    /// an index on its own, but may pick a collection scan for some filters, or flip between
+    /// plans under changing data. This pins the covering index for hot count queries. It does
+    /// not help when the filter is not covered by the index, as documents must then be fetched
+    /// regardless, & the hinted index must exist or the count will fail.
+    fn count_covered(db: Database, filter: Document, hint: Document) -> Result<i64> {
+        let coll = Self::collection(&db);
+        let mut options = CountOptions::new();
+        options.hint_doc = Some(hint);
+        Ok(coll.count(Some(filter), Some(options))?)
+    }
+
+    /// Count the number of documents in this model's collection which lack the given field.
     ///
-    /// This method will consume `self`, and will return a new instance of `Self` based on the given
-    /// return options (`ReturnDocument::Before | ReturnDocument:: After`).
+    /// Useful for data quality audits & for assessing migration readiness, such as how many
+    /// documents still lack a newly introduced field. Pass the field's constant, such as
+    /// [`SOFT_DELETE_FIELD`](./constant.SOFT_DELETE_FIELD.html), where one exists. Dotted paths
+    /// into embedded documents are supported.
+    fn count_missing_field(db: Database, field: &str) -> Result<i64> {
+        let mut filter = Document::new();
+        filter.insert(field, doc!{"$exists": false});
+        Self::count(db, Some(filter), None)
+    }
+
+    /// Count the number of documents in this model's collection which have the given field.
     ///
-    /// In order to provide consistent behavior, this method will also ensure that the operation's write
-    /// concern `journaling` is set to `true`, so that we can receive a complete output document.
+    /// The complement of [`count_missing_field`](#method.count_missing_field). Note that a field
+    /// explicitly set to `null` is present.
+    fn count_present_field(db: Database, field: &str) -> Result<i64> {
+        let mut filter = Document::new();
+        filter.insert(field, doc!{"$exists": true});
+        Self::count(db, Some(filter), None)
+    }
+
+    /// Execute the given aggregation pipeline against this model's collection.
     ///
-    /// If this model instance was never written to the database, this operation will return an error.
-    fn update(self, db: Database, update: Document, opts: Option<FindOneAndUpdateOptions>) -> Result<Self> {
-        let coll = db.collection(Self::COLLECTION_NAME);
+    /// The pipeline may either be a [`Pipeline`](../pipeline/struct.Pipeline.html) or a raw
+    /// `Vec<Document>`. As the output of an aggregation seldom has the shape of the model, the
+    /// driver's cursor over the output documents is returned.
+    fn aggregate<P: Into<Vec<Document>>>(db: Database, pipeline: P, options: Option<AggregateOptions>) -> Result<Cursor> {
+        let coll = Self::collection(&db);
+        Ok(coll.aggregate(pipeline.into(), options)?)
+    }
 
-        // Extract model's ID & use as filter for this operation.
-        let id = match self.id() {
-            Some(id) => id,
-            None => {
-                return Err(ArgumentError("Model must have an ObjectId for this operation.".to_owned()));
+    /// Execute the given aggregation pipeline against this model's collection, writing its output
+    /// into the collection of the `Target` model.
+    ///
+    /// This keeps rollup & materialized view pipelines associated with the model type of their
+    /// destination, rather than a raw collection name. The given `output` stage, `$out` or
+    /// `$merge`, is appended to the pipeline, targeting `Target`'s collection within its
+    /// [routed](#method.database) database. An error is returned before executing anything if
+    /// the pipeline already ends in an output stage, if the two models are routed to different
+    /// clients, as a single aggregation can not span clients, or if `$out` would write to
+    /// another database. Cached query results of `Target` are invalidated.
+    fn aggregate_into_model<'b, Target: Model<'b>, P: Into<Vec<Document>>>(db: Database, pipeline: P, output: OutputStage) -> Result<()> {
+        let mut stages = pipeline.into();
+        if stages.last().map(|stage| stage.contains_key("$out") || stage.contains_key("$merge")).unwrap_or(false) {
+            return Err(ArgumentError("The pipeline already ends in an output stage.".to_owned()).into());
+        }
+        if Target::client_key() != Self::client_key() {
+            return Err(ArgumentError(format!("Can not aggregate '{}' into '{}', as they are routed to different clients.", Self::COLLECTION_NAME, Target::COLLECTION_NAME)).into());
+        }
+        let source = Self::collection(&db);
+        let target = Target::collection(&db);
+        if output == OutputStage::Out && target.db.name != source.db.name {
+            return Err(ArgumentError(format!("`$out` can not write '{}' into another database, '{}'.", source.namespace, target.namespace)).into());
+        }
+        stages.push(output.to_stage(&target.db.name, &target.name()));
+        query_cache::invalidate_after(&target.namespace, || {
+            for doc in source.aggregate(stages, None)? {
+                doc?;
             }
-        };
-        let filter = doc!{"_id": id};
+            Ok(())
+        })
+    }
 
-        // Ensure that journaling is set to true for this call for full output document.
-        // TODO: should probably encapsulate this as a unit-testable function.
-        let options = match opts {
-            Some(mut options) => {
-                options.write_concern = match options.write_concern {
-                    Some(mut wc) => {
-                        wc.j = true;
-                        Some(wc)
-                    },
-                    None => {
-                        let mut wc = Self::model_write_concern();
-                        wc.j = true;
-                        Some(wc)
-                    }
-                };
-                options
-            },
-            None => {
-                let mut options = FindOneAndUpdateOptions::default();
-                let mut wc = Self::model_write_concern();
-                wc.j = true;
-                options.write_concern = Some(wc);
-                options
-            }
+    /// Find all instances of this model whose `ref_field` references no document of the `Ref`
+    /// model's collection.
+    ///
+    /// This audits the integrity of soft foreign keys, such as an `author_id: ObjectId`
+    /// referencing a `User`. A `$lookup` joins each document having a non-null `ref_field`
+    /// against the `_id`s of `Ref`'s collection, & those joining nothing are returned. When
+    /// `ref_field` is an array of IDs, a document is only an orphan when none of them exist.
+    /// **This is a full collection operation**, which also looks up each reference, so it should
+    /// be run off-peak. As a `$lookup` can not span databases, an error is returned if the two
+    /// models are not routed to the same database.
+    fn find_orphans<'b, Ref: Model<'b>>(db: Database, ref_field: &str) -> Result<Vec<Self>> {
+        let source = Self::collection(&db);
+        let target = Ref::collection(&db);
+        if Ref::client_key() != Self::client_key() || target.db.name != source.db.name {
+            return Err(ArgumentError(format!("Can not look up references of '{}' in '{}', as they are not in the same database.", source.namespace, target.namespace)).into());
+        }
+        let mut present = Document::new();
+        present.insert(ref_field, doc!{"$exists": true, "$ne": Bson::Null});
+        let mut unmatched = Document::new();
+        unmatched.insert(ORPHAN_LOOKUP_FIELD, doc!{"$size": 0});
+        let mut projection = Document::new();
+        projection.insert(ORPHAN_LOOKUP_FIELD, 0);
+        let pipeline = Pipeline::new()
+            .match_(present)
+            .lookup(&tenant::scoped_collection_name(Ref::COLLECTION_NAME), ref_field, "_id", ORPHAN_LOOKUP_FIELD)
+            .match_(unmatched)
+            .project(projection);
+        let mut orphans = vec![];
+        for doc in source.aggregate(pipeline.into(), None)? {
+            orphans.push(Self::instance_from_document(doc?)?);
+        }
+        Ok(orphans)
+    }
+
+    /// Find the distinct values of the given field across this model's collection.
+    ///
+    /// Each value is deserialized as `T`, so an error will be returned if any distinct value is of
+    /// an unexpected type. Declaring field names as constants on your model — such as
+    /// `pub const FIELD_COUNTRY: &'static str = "country";` — and passing those constants here
+    /// guards against typos, which would otherwise silently return an empty set.
+    ///
+    /// ```rust,ignore
+    /// let countries = User::distinct::<String>(db.clone(), User::FIELD_COUNTRY, None)?;
+    /// ```
+    fn distinct<T: DeserializeOwned>(db: Database, field: &str, filter: Option<Document>) -> Result<Vec<T>> {
+        let coll = Self::collection(&db);
+        let mut values = vec![];
+        for value in coll.distinct(field, filter, None)? {
+            values.push(bson::from_bson::<T>(value).map_err(DecoderError)?);
+        }
+        Ok(values)
+    }
+
+    /// Find all instances of this model matching the given query.
+    ///
+    /// If the given options do not specify a sort, then the model's
+    /// [`default_sort`](#method.default_sort) will be used.
+    fn find(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Vec<Self>> {
+        let coll = Self::collection(&db);
+
+        // Apply the model's default sort if no sort has been specified.
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        if options.sort.is_none() {
+            options.sort = Self::default_sort();
+        }
+        options.allow_partial_results = options.allow_partial_results || Self::allow_partial_results();
+        if options.read_preference.is_none() {
+            options.read_preference = Self::read_preference();
+        }
+        if options.projection.is_none() {
+            options.projection = lazy_projection(&Self::lazy_fields(), &[]);
+        }
+
+        #[cfg(feature = "slow-query-log")]
+        let (started, slow_filter) = (Instant::now(), filter.clone());
+
+        // Unwrap cursor.
+        let mut cursor = match coll.find(filter, Some(options)) {
+            Ok(cursor) => cursor,
+            Err(err) => return Err(err.into()),
         };
 
-        // Perform a FindOneAndUpdate operation on this model's document by ID. Will fail if this
-        // model instance was never saved to the database to begin with.
-        let updated_doc = match coll.find_one_and_update(filter, update, Some(options))? {
-            Some(doc) => doc,
-            None => return Err(ResponseError("Server failed to return the updated document. Update may have failed.".to_owned())),
+        // Collect all items in the cursor.
+        let bson_docs = match cursor.drain_current_batch() {
+            Ok(docs) => docs,
+            Err(err) => return Err(err.into()),
         };
 
-        // Deserialize the return document into a model instance & return.
-        return Self::instance_from_document(updated_doc);
-    }
+        #[cfg(feature = "slow-query-log")]
+        {
+            let elapsed = started.elapsed();
+            if Self::slow_query_threshold().map(|threshold| elapsed > threshold).unwrap_or(false) {
+                log_slow_query(&coll, slow_filter.unwrap_or_else(Document::new), elapsed);
+            }
+        }
 
-    /////////////////////////
-    // Convenience Methods //
+        // Deserialize bson docs onto struct models.
+        let mut instances: Vec<Self> = vec![];
+        for doc in bson_docs {
+            let inst = Self::instance_from_document(doc)?;
+            instances.push(inst);
+        }
+        Self::after_load_batch(&mut instances)?;
+        Ok(instances)
+    }
 
-    /// Attempt to serialize the given bson document into an instance of this model.
-    fn instance_from_document(document: bson::Document) -> Result<Self> {
-        match bson::from_bson::<Self>(bson::Bson::Document(document)) {
-            Ok(inst) => Ok(inst),
-            Err(err) => Err(DecoderError(err)),
+    /// Find all documents of this model's collection matching the given query, as JSON values.
+    ///
+    /// This is for endpoints passing documents straight through to API consumers, skipping
+    /// deserialization into the model. The options are defaulted exactly as by
+    /// [`find`](#method.find), & each document is converted as described in the
+    /// [`json`](../json/index.html) module; notably, `ObjectId`s become hex strings & datetimes
+    /// become RFC 3339 strings. Neither the model's `from_stored_document` hook nor its
+    /// `after_load_batch` hook is applied.
+    ///
+    /// Only available with the `json` feature.
+    #[cfg(feature = "json")]
+    fn find_json(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Vec<Value>> {
+        let coll = Self::collection(&db);
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        if options.sort.is_none() {
+            options.sort = Self::default_sort();
+        }
+        options.allow_partial_results = options.allow_partial_results || Self::allow_partial_results();
+        if options.read_preference.is_none() {
+            options.read_preference = Self::read_preference();
+        }
+        if options.projection.is_none() {
+            options.projection = lazy_projection(&Self::lazy_fields(), &[]);
         }
+        let mut values = vec![];
+        for doc in coll.find(filter, Some(options))? {
+            values.push(document_to_json(doc?));
+        }
+        Ok(values)
     }
 
-    ///////////////////////
-    // Maintenance Layer //
+    /// Find all instances of this model matching the given query, projecting only the given
+    /// slice of the given array field.
+    ///
+    /// This avoids shipping large arrays when only part is needed, such as the last 5 comments
+    /// via `Slice::Count(-5)`. All other fields are returned as usual, barring any
+    /// [`lazy_fields`](#method.lazy_fields). The sliced field must deserialize from a partial
+    /// array, & an instance loaded this way should not be saved, as that would truncate the
+    /// stored array.
+    fn find_with_slice(db: Database, filter: Option<Document>, array_field: &str, slice: Slice) -> Result<Vec<Self>> {
+        if let Slice::Range{limit, ..} = slice {
+            if limit < 1 {
+                return Err(ArgumentError("Slice `limit` must be positive.".to_owned()).into());
+            }
+        }
+        let mut projection = lazy_projection(&Self::lazy_fields(), &[]).unwrap_or_else(Document::new);
+        projection.insert(array_field, doc!{"$slice": slice.to_bson()});
+        let mut options = FindOptions::new();
+        options.projection = Some(projection);
+        Self::find(db, filter, Some(options))
+    }
 
-    /// Get the vector of index models for this model.
-    fn indexes() -> Vec<IndexModel> {
-        vec![]
+    /// Find all instances of this model matching the given query, loading the given
+    /// [`lazy_fields`](#method.lazy_fields) along with all other fields.
+    ///
+    /// Lazy fields not given remain excluded. Any projection in the given options is replaced.
+    fn find_with_fields(db: Database, filter: Option<Document>, options: Option<FindOptions>, fields: &[&str]) -> Result<Vec<Self>> {
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        options.projection = Some(lazy_projection(&Self::lazy_fields(), fields).unwrap_or_else(Document::new));
+        Self::find(db, filter, Some(options))
     }
 
-    /// Get the vector of migration objects for this model.
-    fn migrations() -> Vec<Box<Migration>> {
-        vec![]
+    /// Search this model's collection via its text index, returning matching instances paired
+    /// with their text score, most relevant first.
+    ///
+    /// Matches scoring below `min_score` are excluded, so that only usable, relevance ranked
+    /// results are returned. The collection must have a text index. The score is projected
+    /// under [`TEXT_SCORE_FIELD`](./constant.TEXT_SCORE_FIELD.html), which is removed before
+    /// each document is deserialized.
+    fn text_search_scored(db: Database, query: &str, min_score: f64) -> Result<Vec<(Self, f64)>> {
+        let coll = Self::collection(&db);
+        let mut meta = Document::new();
+        meta.insert(TEXT_SCORE_FIELD, doc!{"$meta": "textScore"});
+        let mut options = FindOptions::new();
+        options.projection = Some(meta.clone());
+        options.sort = Some(meta);
+
+        let mut results = vec![];
+        for doc_res in coll.find(Some(doc!{"$text": {"$search": query}}), Some(options))? {
+            let mut doc = doc_res?;
+            let score = match doc.remove(TEXT_SCORE_FIELD) {
+                Some(Bson::FloatingPoint(score)) => score,
+                _ => return Err(ResponseError("Server failed to return the text score of a document.".to_owned()).into()),
+            };
+            // Results are sorted by descending score, so no later result can qualify.
+            if score < min_score {
+                break;
+            }
+            results.push((Self::instance_from_document(doc)?, score));
+        }
+        Ok(results)
     }
 
-    /// Synchronize this model with the backend.
+    /// Find all instances of this model matching the given query, grouped by the value of the
+    /// given field, such as users grouped by country for rendering.
     ///
-    /// This routine should be called once per model, early on at boottime. It will synchronize
-    /// any indexes defined on this model with the backend & will execute any active migrations
-    /// against the model's collection.
+    /// Grouping happens within the application, yielding typed model instances rather than the
+    /// output documents of a `$group` aggregation. As BSON values are not hashable, the group
+    /// key is deserialized as `K`, such as `String` — use `Option<K>` if some documents may lack
+    /// the field. Dotted paths into embedded documents are supported. Within each group,
+    /// instances keep the model's [`default_sort`](#method.default_sort) order.
     ///
-    /// This routine will destroy any indexes found on this model's collection which are not
-    /// defined in the response from `Self.indexes()`.
-    fn sync(db: Database) -> Result<()> {
-        let coll = db.collection(Self::COLLECTION_NAME);
-        sync_model_indexes(&coll, Self::indexes())?;
-        sync_model_migrations(&coll, Self::migrations())?;
-        Ok(())
-    }
-}
+    /// **NOTE:** every matching instance is held in memory at once, so use a selective filter,
+    /// or a `$group` aggregation, for large result sets.
+    fn find_grouped<K>(db: Database, filter: Option<Document>, group_field: &str) -> Result<HashMap<K, Vec<Self>>>
+        where K: DeserializeOwned + Hash + Eq
+    {
+        let coll = Self::collection(&db);
+        let mut options = read_options::<Self>();
+        options.sort = Self::default_sort();
 
-fn sync_model_indexes<'a>(coll: &'a Collection, indexes: Vec<IndexModel>) -> Result<()> {
-    info!("Synchronizing indexes for '{}'.", coll.namespace);
+        let mut keys: Vec<K> = vec![];
+        let mut instances: Vec<Self> = vec![];
+        for doc_res in coll.find(filter, Some(options))? {
+            let doc = doc_res?;
+            let key = document_path(&doc, group_field).cloned().unwrap_or(Bson::Null);
+            keys.push(bson::from_bson(key).map_err(DecoderError)?);
+            instances.push(Self::instance_from_document(doc)?);
+        }
+        Self::after_load_batch(&mut instances)?;
 
-    // Fetch current indexes.
-    let _ = coll.db.create_collection(coll.name().as_str(), None); // NOTE: NB: this is account for the mongodb driver bug: #251.
-    let mut current_indexes_map: HashMap<String, Document> = HashMap::new();
-    let indices = coll.list_indexes()
-        .map_err(|err| DefaultError(format!("Error while fetching current indexes for '{}': {:?}", coll.namespace, err.description())))?
-        .filter_map(|doc_res| doc_res.ok());
-    for doc in indices {
-        let idx_keys = doc.get_document("key")
-            .map_err(|err| DefaultError(format!("Error extracting 'key' of index document: {:?}", err.description())))?;
-        let key = idx_keys.keys().fold(String::from(""), |acc, bkey| acc + bkey);
-        current_indexes_map.insert(key, doc.clone());
+        let mut groups: HashMap<K, Vec<Self>> = HashMap::new();
+        for (key, instance) in keys.into_iter().zip(instances) {
+            groups.entry(key).or_insert_with(Vec::new).push(instance);
+        }
+        Ok(groups)
     }
 
-    // Fetch target indexes for this model.
-    let mut target_indexes_map: HashMap<String, IndexModel> = HashMap::new();
-    for model in indexes.iter() {
-        // Populate the 'target' indexes map for easy comparison later.
-        let key = model.keys.keys().fold(String::from(""), |acc, bkey| acc + bkey);
-        target_indexes_map.insert(key, model.to_owned());
+    /// Find all instances of this model matching the given query, keyed by their IDs.
+    ///
+    /// This is the common shape for joining models within the application. Loaded instances
+    /// always have an ID unless it was excluded by a projection, or the model's `id` method does
+    /// not return it; in either case an error is returned rather than silently dropping the
+    /// instance.
+    fn find_map_by_id(db: Database, filter: Option<Document>) -> Result<HashMap<ObjectId, Self>> {
+        let mut instances = HashMap::new();
+        for instance in Self::find(db, filter, None)? {
+            match instance.id() {
+                Some(id) => instances.insert(id, instance),
+                None => return Err(DefaultError("Loaded model instance has no ID. Can not be keyed by ID.".to_owned()).into()),
+            };
+        }
+        Ok(instances)
     }
 
-    // Determine which indexes must be created on the collection.
-    let mut indexes_to_create = vec![];
-    for (key, index_model) in target_indexes_map.iter() {
-        // Check if key already exists.
-        if !current_indexes_map.contains_key(key) {
-            indexes_to_create.push(index_model)
+    /// Find the instances of this model having the given IDs, in the order of the given IDs.
+    ///
+    /// This is for fetching IDs ranked elsewhere, such as by a search service. MongoDB does not
+    /// return `$in` matches in the order of the given values, so the instances are fetched via
+    /// [`find_map_by_id`](#method.find_map_by_id) & then reordered. IDs with no document are
+    /// simply absent from the result, as are repeats of an ID already given.
+    fn find_by_ids_ordered(db: Database, ids: &[ObjectId]) -> Result<Vec<Self>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
         }
+        let values: Vec<Bson> = ids.iter().cloned().map(Bson::ObjectId).collect();
+        let mut instances = Self::find_map_by_id(db, Some(doc!{"_id": {"$in": values}}))?;
+        Ok(ids.iter().filter_map(|id| instances.remove(id)).collect())
     }
 
-    // Determine which indexes to remove.
-    let mut indexes_to_remove = vec![];
-    for (key, index_doc) in current_indexes_map {
-        // Don't attempt to remove the default index.
-        if &key == DEFAULT_INDEX {
-            continue
+    /// Find all instances of this model matching the given query, by way of an older version of
+    /// this model's type.
+    ///
+    /// Each matching document is deserialized as `Old`, and is then upgraded to `Self` via its
+    /// `From<Old>` implementation. If `write_back` is `true`, each upgraded instance will be saved,
+    /// so that the stored document takes on the new shape. The `_id` of the stored document is
+    /// given to the upgraded instance before it is saved, so that `From` need not carry it over;
+    /// a stored document without an ObjectId `_id` can not be written back, & is an error.
+    ///
+    /// This supports lazy, read-time schema upgrades, complementary to the eager migration system.
+    /// Prefer this over an [`IntervalMigration`](../migration/struct.IntervalMigration.html) when
+    /// the upgrade requires logic which can not be expressed as `$set` & `$unset` documents, or
+    /// when touching every document at boottime would be too costly. Prefer an
+    /// `IntervalMigration` when every document must converge on the new shape promptly, as
+    /// documents which are never read will never be upgraded here.
+    fn find_upgrading<Old>(db: Database, filter: Option<Document>, write_back: bool) -> Result<Vec<Self>>
+        where Old: DeserializeOwned, Self: From<Old>
+    {
+        let coll = Self::collection(&db);
+        let mut instances: Vec<Self> = vec![];
+        for doc_res in coll.find(filter, None)? {
+            let doc = Self::from_stored_document(doc_res?)?;
+            let id = match doc.get("_id") {
+                Some(&Bson::ObjectId(ref id)) => Some(id.clone()),
+                _ => None,
+            };
+            let old = bson::from_bson::<Old>(Bson::Document(doc)).map_err(DecoderError)?;
+            let mut instance = Self::from(old);
+            instance.after_load()?;
+            if write_back {
+                // Write back over the source document, rather than inserting a new one.
+                match id {
+                    Some(id) => instance.set_id(id),
+                    None => return Err(ArgumentError(format!("Can not write back an upgraded document of '{}' which has no ObjectId `_id`.", coll.namespace)).into()),
+                }
+                instance.save(db.clone(), None)?;
+            }
+            instances.push(instance);
         }
+        Self::after_load_batch(&mut instances)?;
+        Ok(instances)
+    }
 
-        // Check if key is not present in target indexes map. This means the index needs removal.
-        if !target_indexes_map.contains_key(&key) {
-            indexes_to_remove.push(index_doc);
+    /// Find all instances of this model having keys of the given index within the given bounds.
+    ///
+    /// This constrains the index scan to the key range `[min, max)` via the `$min` & `$max` query
+    /// modifiers, which can be faster than an equivalent `$gte` & `$lt` filter in some cases. Both
+    /// bounds must be documents having the same fields, in the same order, as the key pattern of
+    /// the index, which is given as `hint` — such as `doc!{"age": 1}` — as these modifiers
+    /// require a hint to the matching index. Any other modifiers of the given options are kept.
+    ///
+    /// Unless the given options specify a sort, results are sorted in the order of the index.
+    fn find_range(db: Database, hint: Document, min: Document, max: Document, options: Option<FindOptions>) -> Result<Vec<Self>> {
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        if options.sort.is_none() {
+            options.sort = Some(hint.clone());
         }
+        let mut modifiers = options.modifiers.unwrap_or_else(Document::new);
+        modifiers.insert("$hint", hint);
+        modifiers.insert("$min", min);
+        modifiers.insert("$max", max);
+        options.modifiers = Some(modifiers);
+        Self::find(db, None, Some(options))
     }
 
-    // Create needed indexes.
-    for model in indexes_to_create {
-        // NOTE: this wraps the native MongoDB `ensureIndex` command. Will not fail if index already exists.
-        coll.create_index_model(model.clone())
-            .map_err(|err| DefaultError(format!("Failed to create index: {}", err.description())))?;
-    }
+    /// Find all instances of this model matching the given query, each paired with the internal
+    /// record ID of its document.
+    ///
+    /// This sets the `$showDiskLoc` query modifier — `showRecordId` of the `find` command — for
+    /// low level diagnostics, such as correlating documents with storage engine logs. **Record
+    /// IDs are internal to the storage engine**: they are not stable across compaction, resyncs
+    /// or restores, & differ between the members of a replica set, so they must never be stored
+    /// as references. Results are sorted per the model's [`default_sort`](#method.default_sort).
+    fn find_with_record_id(db: Database, filter: Option<Document>) -> Result<Vec<(Self, i64)>> {
+        let coll = Self::collection(&db);
+        let mut options = FindOptions::new();
+        options.sort = Self::default_sort();
+        options.projection = lazy_projection(&Self::lazy_fields(), &[]);
+        options.modifiers = Some(doc!{"$showDiskLoc": true});
 
-    // Remove old indexes.
-    for doc in indexes_to_remove {
-        let index_name = String::from(
-            doc.get_str("name").map_err(|err| DefaultError(format!("Failed to get index name: {:?}", err.description())))?
-        );
-        coll.drop_index_string(index_name)
-            .map_err(|err| DefaultError(format!("Failed to remove index: {}", err.description())))?;
+        let mut instances = vec![];
+        let mut record_ids = vec![];
+        for doc_res in coll.find(filter, Some(options))? {
+            let mut doc = doc_res?;
+            record_ids.push(take_record_id(&mut doc)?);
+            instances.push(Self::instance_from_document(doc)?);
+        }
+        Self::after_load_batch(&mut instances)?;
+        Ok(instances.into_iter().zip(record_ids).collect())
     }
 
-    info!("Finished synchronizing indexes for '{}'.", coll.namespace);
-    Ok(())
-}
+    /// Count the documents matching the given query which hold each field the model does not
+    /// have, keyed by field name.
+    ///
+    /// This audits the convergence of a field removal: once its migration has fully run, the
+    /// removed field will no longer be counted. Fields are detected as by
+    /// [`warn_unknown_fields`](#method.warn_unknown_fields), so each matching document is loaded
+    /// & re-serialized, though the model's `after_load` hook is not called. Only top level
+    /// fields are considered.
+    fn count_unknown_fields(db: Database, filter: Option<Document>) -> Result<BTreeMap<String, u64>> {
+        let coll = Self::collection(&db);
+        let mut counts = BTreeMap::new();
+        for doc_res in coll.find(filter, None)? {
+            let doc = Self::from_stored_document(doc_res?)?;
+            let instance = bson::from_bson::<Self>(Bson::Document(doc.clone())).map_err(DecoderError)?;
+            for field in instance_unknown_fields(&instance, &doc)? {
+                *counts.entry(field).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
 
-fn sync_model_migrations<'a>(coll: &'a Collection, migrations: Vec<Box<Migration>>) -> Result<()> {
-    info!("Starting migrations for '{}'.", coll.namespace);
+    /// Open a cursor over all instances of this model matching the given query, for long running
+    /// batch jobs.
+    ///
+    /// Cursors which sit idle on the server for longer than 10 minutes are normally killed, which
+    /// will break jobs that process each document slowly. This sets `no_cursor_timeout` on the
+    /// given options, so that the cursor will live until it is exhausted or closed. **The caller
+    /// must ensure that the cursor is eventually exhausted or closed**, otherwise it will leak
+    /// resources on the server.
+    fn find_long_running(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<ModelCursor<Self>> {
+        let coll = Self::collection(&db);
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        options.no_cursor_timeout = true;
+        Ok(ModelCursor::new(coll.find(filter, Some(options))?))
+    }
 
-    // Execute each migration.
-    for migration in migrations {
-        migration.execute(coll)?;
+    /// Find all instances of this model whose GeoJSON point `field` lies within the given
+    /// distance bounds of the given point, nearest first.
+    ///
+    /// This uses `$nearSphere`, & so requires a `2dsphere` index on `field`. Both bounds are in
+    /// meters & are optional: a `min_meters` bound excludes results at or immediately around the
+    /// point itself, for "nearby, but not here" queries. The point is given as a longitude &
+    /// latitude, from which the GeoJSON point is built. Results are ordered by distance, so the
+    /// model's [`default_sort`](#method.default_sort) is **not** applied.
+    fn near_sphere(db: Database, field: &str, lon: f64, lat: f64, min_meters: Option<f64>, max_meters: Option<f64>) -> Result<Vec<Self>> {
+        let coll = Self::collection(&db);
+        let mut options = read_options::<Self>();
+        options.projection = lazy_projection(&Self::lazy_fields(), &[]);
+        let mut instances = vec![];
+        for doc in coll.find(Some(near_sphere_filter(field, lon, lat, min_meters, max_meters)), Some(options))? {
+            instances.push(Self::instance_from_document(doc?)?);
+        }
+        Self::after_load_batch(&mut instances)?;
+        Ok(instances)
     }
 
-    info!("Finished migrations for '{}'.", coll.namespace);
-    Ok(())
-}
+    /// Lazily export the documents of this model's collection matching the given query, with the
+    /// given projection applied, as raw documents.
+    ///
+    /// This is for CSV & other export jobs over huge collections. **It bypasses model typing for
+    /// throughput:** documents are never deserialized into instances, so no hooks are called &
+    /// documents which would fail to deserialize are yielded all the same. Documents are fetched
+    /// from the server in batches as the cursor is iterated, so only the current batch is held in
+    /// memory, & projecting only the exported fields keeps each batch small. The model's
+    /// [`default_sort`](#method.default_sort) is not applied, as exports rarely need an order.
+    fn export_stream(db: Database, filter: Option<Document>, projection: Option<Document>) -> Result<DocumentCursor> {
+        let coll = Self::collection(&db);
+        let mut options = read_options::<Self>();
+        options.projection = projection;
+        Ok(DocumentCursor::new(coll.find(filter, Some(options))?))
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Tail this model's collection, yielding instances matching the given query as they are
+    /// inserted, for consuming an append-only event log in near real time.
+    ///
+    /// **The collection must be capped**, as only capped collections support tailable cursors;
+    /// an error is returned otherwise. The returned cursor yields the matching documents already
+    /// present, then blocks waiting for new ones rather than ending. If the underlying cursor
+    /// dies, it is reopened after the last document yielded, by
+    /// [`tail_resume_field`](#method.tail_resume_field) — see
+    /// [`TailableCursor`](../cursor/struct.TailableCursor.html). The given options' `cursor_type`
+    /// is replaced, & any sort must be left unset, as documents are yielded in insertion order.
+    fn tail(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<TailableCursor<Self>> {
+        let coll = Self::collection(&db);
+        if !collection_is_capped(&coll)? {
+            return Err(ArgumentError(format!("Collection '{}' must be capped to be tailed.", coll.namespace)).into());
+        }
+        let filter = filter.unwrap_or_else(Document::new);
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        options.cursor_type = CursorType::TailableAwait;
+        let cursor = coll.find(Some(filter.clone()), Some(options.clone()))?;
+        Ok(TailableCursor::new(db, filter, options, cursor, Self::tail_resume_field()))
+    }
+
+    /// Find all instances of this model matching the given query, reading from the nearest
+    /// replica set member matching one of the given tag sets.
+    ///
+    /// This routes reads to members in a specific region — tagged e.g. `{"region": "eu-west"}`
+    /// — in order to reduce latency for regional traffic. Tag sets are tried in order, & among
+    /// the members matching the first tag set which matches any, the nearest is read from. If
+    /// **no** member matches any of the tag sets, the read fails rather than silently crossing
+    /// regions. To instead fall back to the nearest member of any region, pass an empty tag set
+    /// last. Secondaries may lag behind the primary, so reads may be stale. See
+    /// [`find`](#method.find) for the handling of default options.
+    fn find_from_tagged(db: Database, filter: Option<Document>, tags: Vec<BTreeMap<String, String>>) -> Result<Vec<Self>> {
+        let mut options = FindOptions::new();
+        options.read_preference = Some(ReadPreference::new(ReadMode::Nearest, Some(tags)));
+        Self::find(db, filter, Some(options))
+    }
+
+    /// Verify that this model's [`read_preference`](#method.read_preference) can be satisfied, for
+    /// use as a boot time readiness check.
+    ///
+    /// This probes the collection with a test read using the read preference — a `find` for the
+    /// `_id` of at most one document — so that a service reading e.g. only from secondaries learns
+    /// at boot, rather than at its first query, that no matching member is reachable. The error of
+    /// the failed read is returned as is. The probe succeeds whether or not the collection has any
+    /// documents, & the document read, if any, is discarded. When the model has no read
+    /// preference, the probe uses the client's default, & so only checks that the collection can
+    /// be read. Note that a standalone server satisfies any read preference.
+    fn verify_read_preference(db: Database) -> Result<()> {
+        let coll = Self::collection(&db);
+        let mut options = FindOptions::new();
+        options.read_preference = Self::read_preference();
+        options.projection = Some(doc!{"_id": 1});
+        coll.find_one(None, Some(options))?;
+        Ok(())
+    }
+
+    /// Find all instances of this model matching the given query, caching the results in process
+    /// for the given TTL.
+    ///
+    /// This is for expensive, rarely changing queries, such as reference data lookups. Results
+    /// are cached by the namespace of the collection & a hash of the filter & of the options
+    /// which shape the results — the sort, projection, skip, limit, modifiers & whether partial
+    /// results are allowed — & identical queries within the TTL are answered from the cache
+    /// without querying the database. Any write to the model's collection made through this
+    /// trait invalidates its cached results; see the [`query_cache`](../query_cache/index.html)
+    /// module for the staleness this still allows, & for the bound on the cache's size. The
+    /// query is otherwise executed as by [`find`](#method.find).
+    fn find_cached(db: Database, filter: Option<Document>, options: Option<FindOptions>, ttl: Duration) -> Result<Vec<Self>> where Self: Clone + Send + 'static {
+        let namespace = Self::collection(&db).namespace;
+        let hash = query_cache_hash(filter.as_ref(), options.as_ref())?;
+        if let Some(instances) = query_cache::get::<Vec<Self>>(&namespace, hash) {
+            return Ok(instances);
+        }
+        let instances = Self::find(db, filter, options)?;
+        query_cache::put(&namespace, hash, instances.clone(), ttl);
+        Ok(instances)
+    }
+
+    /// Stream all instances of this model matching the given query in chunks, holding no more
+    /// than `max_buffered` deserialized instances in memory at once.
+    ///
+    /// This is for processing collections too large to load whole. The next chunk is only read
+    /// once the caller requests it, so memory use stays bounded however slowly each chunk is
+    /// processed. The cursor's `batch_size` is set to `max_buffered`, so that the driver never
+    /// holds more than one chunk's worth of raw documents either; a smaller `batch_size` in the
+    /// given options is kept, trading more round trips for less memory. The model's
+    /// [`default_sort`](#method.default_sort) is used if the options do not specify a sort.
+    fn find_streaming_capped(db: Database, filter: Option<Document>, max_buffered: usize) -> Result<CappedModelCursor<Self>> {
+        Self::find_streaming_capped_with_options(db, filter, max_buffered, None)
+    }
+
+    /// As [`find_streaming_capped`](#method.find_streaming_capped), with the given find options.
+    fn find_streaming_capped_with_options(
+        db: Database, filter: Option<Document>, max_buffered: usize, options: Option<FindOptions>,
+    ) -> Result<CappedModelCursor<Self>> {
+        let coll = Self::collection(&db);
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        if options.sort.is_none() {
+            options.sort = Self::default_sort();
+        }
+        options.allow_partial_results = options.allow_partial_results || Self::allow_partial_results();
+        if options.read_preference.is_none() {
+            options.read_preference = Self::read_preference();
+        }
+        let cap = max_buffered.max(1) as i32;
+        options.batch_size = Some(options.batch_size.map(|size| size.min(cap)).unwrap_or(cap));
+        let cursor = ModelCursor::new(coll.find(filter, Some(options))?);
+        Ok(CappedModelCursor::new(cursor, max_buffered))
+    }
+
+    /// Stream all instances of this model matching the given query, transforming each via `f`
+    /// as it is polled.
+    ///
+    /// This lets async pipelines transform & forward instances without collecting them. Errors
+    /// from `f` are yielded for their item alone, without ending the stream; see
+    /// [`ModelStream`](../stream/struct.ModelStream.html), which also notes that polling blocks
+    /// on the underlying synchronous driver. The model's [`default_sort`](#method.default_sort)
+    /// is used.
+    ///
+    /// Only available with the `async` feature.
+    #[cfg(feature = "async")]
+    fn stream_map<R, F: Fn(Self) -> Result<R>>(db: Database, filter: Option<Document>, f: F) -> Result<ModelStream<Self, R, F>> {
+        let coll = Self::collection(&db);
+        let mut options = read_options::<Self>();
+        options.sort = Self::default_sort();
+        options.projection = lazy_projection(&Self::lazy_fields(), &[]);
+        let cursor = ModelCursor::new(coll.find(filter, Some(options))?);
+        Ok(ModelStream::new(cursor, f))
+    }
+
+    /// Stream the output of the given aggregation pipeline against this model's collection,
+    /// deserializing each output document as `R`.
+    ///
+    /// This is the memory bounded path for exporting a large aggregation, such as a per-user
+    /// rollup: only the current batch of output is held in memory, & `allow_disk_use` lets the
+    /// server spill stages exceeding their memory limit to disk. The server side cursor is
+    /// killed if the stream is dropped before being exhausted. See
+    /// [`AggregateStream`](../stream/struct.AggregateStream.html), which, like every stream of
+    /// this crate, blocks on the underlying synchronous driver when polled.
+    ///
+    /// Only available with the `async` feature.
+    #[cfg(feature = "async")]
+    fn aggregate_stream<R: DeserializeOwned, P: Into<Vec<Document>>>(db: Database, pipeline: P, allow_disk_use: bool) -> Result<AggregateStream<R>> {
+        let coll = Self::collection(&db);
+        AggregateStream::new(coll.db.clone(), &coll.name(), pipeline.into(), allow_disk_use)
+    }
+
+    /// Delete any model instances matching the given query.
+    ///
+    /// When the model uses [`soft_delete`](#method.soft_delete), matching documents are soft
+    /// deleted, exactly as by `Model.delete`. Documents which have already been soft deleted keep
+    /// their original `deleted_at`, unless the filter itself constrains `deleted_at`. Use
+    /// [`hard_delete_many`](#method.hard_delete_many) to physically remove documents.
+    fn delete_many(db: Database, filter: Document) -> Result<()> {
+        if Self::soft_delete().is_none() {
+            return Self::hard_delete_many(db, filter);
+        }
+        let coll = Self::collection(&db);
+        let options = UpdateOptions{upsert: Some(false), write_concern: Some(Self::model_write_concern())};
+        query_cache::invalidate_after(&coll.namespace, || coll.update_many(soft_delete_many_filter(filter), soft_delete_update(), Some(options)))?;
+        Ok(())
+    }
+
+    /// Count the model instances which `Model::delete_many` would delete given the same query,
+    /// without deleting anything.
+    ///
+    /// This lets tooling preview the blast radius of a bulk delete — prompting "this will delete
+    /// N documents, proceed?" — before calling [`delete_many`](#method.delete_many). When the
+    /// model uses [`soft_delete`](#method.soft_delete), documents which are already soft deleted
+    /// are not counted, exactly as they would not be deleted again. As the collection may change
+    /// between the preview & the delete, the count is an estimate of what will be deleted.
+    fn delete_many_dry_run(db: Database, filter: Document) -> Result<i64> {
+        let filter = match Self::soft_delete() {
+            Some(_) => soft_delete_many_filter(filter),
+            None => filter,
+        };
+        Self::count(db, Some(filter), None)
+    }
+
+    /// Physically remove any model instances matching the given query, regardless of the
+    /// model's [`soft_delete`](#method.soft_delete) configuration. Useful for purging data.
+    fn hard_delete_many(db: Database, filter: Document) -> Result<()> {
+        Self::hard_delete_many_with_result(db, filter)?;
+        Ok(())
+    }
+
+    /// As [`hard_delete_many`](#method.hard_delete_many), returning the driver's result, which
+    /// holds the number of documents deleted.
+    fn hard_delete_many_with_result(db: Database, filter: Document) -> Result<DeleteResult> {
+        let coll = Self::collection(&db);
+        let mut res = query_cache::invalidate_after(&coll.namespace, || coll.delete_many(filter, Some(Self::model_write_concern())))?;
+        if let Some(err) = res.write_exception.take() {
+            return Err(WriteError(err).into());
+        }
+        Ok(res)
+    }
+
+    /// Update the document of the model instance having the given ID, returning the driver's
+    /// result.
+    ///
+    /// The result holds the matched & modified counts, & the `upserted_id` when `upsert` is set
+    /// in the given options, so callers can learn what the write did without a second query.
+    /// Unlike `Model.update`, this neither loads the updated instance nor stamps `updated_at`.
+    /// Any cached instance is [invalidated](#method.cache_invalidate). If the options do not
+    /// specify a write concern, the model's write concern is used.
+    fn update_by_id(db: Database, id: ObjectId, update: Document, options: Option<UpdateOptions>) -> Result<UpdateResult> {
+        let coll = Self::collection(&db);
+        let mut options = options.unwrap_or(UpdateOptions{upsert: Some(false), write_concern: None});
+        if options.write_concern.is_none() {
+            options.write_concern = Some(Self::model_write_concern());
+        }
+        let res = query_cache::invalidate_after(&coll.namespace, || coll.update_one(doc!{"_id": id.clone()}, update, Some(options)));
+        Self::cache_invalidate(&id);
+        let mut res = res?;
+        if let Some(err) = res.write_exception.take() {
+            return Err(WriteError(err).into());
+        }
+        Ok(res)
+    }
+
+    /// Update all documents of this model's collection matching the given query, returning the
+    /// driver's result, which holds the matched & modified counts.
+    ///
+    /// Neither stamps `updated_at` nor invalidates cached instances. If the options do not
+    /// specify a write concern, the model's write concern is used.
+    fn update_many(db: Database, filter: Document, update: Document, options: Option<UpdateOptions>) -> Result<UpdateResult> {
+        let coll = Self::collection(&db);
+        let mut options = options.unwrap_or(UpdateOptions{upsert: Some(false), write_concern: None});
+        if options.write_concern.is_none() {
+            options.write_concern = Some(Self::model_write_concern());
+        }
+        let mut res = query_cache::invalidate_after(&coll.namespace, || coll.update_many(filter, update, Some(options)))?;
+        if let Some(err) = res.write_exception.take() {
+            return Err(WriteError(err).into());
+        }
+        Ok(res)
+    }
+
+    /// Insert the given model instances into this model's collection.
+    ///
+    /// The given instances are inserted in order, split into as many batches as needed in order
+    /// to respect MongoDB's batch limits. Each batch will hold at most `chunk_size` documents
+    /// (defaults to [`DEFAULT_INSERT_CHUNK_SIZE`](./constant.DEFAULT_INSERT_CHUNK_SIZE.html)) & at
+    /// most [`MAX_INSERT_CHUNK_BYTES`](./constant.MAX_INSERT_CHUNK_BYTES.html) of encoded data.
+    ///
+    /// Returns the IDs of the inserted documents, in the same order as the given instances. If a
+    /// batch fails, an error is returned & no subsequent batches will be inserted.
+    ///
+    /// A batch failing with a transient error is retried up to
+    /// [`bulk_write_retries`](#method.bulk_write_retries) times, with an exponential backoff of
+    /// 100ms, 200ms & so on. As the server may have committed part of the batch before failing,
+    /// IDs are generated client side for all documents up front, & each retry first skips the
+    /// documents of the batch whose ID already exists. Inserts are thus applied at most once, but
+    /// an instance given with the ID of a document which already existed before the insert is
+    /// silently skipped by a retry, rather than failing with a duplicate key error.
+    fn insert_many(db: Database, instances: Vec<Self>, chunk_size: Option<usize>) -> Result<Vec<ObjectId>> {
+        let coll = Self::collection(&db);
+        let mut docs = vec![];
+        for mut instance in instances {
+            if let Some(timestamps) = instance.timestamps() {
+                touch_timestamps(timestamps);
+            }
+            instance.validate()?;
+            docs.push(instance.document_from_instance()?);
+        }
+        query_cache::invalidate_after(&coll.namespace, || {
+            insert_documents(&coll, docs, chunk_size, Self::model_write_concern(), Self::bulk_write_retries(), Self::generate_id)
+        })
+    }
+
+    /// Insert the given model instances into this model's collection, generating client-side IDs
+    /// for any instances which have no ID before inserting.
+    ///
+    /// This behaves as [`Model::insert_many`](#method.insert_many), except that the instances are
+    /// returned with their IDs populated. When the instances must reference each other — such
+    /// as when persisting a graph of models — call [`allocate_id`](#method.allocate_id) on each
+    /// instance first, so that the IDs are known up front & can be used to wire references
+    /// within the same batch.
+    fn insert_many_with_ids(db: Database, mut instances: Vec<Self>, chunk_size: Option<usize>) -> Result<Vec<Self>> {
+        let coll = Self::collection(&db);
+        let mut docs = vec![];
+        for instance in instances.iter_mut() {
+            instance.allocate_id()?;
+            if let Some(timestamps) = instance.timestamps() {
+                touch_timestamps(timestamps);
+            }
+            instance.validate()?;
+            docs.push(instance.document_from_instance()?);
+        }
+        query_cache::invalidate_after(&coll.namespace, || {
+            insert_documents(&coll, docs, chunk_size, Self::model_write_concern(), Self::bulk_write_retries(), Self::generate_id)
+        })?;
+        Ok(instances)
+    }
+
+    /// Find the model instance having the given ID.
+    ///
+    /// The model's [`cache_get`](#method.cache_get) hook is consulted first, & any instance
+    /// found in the database is passed to [`cache_put`](#method.cache_put).
+    fn find_by_id(db: Database, id: ObjectId) -> Result<Option<Self>> {
+        if let Some(instance) = Self::cache_get(&id) {
+            return Ok(Some(instance));
+        }
+        let instance = Self::find_one(db, Some(doc!{"_id": id}), None)?;
+        if let Some(ref instance) = instance {
+            instance.cache_put();
+        }
+        Ok(instance)
+    }
+
+    /// Find all model instances whose `field` has any of the given values.
+    ///
+    /// This is the batch fetch primitive for resolving references keyed on something other than
+    /// `_id`, such as external IDs or slugs, issuing a single `{field: {$in: values}}` query.
+    /// Results are ordered by the model's [`default_sort`](#method.default_sort), **not** by the
+    /// order of `values`, & there may be fewer results than values when some have no match —
+    /// or more, when `field` is not unique. Callers needing a mapping from value to instance
+    /// should build one from the results.
+    fn find_by_field_in<V: Into<Bson>>(db: Database, field: &str, values: Vec<V>) -> Result<Vec<Self>> {
+        if values.is_empty() {
+            return Ok(vec![]);
+        }
+        let values: Vec<Bson> = values.into_iter().map(Into::into).collect();
+        let mut filter = Document::new();
+        filter.insert(field, doc!{"$in": values});
+        Self::find(db, Some(filter), None)
+    }
+
+    /// Get the model instance having the given ID, returning an error if it does not exist.
+    ///
+    /// This is the same as [`find_by_id`](#method.find_by_id), except that a missing document
+    /// results in a [`WitherError::NotFound`](../error/enum.WitherError.html#variant.NotFound),
+    /// allowing the common not-found case to be propagated with `?`.
+    fn get_by_id(db: Database, id: ObjectId) -> Result<Self> {
+        match Self::find_by_id(db, id.clone())? {
+            Some(instance) => Ok(instance),
+            None => Err(WitherError::NotFound{collection: Self::COLLECTION_NAME.to_owned(), id}),
+        }
+    }
+
+    /// Atomically update the one model record matching the given filter, returning a model instance.
+    ///
+    /// This is a wrapper around MongoDB's `findAndModify` command. Under high contention, the
+    /// server may reject the operation with a transient write conflict. Such errors are retried
+    /// up to [`write_conflict_retries`](#method.write_conflict_retries) times, with an
+    /// exponential backoff of 10ms, 20ms, 40ms & so on, plus up to 10ms of random jitter in order
+    /// to avoid retrying in lockstep with other writers. Any other error is returned immediately.
+    fn find_one_and_update(db: Database, filter: Document, update: Document, options: Option<FindOneAndUpdateOptions>) -> Result<Option<Self>> {
+        let coll = Self::collection(&db);
+        let doc_option = query_cache::invalidate_after(&coll.namespace, || retry_on_write_conflict(Self::write_conflict_retries(), || {
+            Ok(coll.find_one_and_update(filter.clone(), update.clone(), options.clone())?)
+        }))?;
+        match doc_option {
+            Some(doc) => Ok(Some(Self::instance_from_document(doc)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Atomically lock the one model record matching the given filter for the given owner,
+    /// returning the locked instance, or `None` if no matching record is available.
+    ///
+    /// This is a document level pessimistic lock, for job processing patterns where optimistic
+    /// concurrency is not enough. The lock is held in the document's `lock_field` as
+    /// `{owner, expires_at}`, & is set via [`find_one_and_update`](#method.find_one_and_update)
+    /// only if the document is unlocked, its lock has expired, or it is already locked by the
+    /// same owner — in which case the lock is extended. **Locks expire after the given TTL**, so
+    /// that a record locked by a crashed owner becomes available again; an owner still working
+    /// once its TTL has elapsed may have lost the lock to another, so the TTL should comfortably
+    /// exceed the time needed to process the record. Release the lock with
+    /// [`unlock`](#method.unlock). The model should declare `lock_field` — such as an
+    /// `Option<bson::Document>` — so that saving a locked instance does not remove the lock.
+    fn find_one_and_lock(db: Database, filter: Document, lock_field: &str, owner: &str, ttl: Duration) -> Result<Option<Self>> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::milliseconds(duration_ms(ttl));
+        let mut unlocked = vec![];
+        for condition in vec![Bson::Document(doc!{"$exists": false}), Bson::Null] {
+            let mut doc = Document::new();
+            doc.insert(lock_field, condition);
+            unlocked.push(Bson::Document(doc));
+        }
+        let mut expired = Document::new();
+        expired.insert(format!("{}.expires_at", lock_field), doc!{"$lte": Bson::UtcDatetime(now)});
+        unlocked.push(Bson::Document(expired));
+        let mut owned = Document::new();
+        owned.insert(format!("{}.owner", lock_field), owner);
+        unlocked.push(Bson::Document(owned));
+
+        let filter = doc!{"$and": [filter, doc!{"$or": unlocked}]};
+        let mut lock = Document::new();
+        lock.insert(lock_field, doc!{"owner": owner, "expires_at": Bson::UtcDatetime(expires_at)});
+        let mut write_concern = Self::model_write_concern();
+        write_concern.j = true;
+        let options = FindOneAndUpdateOptions{upsert: Some(false), write_concern: Some(write_concern), return_document: Some(ReturnDocument::After), sort: None, projection: None, max_time_ms: None};
+        Self::find_one_and_update(db, filter, doc!{"$set": lock}, Some(options))
+    }
+
+    /// Atomically update the one model record matching the given filter, inserting it if none
+    /// matches, returning a model instance along with whether a new document was created.
+    ///
+    /// This behaves as [`find_one_and_update`](#method.find_one_and_update) with `upsert` set,
+    /// including its retry behavior, except that the document is always returned as it is after
+    /// the update, regardless of the `return_document` option. The outcome is taken from the
+    /// `findAndModify` command's `lastErrorObject`, which distinguishes creation from update in
+    /// atomic upsert flows.
+    fn find_one_and_upsert(db: Database, filter: Document, update: Document, options: Option<FindOneAndUpdateOptions>) -> Result<(Self, UpsertOutcome)> {
+        let options = options.unwrap_or_default();
+        let coll = Self::collection(&db);
+        let mut spec = doc!{
+            "findAndModify": coll.name(),
+            "query": filter,
+            "update": update,
+            "upsert": true,
+            "new": true,
+        };
+        if let Some(sort) = options.sort {
+            spec.insert("sort", sort);
+        }
+        if let Some(projection) = options.projection {
+            spec.insert("fields", projection);
+        }
+        if let Some(max_time_ms) = options.max_time_ms {
+            spec.insert("maxTimeMS", max_time_ms);
+        }
+        let write_concern = options.write_concern.unwrap_or_else(Self::model_write_concern);
+        spec.insert("writeConcern", write_concern.to_bson());
+
+        let res = query_cache::invalidate_after(&coll.namespace, || retry_on_write_conflict(Self::write_conflict_retries(), || {
+            Ok(coll.db.command(spec.clone(), CommandType::FindOneAndUpdate, None)?)
+        }))?;
+        let doc = match res.get("value") {
+            Some(&Bson::Document(ref doc)) => doc.clone(),
+            _ => return Err(ResponseError("Server failed to return the upserted document.".to_owned()).into()),
+        };
+        let outcome = match res.get_document("lastErrorObject").ok().and_then(|obj| obj.get("upserted")) {
+            Some(&Bson::ObjectId(ref id)) => UpsertOutcome::Upserted(id.clone()),
+            Some(_) => return Err(ResponseError("Server returned a non-ObjectId upserted ID.".to_owned()).into()),
+            None => UpsertOutcome::Matched,
+        };
+        Ok((Self::instance_from_document(doc)?, outcome))
+    }
+
+    /// Find the one model record matching your query, returning a model instance.
+    fn find_one(db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Option<Self>> {
+        let coll = Self::collection(&db);
+        let mut options = options.unwrap_or_else(FindOptions::new);
+        options.allow_partial_results = options.allow_partial_results || Self::allow_partial_results();
+        if options.read_preference.is_none() {
+            options.read_preference = Self::read_preference();
+        }
+        if options.projection.is_none() {
+            options.projection = lazy_projection(&Self::lazy_fields(), &[]);
+        }
+
+        // Unwrap result.
+        let doc_option = match coll.find_one(filter, Some(options)) {
+            Ok(doc_option) => doc_option,
+            Err(err) => return Err(err.into()),
+        };
+
+        // Unwrap option.
+        let doc = match doc_option {
+            Some(doc) => doc,
+            None => return Ok(None),
+        };
+
+        // Deserialize bson onto struct model.
+        let instance = Self::instance_from_document(doc)?;
+        Ok(Some(instance))
+    }
+
+    /// Find any one model instance matching the given filter, as an example of a match.
+    ///
+    /// This is semantically [`find_one`](#method.find_one), but signals at the call site that
+    /// any match will do — such as for a validation message like "email already in use by ...".
+    /// No sort is applied, leaving the server free to return whichever match it finds first.
+    fn find_any(db: Database, filter: Document) -> Result<Option<Self>> {
+        Self::find_one(db, Some(filter), None)
+    }
+
+    /// Whether any document of this model's collection matches the given filter.
+    ///
+    /// Only the `_id` of at most one document is fetched, making this cheaper than a `count`
+    /// or a [`find_any`](#method.find_any) when only existence matters.
+    fn exists(db: Database, filter: Document) -> Result<bool> {
+        let coll = Self::collection(&db);
+        let mut options = FindOptions::new();
+        options.projection = Some(doc!{"_id": 1});
+        options.limit = Some(1);
+        Ok(coll.find_one(Some(filter), Some(options))?.is_some())
+    }
+
+    /// Find a single page of model instances matching the given query, along with the total
+    /// number of matching documents.
+    ///
+    /// This is performed in a single round trip by way of an aggregation `$facet`, as opposed to
+    /// separate `find` & `count` operations. The given `filter` & `sort` are applied before the
+    /// facet, so both the page & the total are computed over exactly the same documents. If no
+    /// `sort` is given, the model's [`default_sort`](#method.default_sort) will be used.
+    ///
+    /// `page` is zero-based. `$facet` requires MongoDB `>= 3.4`; against older servers, the page
+    /// & the total are instead fetched by separate `find` & `count` operations, so the total may
+    /// disagree with the page should the collection be written to in between.
+    fn find_page_with_total(db: Database, filter: Option<Document>, page: i64, per_page: i64, sort: Option<Document>) -> Result<Page<Self>> {
+        if page < 0 || per_page < 1 {
+            return Err(ArgumentError("`page` must be non-negative & `per_page` must be positive.".to_owned()).into());
+        }
+        let coll = Self::collection(&db);
+        if server_version(&coll.db)? < ServerVersion::new(3, 4, 0) {
+            let mut options = FindOptions::new();
+            options.sort = sort;
+            options.skip = Some(page * per_page);
+            options.limit = Some(per_page);
+            let total = coll.count(filter.clone(), None)?;
+            let items = Self::find(db, filter, Some(options))?;
+            return Ok(Page{items, total, page, per_page});
+        }
+
+        // Build the pipeline. Filter & sort apply identically to both facet branches.
+        let mut pipeline = vec![doc!{"$match": filter.unwrap_or_else(Document::new)}];
+        if let Some(sort) = sort.or_else(Self::default_sort) {
+            pipeline.push(doc!{"$sort": sort});
+        }
+        pipeline.push(doc!{"$facet": {
+            "items": [{"$skip": page * per_page}, {"$limit": per_page}],
+            "total": [{"$count": "count"}],
+        }});
+
+        // Extract the single facet output document.
+        let facet_doc = match coll.aggregate(pipeline, Some(AggregateOptions::new()))?.next() {
+            Some(doc_res) => doc_res?,
+            None => return Err(ResponseError("Server failed to return the output of the `$facet` stage.".to_owned()).into()),
+        };
+
+        // Deserialize the page's bson docs onto struct models.
+        let mut items: Vec<Self> = vec![];
+        let item_docs = facet_doc.get_array("items")
+            .map_err(|err| ResponseError(format!("Error extracting 'items' of facet document: {:?}", err.description())))?;
+        for item in item_docs {
+            match item {
+                &Bson::Document(ref doc) => items.push(Self::instance_from_document(doc.clone())?),
+                _ => return Err(ResponseError("Server returned a non-document page item.".to_owned()).into()),
+            }
+        }
+
+        // The `$count` branch is empty when nothing matches.
+        let total = match facet_doc.get_array("total").ok().and_then(|total| total.first()) {
+            Some(&Bson::Document(ref doc)) => match doc.get("count") {
+                Some(&Bson::I32(count)) => count as i64,
+                Some(&Bson::I64(count)) => count,
+                _ => return Err(ResponseError("Server returned an invalid total count.".to_owned()).into()),
+            },
+            _ => 0,
+        };
+
+        Self::after_load_batch(&mut items)?;
+        Ok(Page{items, total, page, per_page})
+    }
+
+    /// Find up to `limit` model instances matching the given query, along with whether more
+    /// matching instances exist beyond them.
+    ///
+    /// This is for "load more" style lists, which need only know whether there is more to load
+    /// rather than how much. One extra instance is fetched & discarded, avoiding a separate count
+    /// query. If no `sort` is given, the model's [`default_sort`](#method.default_sort) will be
+    /// used; a stable sort is needed for subsequent loads to be consistent. `limit` must be
+    /// positive.
+    fn find_limited_has_more(db: Database, filter: Option<Document>, limit: i64, sort: Option<Document>) -> Result<(Vec<Self>, bool)> {
+        if limit < 1 {
+            return Err(ArgumentError("`limit` must be positive.".to_owned()).into());
+        }
+        let mut options = FindOptions::new();
+        options.sort = sort;
+        options.limit = Some(limit + 1);
+        let mut items = Self::find(db, filter, Some(options))?;
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+        Ok((items, has_more))
+    }
+
+    ////////////////////
+    // Instance Layer //
+
+    /// Delete this model instance by ID.
+    ///
+    /// If this model uses [soft delete](#method.soft_delete), the document will be marked as
+    /// deleted rather than removed, & the deletion is then
+    /// [cascaded](#method.soft_delete_cascade) to the model's related collections.
+    fn delete(&self, db: Database) -> Result<()> {
+        // Return an error if the instance was never saved.
+        let id = self.id().ok_or(DefaultError("This instance has no ID. Can not be deleted.".to_string()))?;
+
+        let coll = Self::collection(&db);
+        if Self::soft_delete().is_some() {
+            let options = UpdateOptions{upsert: Some(false), write_concern: Some(Self::model_write_concern())};
+            let res = query_cache::invalidate_after(&coll.namespace, || coll.update_one(doc!{"_id": id.clone()}, soft_delete_update(), Some(options)));
+            Self::cache_invalidate(&id);
+            res?;
+            return self.soft_delete_cascade(db);
+        }
+        let res = query_cache::invalidate_after(&coll.namespace, || coll.delete_one(doc!{"_id": id.clone()}, Some(Self::model_write_concern())));
+        Self::cache_invalidate(&id);
+        res?;
+        Ok(())
+    }
+
+    /// Soft delete the documents of the model's [related collections](#method.soft_delete_cascades)
+    /// which belong to this instance.
+    ///
+    /// This is called by `Model.delete` after soft deleting the instance itself. Each related
+    /// collection is updated in turn, setting `deleted_at` on each document whose join field
+    /// holds this instance's ID & which is not already soft deleted. This is best effort rather
+    /// than transactional, as the underlying driver does not support transactions: should an
+    /// update fail, the error is returned & the collections updated before it stay updated. As
+    /// documents already soft deleted are skipped, simply retrying is safe & completes the
+    /// cascade. Cascades are not applied to `Model::delete_many`.
+    fn soft_delete_cascade(&self, db: Database) -> Result<()> {
+        let id = self.id().ok_or(DefaultError("This instance has no ID. Can not cascade its deletion.".to_string()))?;
+        for cascade in Self::soft_delete_cascades() {
+            let coll = db.collection(&cascade.collection);
+            let mut filter = doc!{};
+            filter.insert(cascade.field.clone(), id.clone());
+            filter.insert(SOFT_DELETE_FIELD, Bson::Null);
+            let options = UpdateOptions{upsert: Some(false), write_concern: Some(Self::model_write_concern())};
+            let res = query_cache::invalidate_after(&coll.namespace, || coll.update_many(filter, soft_delete_update(), Some(options)))?;
+            if let Some(err) = res.write_exception {
+                error!("Error cascading soft delete to '{}': {:?}", coll.namespace, err.description());
+                return Err(WriteError(err).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the current model instance.
+    ///
+    /// In order to make this method as flexible as possible, its behavior varies a little based
+    /// on the input and the state of the instance.
+    ///
+    /// When the instance already has an ID, this method will operate purely based on the instance
+    /// ID. If no ID is present, and no `filter` has been specified, then an ID will be generated.
+    ///
+    /// If a `filter` is specified, and no ID exists for the instance, then the filter will be used
+    /// and the first document matching the filter will be replaced by this instance. This is
+    /// useful when the model has unique indexes on fields which need to be the target of the save
+    /// operation.
+    fn save(&mut self, db: Database, filter: Option<Document>) -> Result<()> {
+        let coll = Self::collection(&db);
+        if let Some(timestamps) = self.timestamps() {
+            touch_timestamps(timestamps);
+        }
+        self.validate()?;
+        let instance_doc = self.document_from_instance()?;
+
+        // Ensure that journaling is set to true for this call, as we need to be able to get an ID back.
+        let mut write_concern = Self::model_write_concern();
+        write_concern.j = true;
+
+        // Handle case where instance already has an ID.
+        let mut id_needs_update = false;
+        let _filter = if let Some(id) = self.id() {
+            doc!{"_id": id}
+
+        // Handle case where no filter and no ID exist.
+        } else if filter == None {
+            let new_id = self.allocate_id()?;
+            doc!{"_id": new_id}
+
+        // Handle case where no ID exists, and a filter has been provided.
+        } else {
+            id_needs_update = true;
+            filter.unwrap()
+        };
+
+        // Save the record by replacing it entirely, or upserting if it doesn't already exist.
+        let opts = FindOneAndUpdateOptions{upsert: Some(true), write_concern: Some(write_concern), return_document: Some(ReturnDocument::After), sort: None, projection: None, max_time_ms: None};
+        let updated_doc = match query_cache::invalidate_after(&coll.namespace, || save_document(&coll, _filter, instance_doc, &Self::lazy_fields(), opts))? {
+            Some(doc) => doc,
+            None => return Err(ResponseError("Server failed to return the updated document. Update may have failed.".to_owned()).into()),
+        };
+
+        // Update instance ID if needed.
+        if id_needs_update {
+            let response_id = match updated_doc.get_object_id("_id") {
+                Ok(id) => id,
+                Err(_) => return Err(ResponseError("Server failed to return ObjectId of updated document.".to_owned()).into()),
+            };
+            self.set_id(response_id.clone());
+        };
+        if let Some(id) = self.id() {
+            Self::cache_invalidate(&id);
+        }
+
+        return Ok(());
+    }
+
+    /// Whether saving this instance would change its stored document.
+    ///
+    /// The stored document is fetched by ID & compared field by field against this instance as
+    /// it would be written, so that callers of idempotent update flows can skip no-op writes,
+    /// reducing write load & oplog churn. Field order is not significant. **This costs a read**,
+    /// so it only pays off when writes are more costly than reads, or are frequently no-ops.
+    /// Fields present in the stored document but unknown to the model count as a difference, as
+    /// a save would remove them. Always `true` for an instance without an ID or with no stored
+    /// document. Note that [`save`](#method.save) itself touches any
+    /// [`timestamps`](#method.timestamps), which this does not.
+    fn needs_save(&self, db: Database) -> Result<bool> {
+        let id = match self.id() {
+            Some(id) => id,
+            None => return Ok(true),
+        };
+        let coll = Self::collection(&db);
+        let stored = match coll.find_one(Some(doc!{"_id": id}), None)? {
+            Some(stored) => stored,
+            None => return Ok(true),
+        };
+        Ok(!documents_equivalent(&stored, &self.document_from_instance()?))
+    }
+
+    /// The changes to each top level field from this instance to the given one, comparing both
+    /// as they would be written.
+    ///
+    /// This is a building block for audit trails: load an instance, update it, then load it again
+    /// via [`find_by_id`](#method.find_by_id) & record `before.diff(&after)?` to an audit
+    /// collection.
+    ///
+    /// Fields are compared as in [`needs_save`](#method.needs_save), so field order within
+    /// embedded documents is not significant, & a changed embedded document is reported whole.
+    /// The model's [`content_hash_field`](#method.content_hash_field), if any, is never reported.
+    /// Changes are ordered as this instance's fields, followed by fields only the given one has.
+    fn diff(&self, other: &Self) -> Result<Vec<FieldChange>> {
+        let mut changes = diff_documents(&self.document_from_instance()?, &other.document_from_instance()?);
+        if let Some(field) = Self::content_hash_field() {
+            changes.retain(|change| change.field != field);
+        }
+        Ok(changes)
+    }
+
+    /// The public view of this instance, as JSON, omitting the model's
+    /// [`internal_fields`](#method.internal_fields).
+    ///
+    /// This separates the shape exposed by an API from the stored shape: the instance is
+    /// serialized as by serde, its internal fields are removed, & it is converted as described
+    /// in the [`json`](../json/index.html) module. Storage is unaffected.
+    ///
+    /// Only available with the `json` feature.
+    #[cfg(feature = "json")]
+    fn to_public(&self) -> Result<Value> {
+        let mut doc = match bson::to_bson(&self)? {
+            Bson::Document(doc) => doc,
+            _ => return Err(DefaultError("Failed to convert struct to a bson document.".to_string()).into()),
+        };
+        let mut internal = Self::internal_fields();
+        internal.extend(Self::content_hash_field());
+        if Self::schema_version().is_some() {
+            internal.push(Self::schema_version_field());
+        }
+        for field in internal {
+            doc.remove(field);
+        }
+        Ok(document_to_json(doc))
+    }
+
+    /// Apply the given RFC 7396 JSON Merge Patch, such as the body of an HTTP `PATCH` request, to
+    /// this instance's document, refreshing this instance from the updated document.
+    ///
+    /// `null`s delete their field & objects merge recursively into embedded documents, as
+    /// described by [`json::merge_patch`](../json/fn.merge_patch.html), which translates the patch
+    /// into a single `$set` & `$unset` update. Field names are the serialized names. The patch is
+    /// first applied to this instance in memory, & if the result is not a valid instance — the
+    /// patch set a field to the wrong type, or removed a required field — the error is returned
+    /// & nothing is written. A patch changing nothing writes nothing. The instance must have an
+    /// ID.
+    ///
+    /// Only available with the `json` feature.
+    #[cfg(feature = "json")]
+    fn apply_merge_patch(&mut self, db: Database, patch: Value) -> Result<()> {
+        let mut doc = self.document_from_instance()?;
+        let update = match merge_patch(&mut doc, patch)? {
+            Some(update) => update,
+            None => return Ok(()),
+        };
+        Self::instance_from_document(doc)?.validate()?;
+        update_in_place(self, db, update)
+    }
+
+    /// A stable hash of this instance's content, for telling whether a record has changed
+    /// without comparing it field by field.
+    ///
+    /// The instance is serialized with `_id`, `created_at` & `updated_at` excluded & with the
+    /// fields of every document sorted, so that the hash depends only on the content itself.
+    /// The hash is stable across platforms & releases, so it may be persisted; set a
+    /// [`content_hash_field`](#method.content_hash_field) to store it with each write, & use
+    /// [`save_if_content_changed`](#method.save_if_content_changed) to skip unchanged writes
+    /// when reconciling with an external source.
+    fn content_hash(&self) -> Result<u64> {
+        match bson::to_bson(&self)? {
+            bson::Bson::Document(doc) => document_content_hash(&doc, Self::content_hash_field()),
+            _ => Err(DefaultError("Failed to convert struct to a bson document.".to_string()).into()),
+        }
+    }
+
+    /// The field in which each written document's [`content_hash`](#method.content_hash) is
+    /// stored, as an `i64`. This field is itself excluded from the hash. Defaults to `None`, not
+    /// storing the hash.
+    fn content_hash_field() -> Option<&'static str> {
+        None
+    }
+
+    /// Save this instance, only if its [`content_hash`](#method.content_hash) differs from the
+    /// hash stored with its document, returning whether it was saved.
+    ///
+    /// This costs a read of only the stored hash, making "only write if changed" reconciliation
+    /// with an external source cheap. The model must have a
+    /// [`content_hash_field`](#method.content_hash_field). An instance without an ID, or with no
+    /// stored document, is always saved.
+    fn save_if_content_changed(&mut self, db: Database) -> Result<bool> {
+        let field = match Self::content_hash_field() {
+            Some(field) => field,
+            None => return Err(ArgumentError("Model must have a content hash field for this operation.".to_owned()).into()),
+        };
+        if let Some(id) = self.id() {
+            let mut options = FindOptions::new();
+            let mut projection = Document::new();
+            projection.insert(field, 1);
+            options.projection = Some(projection);
+            let stored = Self::collection(&db).find_one(Some(doc!{"_id": id}), Some(options))?;
+            let stored_hash = stored.as_ref().and_then(|doc| doc.get_i64(field).ok());
+            if stored_hash == Some(self.content_hash()? as i64) {
+                return Ok(false);
+            }
+        }
+        self.save(db, None)?;
+        Ok(true)
+    }
+
+    /// Save the current model instance, only if its stored `updated_at` still matches the given
+    /// value, which should be the `updated_at` of the instance as it was loaded.
+    ///
+    /// This is a lightweight form of optimistic concurrency control, built upon the model's
+    /// [`timestamps`](#method.timestamps), guarding against overwriting the edits of another
+    /// writer. If the stored document has since been modified — or no longer exists — nothing
+    /// is written & a [`WitherError::Stale`](../error/enum.WitherError.html#variant.Stale) is
+    /// returned, at which point the caller should reload the instance & retry. Otherwise the
+    /// instance is replaced with its `updated_at` touched, as by [`save`](#method.save). The
+    /// instance's timestamps are only touched once the write has succeeded, so that a stale
+    /// instance keeps its loaded `updated_at` for the caller to inspect. An expected value of
+    /// `None` matches documents having no `updated_at`.
+    ///
+    /// The instance must have an ID & must manage timestamps. As BSON datetimes have millisecond
+    /// precision, the expected value should come from a loaded instance, not the system clock.
+    fn save_if_unchanged(&mut self, db: Database, expected_updated_at: Option<DateTime<Utc>>) -> Result<()> {
+        let id = match self.id() {
+            Some(id) => id,
+            None => return Err(ArgumentError("Model must have an ObjectId for this operation.".to_owned()).into()),
+        };
+        if self.timestamps().is_none() {
+            return Err(ArgumentError("Model must manage timestamps for this operation.".to_owned()).into());
+        }
+        self.validate()?;
+
+        // Touch the written document, leaving the instance as it is should the write fail.
+        let now = Utc::now();
+        let mut instance_doc = self.document_from_instance()?;
+        touch_document_timestamps(&mut instance_doc, now);
+
+        // Only match the document if it is unchanged. A null matches a missing field as well.
+        let mut filter = doc!{"_id": id.clone()};
+        filter.insert(UPDATED_AT_FIELD, expected_updated_at.map(Bson::UtcDatetime).unwrap_or(Bson::Null));
+
+        let coll = Self::collection(&db);
+        let opts = FindOneAndUpdateOptions{upsert: Some(false), write_concern: Some(Self::model_write_concern()), return_document: Some(ReturnDocument::After), sort: None, projection: None, max_time_ms: None};
+        match query_cache::invalidate_after(&coll.namespace, || save_document(&coll, filter, instance_doc, &Self::lazy_fields(), opts))? {
+            Some(_) => {
+                Self::cache_invalidate(&id);
+                if let Some(timestamps) = self.timestamps() {
+                    touch_timestamps_at(timestamps, now);
+                }
+                Ok(())
+            },
+            None => Err(WitherError::Stale{collection: Self::COLLECTION_NAME.to_owned(), id}),
+        }
+    }
+
+    /// Generate a new client-side ID for an instance of this model.
+    ///
+    /// Used by [`allocate_id`](#method.allocate_id) — and so by `Model.save` — as well as by
+    /// `Model::insert_many`. Defaults to `ObjectId::new`, whose IDs embed their creation time &
+    /// a machine & process identifier. Systems exposing IDs publicly which must not leak either
+    /// may return [`random_object_id`](./fn.random_object_id.html) instead, at the cost of the
+    /// natural time ordering of IDs: sorting by `_id` no longer sorts by creation, & the
+    /// creation time can no longer be read from an ID. Note that an instance saved by filter
+    /// without an ID is upserted, & so is given an ID by the server.
+    fn generate_id() -> Result<ObjectId> {
+        ObjectId::new().map_err(|err| OIDError(err).into())
+    }
+
+    /// Get the ID of this model instance, generating a new client-side ID first if it has none.
+    ///
+    /// This allows the ID of a new instance to be known before it is ever written, so that it
+    /// may be referenced by other instances persisted along with it. A subsequent
+    /// [`save`](#method.save) will operate by this ID. The ID is generated by
+    /// [`generate_id`](#method.generate_id).
+    fn allocate_id(&mut self) -> Result<ObjectId> {
+        if let Some(id) = self.id() {
+            return Ok(id);
+        }
+        let new_id = Self::generate_id()?;
+        self.set_id(new_id.clone());
+        Ok(new_id)
+    }
+
+    /// Update the current model instance.
+    ///
+    /// As this method is simply a wrapper around MongoDB's
+    /// [FindOneAndUpdate](https://docs.mongodb.com/v3.2/reference/method/db.collection.findOneAndUpdate/)
+    /// operation, the `update` argument must be a valid update document. This operation targets the model,
+    /// instance by the instance's ID. If its ID is `None`, this method will return an error.
+    /// All other aspects of this method's input are passthrough.
+    ///
+    /// This method will consume `self`, and will return a new instance of `Self` based on the given
+    /// return options (`ReturnDocument::Before | ReturnDocument:: After`).
+    ///
+    /// In order to provide consistent behavior, this method will also ensure that the operation's write
+    /// concern `journaling` is set to `true`, so that we can receive a complete output document.
+    ///
+    /// If this model instance was never written to the database, this operation will return an error.
+    fn update(mut self, db: Database, update: Document, opts: Option<FindOneAndUpdateOptions>) -> Result<Self> {
+        // Extract model's ID & use as filter for this operation.
+        let id = match self.id() {
+            Some(id) => id,
+            None => {
+                return Err(ArgumentError("Model must have an ObjectId for this operation.".to_owned()).into());
+            }
+        };
+        let filter = doc!{"_id": id.clone()};
+
+        // Stamp the update time of models having timestamps.
+        let mut update = update;
+        if self.timestamps().is_some() {
+            stamp_updated_at(&mut update);
+        }
+
+        // Ensure that journaling is set to true for this call for full output document.
+        // TODO: should probably encapsulate this as a unit-testable function.
+        let options = match opts {
+            Some(mut options) => {
+                options.write_concern = match options.write_concern {
+                    Some(mut wc) => {
+                        wc.j = true;
+                        Some(wc)
+                    },
+                    None => {
+                        let mut wc = Self::model_write_concern();
+                        wc.j = true;
+                        Some(wc)
+                    }
+                };
+                options
+            },
+            None => {
+                let mut options = FindOneAndUpdateOptions::default();
+                let mut wc = Self::model_write_concern();
+                wc.j = true;
+                options.write_concern = Some(wc);
+                options
+            }
+        };
+
+        // Perform a FindOneAndUpdate operation on this model's document by ID. Will fail if this
+        // model instance was never saved to the database to begin with.
+        let res = Self::find_one_and_update(db, filter, update, Some(options));
+        Self::cache_invalidate(&id);
+        match res? {
+            Some(instance) => Ok(instance),
+            None => Err(ResponseError("Server failed to return the updated document. Update may have failed.".to_owned()).into()),
+        }
+    }
+
+    /// Atomically raise the given field of this instance's document to `value`, if `value` is
+    /// greater than the stored value, refreshing this instance from the updated document.
+    ///
+    /// This uses the `$max` update operator, which only writes when the new value is greater —
+    /// or when the field is missing — so concurrent writers tracking a high water mark, such as
+    /// the highest score seen, can never lower it. The instance must have an ID.
+    fn update_max(&mut self, db: Database, field: &str, value: Bson) -> Result<()> {
+        let mut max = Document::new();
+        max.insert_bson(field.to_owned(), value);
+        update_in_place(self, db, doc!{"$max": max})
+    }
+
+    /// Atomically lower the given field of this instance's document to `value`, if `value` is
+    /// less than the stored value, refreshing this instance from the updated document.
+    ///
+    /// This uses the `$min` update operator; see [`update_max`](#method.update_max).
+    fn update_min(&mut self, db: Database, field: &str, value: Bson) -> Result<()> {
+        let mut min = Document::new();
+        min.insert_bson(field.to_owned(), value);
+        update_in_place(self, db, doc!{"$min": min})
+    }
+
+    /// Release the lock held on this instance's document by the given owner, as taken by
+    /// [`find_one_and_lock`](#method.find_one_and_lock), returning `true` if it was released.
+    ///
+    /// Nothing is released if the lock is held by another owner, such as after this owner's lock
+    /// expired & was taken over. This instance itself is not modified. Any cached instance is
+    /// [invalidated](#method.cache_invalidate).
+    fn unlock(&self, db: Database, lock_field: &str, owner: &str) -> Result<bool> {
+        let id = match self.id() {
+            Some(id) => id,
+            None => return Err(ArgumentError("Model must have an ObjectId for this operation.".to_owned()).into()),
+        };
+        let mut filter = doc!{"_id": id.clone()};
+        filter.insert(format!("{}.owner", lock_field), owner);
+        let mut unset = Document::new();
+        unset.insert(lock_field, "");
+        let res = Self::update_many(db, filter, doc!{"$unset": unset}, None);
+        Self::cache_invalidate(&id);
+        Ok(res?.modified_count > 0)
+    }
+
+    /////////////////////
+    // Lifecycle Hooks //
+
+    /// The fields of this model which must be non-empty whenever an instance is written.
+    ///
+    /// serde only requires that fields be present, so this catches values which are present but
+    /// empty: a field is considered empty when it is missing or null, an empty string, a numeric
+    /// zero, or an empty array or embedded document. Fields are given by their serialized name,
+    /// & may be dotted paths into embedded documents. Checked by the default
+    /// [`validate`](#method.validate). Defaults to no fields.
+    fn required_fields() -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// The ranges of values allowed for numeric fields of this model whenever an instance is
+    /// written, such as that an `age` be between `0` & `150`.
+    ///
+    /// A range may be open ended, having only a `min` or a `max`. Fields which are missing or
+    /// null are not checked, so list them in [`required_fields`](#method.required_fields) as
+    /// well if they must be present. Checked by the default [`validate`](#method.validate):
+    ///
+    /// ```rust,ignore
+    /// fn field_ranges() -> Vec<FieldRange> {
+    ///     vec![FieldRange::between("age", 0.0, 150.0), FieldRange::at_least("balance", 0.0)]
+    /// }
+    /// ```
+    ///
+    /// Defaults to no ranges.
+    fn field_ranges() -> Vec<FieldRange> {
+        vec![]
+    }
+
+    /// A hook which is called on every model instance before it is written by `Model.save`,
+    /// `Model.save_if_unchanged`, `Model::insert_many` & seeding, as well as by `Tracked.save`.
+    ///
+    /// Returning an error will cause the write to fail before anything is sent to the database.
+    /// Defaults to checking that none of the model's [`required_fields`](#method.required_fields)
+    /// are empty, returning `WitherError::MissingRequiredField` for the first which is, & then
+    /// that none of its [`field_ranges`](#method.field_ranges) are violated, returning
+    /// `WitherError::OutOfRange` for the first which is. Overriding implementations should
+    /// perform these checks too, via `wither::model::check_required_fields` &
+    /// `wither::model::check_field_ranges`. Partial updates, such as via `Model.update`, are not
+    /// validated.
+    fn validate(&self) -> Result<()> {
+        let (fields, ranges) = (Self::required_fields(), Self::field_ranges());
+        if fields.is_empty() && ranges.is_empty() {
+            return Ok(());
+        }
+        let doc = self.document_from_instance()?;
+        check_required_fields(&doc, &fields)?;
+        check_field_ranges(&doc, &ranges)
+    }
+
+    /// A hook which is called on every model instance loaded from the database.
+    ///
+    /// This is the place to populate computed or virtual fields — fields derived from others,
+    /// such as a `full_name` derived from `first_name` & `last_name`. Such fields should be
+    /// marked with `#[serde(skip)]` so that they are never persisted to the database, and will
+    /// need to implement `Default` so that they can be deserialized before this hook runs.
+    ///
+    /// ```rust,ignore
+    /// #[serde(skip)]
+    /// pub full_name: String,
+    ///
+    /// // snip ...
+    ///
+    /// fn after_load(&mut self) -> wither::error::Result<()> {
+    ///     self.full_name = format!("{} {}", self.first_name, self.last_name);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// Returning an error will cause the operation which loaded the instance to fail. Defaults
+    /// to a no-op.
+    fn after_load(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// A hook which is called once with all of the model instances loaded by a single `find`.
+    ///
+    /// This complements [`after_load`](#method.after_load), which has already been called on
+    /// each instance, by allowing enrichment to be performed in bulk — such as populating a
+    /// transient field of every instance with a single cache round trip, rather than one lookup
+    /// per instance. It is called by every read which loads a `Vec` of instances, such as
+    /// `Model::find`, `Model::find_with_record_id` & `Model::find_page_with_total`, even when no
+    /// instances were found. A `CappedModelCursor` calls it with each chunk it yields.
+    ///
+    /// Returning an error will cause the `find` to fail. Defaults to a no-op.
+    fn after_load_batch(_models: &mut Vec<Self>) -> Result<()> {
+        Ok(())
+    }
+
+    /// A hook which transforms every whole document serialized from a model instance, before it
+    /// is written to the database.
+    ///
+    /// This is distinct from serde's field attributes, operating on the entire serialized
+    /// document, such as to compress a large field. It is applied by
+    /// [`document_from_instance`](#method.document_from_instance), & so to `Model.save`,
+    /// `Model::insert_many` & seeds; it is not applied to update documents, such as those given
+    /// to `Model.update`. It must be reversed by [`from_stored_document`](#method.from_stored_document).
+    /// Defaults to the identity.
+    ///
+    /// ```rust,ignore
+    /// fn to_stored_document(mut document: Document) -> wither::error::Result<Document> {
+    ///     let body = document.get_str("body").unwrap_or_default().to_owned();
+    ///     document.insert("body", Bson::Binary(BinarySubtype::Generic, compress(body.as_bytes())));
+    ///     Ok(document)
+    /// }
+    /// ```
+    fn to_stored_document(document: Document) -> Result<Document> {
+        Ok(document)
+    }
+
+    /// A hook which transforms every whole document read from the database, before it is
+    /// deserialized into a model instance. The reverse of
+    /// [`to_stored_document`](#method.to_stored_document).
+    ///
+    /// It is applied by [`instance_from_document`](#method.instance_from_document), & so to all
+    /// reads of model instances. Defaults to the identity.
+    fn from_stored_document(document: Document) -> Result<Document> {
+        Ok(document)
+    }
+
+    /////////////////////////
+    // Convenience Methods //
+
+    /// Get a builder of instances of this model, which validates each instance upon `build`.
+    ///
+    /// See the [`builder`](../builder/index.html) module for more details.
+    fn builder() -> ModelBuilder<Self> {
+        ModelBuilder::new()
+    }
+
+    /// Attempt to serialize the given bson document into an instance of this model.
+    ///
+    /// The document is first passed through the model's
+    /// [`from_stored_document`](#method.from_stored_document) hook, & the model's
+    /// [`after_load`](#method.after_load) hook is called on the new instance.
+    fn instance_from_document(document: bson::Document) -> Result<Self> {
+        let document = Self::from_stored_document(document)?;
+        let stored = if Self::warn_unknown_fields() { Some(document.clone()) } else { None };
+        let mut instance = match bson::from_bson::<Self>(bson::Bson::Document(document)) {
+            Ok(inst) => inst,
+            Err(err) => return Err(DecoderError(err).into()),
+        };
+        if let Some(stored) = stored {
+            let unknown = instance_unknown_fields(&instance, &stored)?;
+            if !unknown.is_empty() {
+                let id = stored.get("_id").map(|id| id.to_string()).unwrap_or_default();
+                warn!("Document {} of '{}' holds fields unknown to the model: {}.", id, Self::COLLECTION_NAME, unknown.join(", "));
+            }
+        }
+        instance.after_load()?;
+        Ok(instance)
+    }
+
+    /// Attempt to serialize this model instance into a bson document.
+    ///
+    /// The model's [`content_hash`](#method.content_hash) & [`schema_version`](#method.schema_version)
+    /// are stamped into the document if configured, which is then passed through the model's
+    /// [`to_stored_document`](#method.to_stored_document) hook.
+    fn document_from_instance(&self) -> Result<Document> {
+        match bson::to_bson(&self)? {
+            bson::Bson::Document(mut doc) => {
+                if let Some(field) = Self::content_hash_field() {
+                    let hash = document_content_hash(&doc, Some(field))?;
+                    doc.insert(field, hash as i64);
+                }
+                if let Some(version) = Self::schema_version() {
+                    doc.insert(Self::schema_version_field(), version);
+                }
+                Self::to_stored_document(doc)
+            },
+            _ => Err(DefaultError("Failed to convert struct to a bson document.".to_string()).into()),
+        }
+    }
+
+    ///////////////////////
+    // Maintenance Layer //
+
+    /// Get the vector of index models for this model.
+    fn indexes() -> Vec<IndexModel> {
+        vec![]
+    }
+
+    /// Get the name of the declared index which best matches the fields of the given filter.
+    ///
+    /// This is a heuristic over the model's [`indexes`](#method.indexes): an index is a
+    /// candidate when the first field of its key pattern is queried by the filter, & candidates
+    /// are ranked by how many of their leading key fields the filter queries, then by
+    /// uniqueness, then by having fewer keys. Top level fields of the filter & of any top level
+    /// `$and` are considered. Text indexes are never chosen. Returns `None` when no declared
+    /// index matches.
+    ///
+    /// The server's query planner usually chooses well by itself, so this is best used to hint
+    /// hot queries whose plans are known to flip. The result can be passed as a hint, & is
+    /// easily overridden by hinting a specific index instead:
+    ///
+    /// ```rust,ignore
+    /// let mut options = FindOptions::new();
+    /// if let Some(index) = User::best_index_for(&filter) {
+    ///     options.modifiers = Some(doc!{"$hint": index});
+    /// }
+    /// let users = User::find(db.clone(), Some(filter), Some(options))?;
+    /// ```
+    fn best_index_for(filter: &Document) -> Option<String> {
+        best_index(&Self::indexes(), filter)
+    }
+
+    /// Get the usage of each index of this model's collection, least used first, via the
+    /// `$indexStats` aggregation.
+    ///
+    /// This makes removing dead indexes — which only cost write overhead — a data driven
+    /// decision: a declared index with few or no accesses over a long enough period is a
+    /// candidate for removal. **Statistics are kept in memory by each server**, so they reset
+    /// whenever a server restarts, & each member of a replica set only counts the operations it
+    /// served itself; after a primary change, the new primary's counts start from its own
+    /// restart. Check `since` before drawing conclusions. Requires MongoDB 3.2+.
+    fn index_usage(db: Database) -> Result<Vec<IndexUsage>> {
+        let declared: Vec<String> = Self::indexes().iter().map(index_name).collect();
+        let mut usage = vec![];
+        for stats in Self::aggregate(db, vec![doc!{"$indexStats": {}}], None)? {
+            usage.extend(index_usage_from_stats(&stats?, &declared));
+        }
+        usage.sort_by_key(|usage| usage.accesses);
+        Ok(usage)
+    }
+
+    /// The minimum server version needed to support the given index, if any.
+    ///
+    /// [`Model::sync`](#method.sync) skips — with a warning, rather than failing the boot — any
+    /// index which the connected server, as reported by `buildInfo`, is too old to support. This
+    /// allows a single codebase to be deployed against older clusters. The default detects the
+    /// features of indexes which gate on the server version as follows:
+    ///
+    /// - wildcard keys (`$**`) require MongoDB 4.2.
+    /// - `2dsphere` version 3 & `text` version 3 indexes require MongoDB 3.2.
+    ///
+    /// Override this to gate indexes on features which are not detected, falling back to
+    /// [`index_min_server_version`](./fn.index_min_server_version.html) for the rest.
+    fn index_min_server_version(index: &IndexModel) -> Option<ServerVersion> {
+        index_min_server_version(index)
+    }
+
+    /// Get the vector of migration objects for this model.
+    fn migrations() -> Vec<Box<Migration>> {
+        vec![]
+    }
+
+    /// Get a serializable snapshot of everything this model declares about its collection: its
+    /// name, time-series configuration, validator, shard key, indexes & migration manifest.
+    ///
+    /// This is for infrastructure as code workflows, such as writing the descriptor of each
+    /// model to JSON on every release so that schemas can be diffed across releases, or fed to
+    /// external tooling. The descriptor is deterministic, & builds on the
+    /// [`migration_manifest`](#method.migration_manifest). Indexes include those needed by the
+    /// model's soft delete & shard key, whatever the server version, & an error is returned if
+    /// they would exceed MongoDB's limits. As the descriptor is built from the model's
+    /// declarations alone, nothing is read from the database.
+    fn schema_descriptor() -> Result<SchemaDescriptor> {
+        let mut indexes = declared_indexes::<Self>()?;
+        indexes.sort_by_key(index_name);
+        Ok(SchemaDescriptor{
+            collection: Self::COLLECTION_NAME.to_owned(),
+            timeseries: Self::timeseries(),
+            validator: Self::validator(),
+            validation_level: Self::validation_level().to_owned(),
+            validation_action: Self::validation_action().to_owned(),
+            shard_key: Self::shard_key(),
+            indexes: indexes.iter().map(index_descriptor).collect(),
+            migrations: Self::migration_manifest(),
+        })
+    }
+
+    /// Get a serializable description of each of this model's [`migrations`](#method.migrations),
+    /// in order.
+    ///
+    /// This turns migration code into data which reviewers can inspect, such as by serializing
+    /// the manifest to JSON as a deploy artifact. See
+    /// [`Migration::spec`](../migration/trait.Migration.html#method.spec) for what each
+    /// description holds.
+    fn migration_manifest() -> Vec<MigrationSpec> {
+        Self::migrations().iter().map(|migration| migration.spec()).collect()
+    }
+
+    /// Get the vector of seed instances for this model.
+    ///
+    /// Seeds are baseline reference data — roles, settings & the like — which must be present
+    /// in the model's collection. Each seed must have an ID, and will be upserted by its ID
+    /// whenever [`Model::sync`](#method.sync) is called, after indexes & migrations have been
+    /// synchronized. As such, re-running the sync is always safe.
+    fn seeds() -> Vec<Self> {
+        vec![]
+    }
+
+    /// The `$jsonSchema` — or other query expression — which the server will use to validate
+    /// documents written to this model's collection.
+    ///
+    /// When set, [`Model::sync`](#method.sync) will create the collection along with this
+    /// validator if it does not yet exist, or will apply this validator to the existing
+    /// collection via `collMod` if the collection's current validator differs. This enforces the
+    /// model's schema at the database level in addition to serde. When `None`, any validator
+    /// already present on the collection is left untouched.
+    ///
+    /// ```rust,ignore
+    /// fn validator() -> Option<Document> {
+    ///     Some(doc!{"$jsonSchema": {
+    ///         "bsonType": "object",
+    ///         "required": ["email"],
+    ///         "properties": {"email": {"bsonType": "string"}},
+    ///     }})
+    /// }
+    /// ```
+    fn validator() -> Option<Document> {
+        None
+    }
+
+    /// The `validationLevel` to apply along with the model's [`validator`](#method.validator).
+    ///
+    /// One of `strict` or `moderate`. Defaults to `strict`.
+    fn validation_level() -> &'static str {
+        "strict"
+    }
+
+    /// The `validationAction` to apply along with the model's [`validator`](#method.validator).
+    ///
+    /// One of `error` or `warn`. Defaults to `error`.
+    fn validation_action() -> &'static str {
+        "error"
+    }
+
+    /// The time-series configuration of this model's collection, for models of timestamped
+    /// measurements such as metrics. Defaults to `None`.
+    ///
+    /// When set, [`Model::sync`](#method.sync) will create the collection as a time-series
+    /// collection if it does not yet exist, failing if the server is older than MongoDB 5.0. The
+    /// time & meta fields must be plain field paths, & must be among the `properties` of the
+    /// model's `$jsonSchema` [`validator`](#method.validator), when it has one. An existing
+    /// collection can not be converted, so a warning is logged if it is not a time-series
+    /// collection, or if its time-series options differ.
+    ///
+    /// ```rust,ignore
+    /// fn timeseries() -> Option<Timeseries> {
+    ///     Some(Timeseries{
+    ///         time_field: "ts".to_owned(),
+    ///         meta_field: Some("sensor".to_owned()),
+    ///         granularity: Some(TimeseriesGranularity::Seconds),
+    ///     })
+    /// }
+    /// ```
+    fn timeseries() -> Option<Timeseries> {
+        None
+    }
+
+    /// The shard key of this model's collection, for collections which should be sharded.
+    ///
+    /// When set & running against a sharded cluster, [`Model::sync`](#method.sync) will ensure
+    /// that an index supporting the shard key exists — unless one of the model's
+    /// [`indexes`](#method.indexes) has exactly the same keys — & will then shard the
+    /// collection via `shardCollection`, enabling sharding on the database if needed. A
+    /// collection which is already sharded is left untouched, as its shard key can not be
+    /// changed; a warning is logged if it differs from this key. When not connected to a
+    /// sharded cluster, a warning is logged & sharding is skipped. Defaults to `None`.
+    fn shard_key() -> Option<Document> {
+        None
+    }
+
+    /// The name of the environment the model is being synced in, such as `dev` or `production`,
+    /// against which each migration's [`environments`](../migration/trait.Migration.html#method.environments)
+    /// are checked. Defaults to the value of the
+    /// [`WITHER_ENV_VAR`](./constant.WITHER_ENV_VAR.html) environment variable, `WITHER_ENV`, or
+    /// `None` when it is unset.
+    fn environment() -> Option<String> {
+        env::var(WITHER_ENV_VAR).ok()
+    }
+
+    /// The log level used by [`Model::sync`](#method.sync) for routine records.
+    ///
+    /// Migrations which match no documents — including those past their threshold — are
+    /// logged at this level. Migrations which actually match documents are always logged at
+    /// `info` & errors are always logged at `error`. Defaults to `debug`, so that booting many
+    /// models does not flood the logs with no-ops.
+    fn sync_log_level() -> Level {
+        Level::Debug
+    }
+
+    /// How long [`Model::sync`](#method.sync) should wait for newly created indexes to finish
+    /// building before continuing. Defaults to `None`, not waiting.
+    ///
+    /// An index is not used by queries until its build finishes, so a service which begins
+    /// serving traffic right after boot may see collection scans. When set, the sync polls the
+    /// server via `currentOp` until no build of an index it created is in flight. If a build
+    /// stalls such that it is still in flight once this timeout has elapsed, the sync fails with
+    /// an error naming the pending indexes. The builds themselves are not interrupted, & will
+    /// continue on the server. Should the connection not be authorized to run `currentOp`, a
+    /// warning is logged & the sync continues without waiting.
+    fn index_ready_timeout() -> Option<Duration> {
+        None
+    }
+
+    /// Whether each execution of this model's migrations should be appended to the audit
+    /// collection named by [`migration_audit_collection`](#method.migration_audit_collection).
+    /// Defaults to `false`.
+    ///
+    /// Unlike the tracking collection, which holds only the latest execution of each migration,
+    /// the audit collection keeps a record of every execution across the fleet — its name &
+    /// namespace, matched & modified counts, duration, timestamp & the
+    /// [`instance_id`](../migration/fn.instance_id.html) of the executing process — for
+    /// post-incident analysis. Records are never removed by this crate.
+    fn audit_migrations() -> bool {
+        false
+    }
+
+    /// The name of the collection, within the model's database, to which migration executions
+    /// are audited. Defaults to [`MIGRATION_AUDIT_COLLECTION`](../migration/constant.MIGRATION_AUDIT_COLLECTION.html).
+    fn migration_audit_collection() -> &'static str {
+        MIGRATION_AUDIT_COLLECTION
+    }
+
+    /// How long the advisory lock taken while executing this model's migrations is held before it
+    /// is considered stale. Defaults to 10 minutes.
+    ///
+    /// [`Model::sync`](#method.sync) & [`Model::run_migrations`](#method.run_migrations) take the
+    /// lock — held in [`MIGRATION_LOCKS_COLLECTION`](../migration/constant.MIGRATION_LOCKS_COLLECTION.html)
+    /// — before executing any migrations, so concurrently booting instances do not migrate the
+    /// same collection at once. An instance which finds the lock held — such as a replica booting
+    /// alongside the lock holder in a rolling deploy — waits for it to be released, for at most
+    /// this timeout, & then executes any migrations still pending. A lock left behind by a
+    /// crashed instance expires after this timeout, so it should be comfortably longer than the
+    /// model's migrations take to execute.
+    fn migration_lock_timeout() -> Duration {
+        Duration::from_secs(600)
+    }
+
+    /// Clear the advisory migration lock of this model's collection, returning `true` if a lock
+    /// was cleared.
+    ///
+    /// This is an administrative command for operators, to release a lock left behind by a
+    /// crashed instance without waiting for it to expire. **Only use this when the instance
+    /// holding the lock is known to be dead.** Forcing the lock while a run is genuinely in
+    /// progress allows another instance to execute the same migrations concurrently.
+    fn force_unlock_migrations(db: Database) -> Result<bool> {
+        let coll = Self::collection(&db);
+        Ok(force_migration_unlock(&coll)?)
+    }
+
+    /// Whether [`Model::sync`](#method.sync) should verify, before changing anything, that the
+    /// connection has the privileges the sync needs. Defaults to `true`.
+    ///
+    /// The privileges of the connection's users are read via `connectionStatus`, & the sync fails
+    /// upfront with an error listing any missing actions — such as `createIndex on users` —
+    /// rather than midway through with a bare authorization error. Only the actions which the
+    /// sync will actually perform, given the current state of the collection, are required, as
+    /// in [`sync_plan`](#method.sync_plan): `createIndex` only when an index is to be created,
+    /// `dropIndex` only when one is to be dropped, & `createCollection` or `collMod` only when
+    /// the collection is to be created or its validator changed. Migrations need `find` &
+    /// `update` on the collection, along with `find`, `insert`, `update` & `remove` on the
+    /// [migration tracking](../migration/constant.MIGRATIONS_COLLECTION.html) & [migration
+    /// lock](../migration/constant.MIGRATION_LOCKS_COLLECTION.html) collections, & `insert` on
+    /// the [audit collection](#method.migration_audit_collection) when auditing. Seeds need
+    /// `insert` & `update`. The check is skipped, with a warning, when the server does not report
+    /// privileges, & is skipped silently when authentication is disabled.
+    fn verify_sync_privileges() -> bool {
+        true
+    }
+
+    /// Rebuild this model's indexes & re-run its migrations from scratch, ignoring all
+    /// migration tracking state. `confirm` must be the name of the model's collection.
+    ///
+    /// **This is a last resort for recovery, not for routine operation**, such as for a
+    /// collection whose tracking records are corrupt or whose data has drifted. The tracking
+    /// records of the collection's migrations — their recorded checksums & any
+    /// `BatchedMigration` checkpoints — are deleted in the
+    /// [`MIGRATIONS_COLLECTION`](../migration/constant.MIGRATIONS_COLLECTION.html), all indexes
+    /// other than that on `_id` are dropped, & the collection is then synchronized exactly as by
+    /// [`Model::sync`](#method.sync), so every index is recreated & every migration re-executed.
+    /// As migrations must be idempotent, re-executing them should be safe, though migrations
+    /// past their threshold will still no-op. Queries will not be able to use the dropped
+    /// indexes until they are rebuilt. Destructive migrations remain gated as usual.
+    ///
+    /// Before anything is cleared or dropped, the checks which could otherwise fail the sync
+    /// midway are made — the [sync privileges](#method.verify_sync_privileges), the index limits
+    /// & the gating of transactional & destructive migrations — & the collection's migration lock
+    /// is taken, & held until the sync completes, so that an error is returned while the
+    /// collection still has its indexes.
+    fn force_resync(db: Database, confirm: &str) -> Result<SyncReport> {
+        let coll = Self::collection(&db);
+        let name = coll.name();
+        if confirm != name {
+            return Err(ArgumentError(format!("Refusing to force a resync of '{}' unless its collection name is given to confirm.", coll.namespace)).into());
+        }
+
+        // Check everything which could fail the sync before dropping anything, & hold the
+        // migration lock throughout, so that the collection is never left without its indexes.
+        if Self::verify_sync_privileges() {
+            let mut privileges = sync_privileges::<Self>(&coll)?;
+            privileges.extend(vec![(name.clone(), "dropIndex"), (name.clone(), "createIndex")]);
+            privileges.extend(migration_privileges(migration_audit::<Self>()));
+            privileges.sort();
+            privileges.dedup();
+            check_sync_privileges(&coll, &privileges)?;
+        }
+        declared_indexes::<Self>()?;
+        check_migrations_executable(&coll, &Self::migrations(), Self::environment().as_ref().map(String::as_str))?;
+        let token = acquire_migration_lock(&coll, Self::migration_lock_timeout())?;
+        let res = clear_migration_tracking(&coll).map_err(WitherError::from).and_then(|cleared| {
+            warn!("Forcing a resync of '{}': cleared {} migration tracking records, dropping all indexes.", coll.namespace, cleared);
+            if collection_options(&coll)?.is_some() {
+                coll.db.command(doc!{"dropIndexes": name, "index": "*"}, CommandType::Suppressed, None)?;
+            }
+            sync_model::<Self>(db, None, true)
+        });
+        release_migration_lock_or_warn(&coll, &token);
+        res
+    }
+
+    /// A hook which is called at the end of [`Model::sync`](#method.sync), with the report of
+    /// what the sync did.
+    ///
+    /// This is the place to verify invariants before declaring the service healthy — such as
+    /// that a newly declared index exists & is ready. Returning an error fails the sync, & so
+    /// the boot. Defaults to a no-op.
+    fn after_sync(_db: Database, _report: &SyncReport) -> Result<()> {
+        Ok(())
+    }
+
+    /// Synchronize this model with the backend.
+    ///
+    /// This routine should be called once per model, early on at boottime. It will create the
+    /// model's collection as a [`timeseries`](#method.timeseries) collection, if so configured,
+    /// will apply the model's [`validator`](#method.validator), if any, will synchronize any
+    /// indexes defined on this model with the backend, will execute any active migrations against
+    /// the model's collection & will then upsert any of the model's seeds. Finally, the model's
+    /// [`after_sync`](#method.after_sync) hook is called with a `SyncReport` of what was done,
+    /// which is then returned.
+    ///
+    /// This routine will destroy any indexes found on this model's collection which are not
+    /// defined in the response from `Self.indexes()`.
+    fn sync(db: Database) -> Result<SyncReport> {
+        Self::sync_with_progress(db, None)
+    }
+
+    /// Describe what [`Model::sync`](#method.sync) would do to this model's collection, as a list
+    /// of human readable statements, without executing anything.
+    ///
+    /// This makes schema changes reviewable by people who do not read Rust, such as by posting
+    /// the plan of each deploy for review. Statements are listed in the order the sync would
+    /// execute them, & are deterministic, so that plans may be diffed across runs:
+    ///
+    /// ```text
+    /// SET VALIDATOR ON users { $jsonSchema: { ... } } LEVEL strict ACTION error
+    /// CREATE UNIQUE INDEX unique-email ON users { email: 1 }
+    /// DROP INDEX stale-index ON users
+    /// RUN MIGRATION remove-oldfield: $unset {oldfield} WHERE { oldfield: { $exists: true } }
+    /// UPSERT SEED ObjectId("5a1d7ddf8d1b43e5a1b2c3d4") INTO users
+    /// ```
+    ///
+    /// Migrations are listed when they apply to the current [`environment`](#method.environment)
+    /// & have not passed their threshold, though one may still match no documents. Sharding the
+    /// collection is not planned.
+    fn sync_plan(db: Database) -> Result<Vec<String>> {
+        let coll = Self::collection(&db);
+        let name = coll.name();
+        let options = collection_options(&coll)?;
+        let mut plan = vec![];
+
+        if let Some(ref timeseries) = Self::timeseries() {
+            let timeseries = timeseries_options(timeseries, Self::validator().as_ref())?;
+            if options.is_none() {
+                plan.push(format!("CREATE TIMESERIES COLLECTION {} {}", name, timeseries));
+            }
+        }
+        if let Some(validator) = Self::validator() {
+            let (level, action) = (Self::validation_level(), Self::validation_action());
+            match options {
+                None => plan.push(format!("CREATE COLLECTION {} WITH VALIDATOR {} LEVEL {} ACTION {}", name, validator, level, action)),
+                Some(ref options) if !validator_matches(options, &validator, level, action) => {
+                    plan.push(format!("SET VALIDATOR ON {} {} LEVEL {} ACTION {}", name, validator, level, action));
+                },
+                Some(_) => (),
+            }
+        }
+
+        let current = if options.is_some() { current_indexes(&coll)? } else { vec![] };
+        let (indexes_to_create, indexes_to_remove) = index_changes(current, synced_indexes::<Self>(&coll)?)?;
+        for index in indexes_to_create {
+            let unique = if index.options.unique == Some(true) { "UNIQUE " } else { "" };
+            plan.push(format!("CREATE {}INDEX {} ON {} {}", unique, index_name(&index), name, index.keys));
+        }
+        for doc in indexes_to_remove {
+            plan.push(format!("DROP INDEX {} ON {}", doc.get_str("name").unwrap_or(""), name));
+        }
+
+        let environment = Self::environment();
+        let now = Utc::now();
+        for migration in Self::migrations() {
+            if !runs_in_environment(migration.environments(), environment.as_ref().map(String::as_str)) {
+                continue;
+            }
+            let spec = migration.spec();
+            let expired = spec.threshold.as_ref()
+                .and_then(|threshold| DateTime::parse_from_rfc3339(threshold).ok())
+                .map(|threshold| threshold < now)
+                .unwrap_or(false);
+            if !expired {
+                plan.push(migration_statement(&spec));
+            }
+        }
+
+        for seed in Self::seeds() {
+            match seed.id() {
+                Some(id) => plan.push(format!("UPSERT SEED {} INTO {}", Bson::ObjectId(id), name)),
+                None => return Err(ArgumentError(format!("Every seed for '{}' must have an ID.", coll.namespace)).into()),
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Synchronize this model with the backend, reporting the progress of index builds.
+    ///
+    /// This behaves exactly as [`Model::sync`](#method.sync), except that while indexes are being
+    /// created the server will be periodically polled via `currentOp`, & the given `progress`
+    /// function will be called with the progress of each index being built. This gives
+    /// operators visibility into long running index builds at boottime. For background index
+    /// builds, which do not block the collection, the reported progress is informational.
+    ///
+    /// The indexes are created on another thread, so that `progress` is called on the calling
+    /// thread & may borrow from its environment.
+    fn sync_with_progress(db: Database, progress: Option<&Fn(&IndexBuildProgress)>) -> Result<SyncReport> {
+        sync_model::<Self>(db, progress, false)
+    }
+
+    /// Execute only the named migrations of this model, in declaration order.
+    ///
+    /// This is for re-running a single migration during an incident, or testing one in
+    /// isolation. Unlike [`Model::sync`](#method.sync), neither indexes nor seeds are
+    /// synchronized. Each migration is executed & tracked exactly as by a sync, subject to the
+    /// same refusals of transactional & destructive migrations, & the same environment gating.
+    /// An error is returned, before anything is executed, if any of the names is not that of one
+    /// of the model's [`migrations`](#method.migrations).
+    fn run_migrations(db: Database, names: &[&str]) -> Result<Vec<MigrationResult>> {
+        let migrations = Self::migrations();
+        let unknown: Vec<&str> = names.iter().cloned()
+            .filter(|name| !migrations.iter().any(|migration| migration.name() == *name))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(ArgumentError(format!("Unknown migrations for '{}': {}.", Self::COLLECTION_NAME, unknown.join(", "))).into());
+        }
+        let selected = migrations.into_iter().filter(|migration| names.contains(&migration.name())).collect();
+        let coll = Self::collection(&db);
+        sync_model_migrations(&coll, selected, Self::sync_log_level(), Self::environment(), Some(Self::migration_lock_timeout()), migration_audit::<Self>())
+    }
+
+    /// Drop this model's collection & rebuild it from scratch, for fast iteration in local
+    /// development.
+    ///
+    /// **This destroys all of the collection's data.** The collection is dropped, recreated &
+    /// then synchronized exactly as by [`Model::sync`](#method.sync), so that it is left holding
+    /// only the model's indexes & [`seeds`](#method.seeds). So that this can never run against a
+    /// production database, it refuses to execute unless the
+    /// [`WITHER_ENV_VAR`](./constant.WITHER_ENV_VAR.html) environment variable is set to `dev`.
+    /// A collection which does not exist yet is simply created.
+    fn sync_fresh(db: Database) -> Result<SyncReport> {
+        if env::var(WITHER_ENV_VAR).map(|val| val != "dev").unwrap_or(true) {
+            return Err(ArgumentError(format!("Refusing to drop collection '{}' unless {}=dev.", Self::COLLECTION_NAME, WITHER_ENV_VAR)).into());
+        }
+        let coll = Self::collection(&db);
+        // A collection which does not exist yet has nothing to drop.
+        query_cache::invalidate_after(&coll.namespace, || match coll.drop() {
+            Err(OperationError(ref msg)) if msg.contains("ns not found") => Ok(()),
+            res => res,
+        })?;
+        // A time-series collection is created by the sync itself.
+        if Self::timeseries().is_none() {
+            Self::database(db.clone()).create_collection(&coll.name(), None)?;
+        }
+        Self::sync(db)
+    }
+}
+
+/// The indexes which `Model::sync` maintains on the given model's collection, skipping with a
+/// warning those which the connected server is too old to support.
+fn synced_indexes<'a, M: Model<'a>>(coll: &Collection) -> Result<Vec<IndexModel>> {
+    let indexes = declared_indexes::<M>()?;
+
+    // Only ask the server for its version when some index needs it.
+    if !indexes.iter().any(|index| M::index_min_server_version(index).is_some()) {
+        return Ok(indexes);
+    }
+    let version = server_version(&coll.db)?;
+    Ok(indexes.into_iter().filter(|index| match M::index_min_server_version(index) {
+        Some(ref min) if &version < min => {
+            warn!("Skipping index '{}' on '{}', as it requires MongoDB {} but the server is {}.", index_name(index), coll.namespace, min, version);
+            false
+        },
+        _ => true,
+    }).collect())
+}
+
+/// The indexes which the given model declares: its own indexes, along with those needed by its
+/// soft delete & shard key, checked against MongoDB's limits.
+fn declared_indexes<'a, M: Model<'a>>() -> Result<Vec<IndexModel>> {
+    let mut indexes = M::indexes();
+    if let Some(soft_delete) = M::soft_delete() {
+        indexes.extend(soft_delete_index(&soft_delete)?);
+    }
+    if let Some(ref shard_key) = M::shard_key() {
+        if !indexes.iter().any(|index| &index.keys == shard_key) {
+            indexes.push(shard_key_index(shard_key));
+        }
+    }
+    check_index_limits(M::COLLECTION_NAME, &indexes)?;
+    Ok(indexes)
+}
+
+/// Describe the given index as its name, `key` & every one of its options which is set, named as
+/// the server names them.
+fn index_descriptor(index: &IndexModel) -> Document {
+    let mut descriptor = doc!{"name": index_name(index), "key": index.keys.clone()};
+    if let Some(unique) = index.options.unique {
+        descriptor.insert("unique", unique);
+    }
+    if let Some(sparse) = index.options.sparse {
+        descriptor.insert("sparse", sparse);
+    }
+    if let Some(expire_after_seconds) = index.options.expire_after_seconds {
+        descriptor.insert("expireAfterSeconds", expire_after_seconds);
+    }
+    if let Some(background) = index.options.background {
+        descriptor.insert("background", background);
+    }
+    if let Some(ref storage_engine) = index.options.storage_engine {
+        descriptor.insert("storageEngine", storage_engine.clone());
+    }
+    if let Some(version) = index.options.version {
+        descriptor.insert("v", version);
+    }
+    if let Some(ref weights) = index.options.weights {
+        descriptor.insert("weights", weights.clone());
+    }
+    if let Some(ref default_language) = index.options.default_language {
+        descriptor.insert("default_language", default_language.clone());
+    }
+    if let Some(ref language_override) = index.options.language_override {
+        descriptor.insert("language_override", language_override.clone());
+    }
+    if let Some(text_version) = index.options.text_version {
+        descriptor.insert("textIndexVersion", text_version);
+    }
+    if let Some(sphere_version) = index.options.sphere_version {
+        descriptor.insert("2dsphereIndexVersion", sphere_version);
+    }
+    if let Some(bits) = index.options.bits {
+        descriptor.insert("bits", bits);
+    }
+    if let Some(min) = index.options.min {
+        descriptor.insert("min", min);
+    }
+    if let Some(max) = index.options.max {
+        descriptor.insert("max", max);
+    }
+    if let Some(bucket_size) = index.options.bucket_size {
+        descriptor.insert("bucketSize", bucket_size);
+    }
+    descriptor
+}
+
+/// Get the `FindOptions` of a read of the given model which is not given options by the caller,
+/// with the model's `allow_partial_results` & `read_preference` applied.
+fn read_options<'a, M: Model<'a>>() -> FindOptions {
+    let mut options = FindOptions::new();
+    options.allow_partial_results = M::allow_partial_results();
+    options.read_preference = M::read_preference();
+    options
+}
+
+/// Get the projection excluding the given lazy fields, barring those to be included, if any are
+/// to be excluded.
+fn lazy_projection(lazy: &[&str], include: &[&str]) -> Option<Document> {
+    let mut projection = Document::new();
+    for field in lazy.iter().filter(|field| !include.contains(field)) {
+        projection.insert(*field, 0);
+    }
+    if projection.is_empty() { None } else { Some(projection) }
+}
+
+/// Write the given instance document to the one document matching `filter`, without removing
+/// any of the given lazy fields which the instance has not loaded.
+///
+/// The stored document is replaced if every lazy field is loaded. Otherwise the loaded fields
+/// are `$set`, leaving the stored values of the lazy fields which are missing or `null`.
+fn save_document(coll: &Collection, filter: Document, doc: Document, lazy: &[&str], opts: FindOneAndUpdateOptions) -> Result<Option<Document>> {
+    let unloaded = unloaded_lazy_fields(&doc, lazy);
+    if unloaded.is_empty() {
+        return Ok(coll.find_one_and_replace(filter, doc, Some(opts))?);
+    }
+    let mut doc = doc;
+    for field in unloaded {
+        doc.remove(field);
+    }
+    Ok(coll.find_one_and_update(filter, doc!{"$set": doc}, Some(opts))?)
+}
+
+/// Get the given lazy fields which are missing or `null` in the given instance document.
+fn unloaded_lazy_fields<'a>(doc: &Document, lazy: &[&'a str]) -> Vec<&'a str> {
+    lazy.iter().cloned().filter(|field| match doc.get(field) {
+        None | Some(&Bson::Null) => true,
+        Some(_) => false,
+    }).collect()
+}
+
+/// Get the serialized names of the fields of the model `M`, as declared to serde, or `None` when
+/// they can not be determined, such as for a model having flattened fields.
+fn declared_fields<'a, M: Model<'a>>() -> Option<&'static [&'static str]> {
+    let mut probe = FieldProbe(None);
+    let _ = M::deserialize(&mut probe);
+    probe.0
+}
+
+/// A deserializer which records the fields of the struct deserialized from it, & then fails.
+struct FieldProbe(Option<&'static [&'static str]>);
+
+impl<'p, 'de> Deserializer<'de> for &'p mut FieldProbe {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> result::Result<V::Value, de::value::Error> {
+        Err(de::Error::custom("Only the fields of structs may be probed."))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], _visitor: V) -> result::Result<V::Value, de::value::Error> {
+        self.0 = Some(fields);
+        Err(de::Error::custom("Probed the fields of the struct."))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Ensure that the model `M` declares the field which soft deletes record, if soft delete is
+/// enabled. Models whose fields can not be determined are not checked.
+fn check_soft_delete_field<'a, M: Model<'a>>() -> Result<()> {
+    if M::soft_delete().is_none() {
+        return Ok(());
+    }
+    match declared_fields::<M>() {
+        Some(fields) if !fields.contains(&SOFT_DELETE_FIELD) => {
+            Err(ArgumentError(format!("Model of collection '{}' enables soft delete, but declares no '{}' field.", M::COLLECTION_NAME, SOFT_DELETE_FIELD)).into())
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Get the fields of the given stored document which are not held by the given instance of a
+/// model when re-serialized, excluding the bookkeeping fields which the model stores itself.
+fn instance_unknown_fields<'a, M: Model<'a>>(instance: &M, stored: &Document) -> Result<Vec<String>> {
+    let known = match bson::to_bson(instance)? {
+        Bson::Document(doc) => doc,
+        _ => return Err(DefaultError("Failed to convert struct to a bson document.".to_string()).into()),
+    };
+    let mut ignored: Vec<&str> = M::content_hash_field().into_iter().collect();
+    if M::schema_version().is_some() {
+        ignored.push(M::schema_version_field());
+    }
+    Ok(unknown_fields(stored, &known, &ignored))
+}
+
+/// Get the non-null top level fields of `stored` which are neither in `known` nor `ignored`.
+fn unknown_fields(stored: &Document, known: &Document, ignored: &[&str]) -> Vec<String> {
+    stored.iter()
+        .filter(|&(key, val)| val != &Bson::Null && !known.contains_key(key) && !ignored.contains(&key.as_str()))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// The field under which the server reports the record ID of each document, when asked to.
+const RECORD_ID_FIELD: &str = "$recordId";
+
+/// Remove the record ID reported by the server from the given document, returning it.
+fn take_record_id(doc: &mut Document) -> Result<i64> {
+    match doc.remove(RECORD_ID_FIELD) {
+        Some(Bson::I64(record_id)) => Ok(record_id),
+        Some(Bson::I32(record_id)) => Ok(record_id as i64),
+        _ => Err(ResponseError(format!("Expected '{}' in each document returned by the server.", RECORD_ID_FIELD)).into()),
+    }
+}
+
+/// Check that none of the given fields of the given document are empty, returning
+/// `WitherError::MissingRequiredField` for the first which is.
+///
+/// See [`Model::required_fields`](./trait.Model.html#method.required_fields) for what is
+/// considered empty.
+pub fn check_required_fields(doc: &Document, fields: &[&str]) -> Result<()> {
+    for field in fields {
+        let empty = match document_path(doc, field) {
+            None | Some(&Bson::Null) => true,
+            Some(&Bson::String(ref val)) => val.is_empty(),
+            Some(&Bson::I32(val)) => val == 0,
+            Some(&Bson::I64(val)) => val == 0,
+            Some(&Bson::FloatingPoint(val)) => val == 0.0,
+            Some(&Bson::Array(ref vals)) => vals.is_empty(),
+            Some(&Bson::Document(ref doc)) => doc.is_empty(),
+            Some(_) => false,
+        };
+        if empty {
+            return Err(WitherError::MissingRequiredField{field: field.to_string()});
+        }
+    }
+    Ok(())
+}
+
+/// Check that the given numeric fields of the given document are within their ranges, returning
+/// `WitherError::OutOfRange` for the first which is not.
+///
+/// Fields which are missing or null are not checked. Returns an `ArgumentError` for a field which
+/// is not numeric. See [`Model::field_ranges`](./trait.Model.html#method.field_ranges).
+pub fn check_field_ranges(doc: &Document, ranges: &[FieldRange]) -> Result<()> {
+    for range in ranges {
+        let actual = match document_path(doc, range.field) {
+            None | Some(&Bson::Null) => continue,
+            Some(&Bson::I32(val)) => val as f64,
+            Some(&Bson::I64(val)) => val as f64,
+            Some(&Bson::FloatingPoint(val)) => val,
+            Some(_) => return Err(ArgumentError(format!("Field '{}' has a range, but is not numeric.", range.field)).into()),
+        };
+        let below = range.min.map(|min| actual < min).unwrap_or(false);
+        let above = range.max.map(|max| actual > max).unwrap_or(false);
+        if below || above || actual.is_nan() {
+            return Err(WitherError::OutOfRange{field: range.field.to_string(), min: range.min, max: range.max, actual});
+        }
+    }
+    Ok(())
+}
+
+/// Apply the given update to the given instance's document by ID, replacing the instance with
+/// the updated document.
+fn update_in_place<'a, M: Model<'a>>(model: &mut M, db: Database, update: Document) -> Result<()> {
+    let id = match model.id() {
+        Some(id) => id,
+        None => return Err(ArgumentError("Model must have an ObjectId for this operation.".to_owned()).into()),
+    };
+    let mut update = update;
+    if model.timestamps().is_some() {
+        stamp_updated_at(&mut update);
+    }
+    let mut write_concern = M::model_write_concern();
+    write_concern.j = true;
+    let opts = FindOneAndUpdateOptions{upsert: Some(false), write_concern: Some(write_concern), return_document: Some(ReturnDocument::After), sort: None, projection: None, max_time_ms: None};
+    let res = M::find_one_and_update(db, doc!{"_id": id.clone()}, update, Some(opts));
+    M::cache_invalidate(&id);
+    match res? {
+        Some(instance) => {
+            *model = instance;
+            Ok(())
+        },
+        None => Err(WitherError::NotFound{collection: M::COLLECTION_NAME.to_owned(), id}),
+    }
+}
+
+/// The changes to each top level field from the `old` document to the `new` one.
+fn diff_documents(old: &Document, new: &Document) -> Vec<FieldChange> {
+    let mut changes = vec![];
+    for (key, val) in old.iter() {
+        match new.get(key) {
+            Some(other) if bson_equivalent(val, other) => (),
+            other => changes.push(FieldChange{field: key.clone(), old: Some(val.clone()), new: other.cloned()}),
+        }
+    }
+    for (key, val) in new.iter().filter(|&(key, _)| !old.contains_key(key)) {
+        changes.push(FieldChange{field: key.clone(), old: None, new: Some(val.clone())});
+    }
+    changes
+}
+
+/// Whether the given documents hold equal values for the same fields, regardless of the order
+/// of fields within them or any of their embedded documents.
+fn documents_equivalent(a: &Document, b: &Document) -> bool {
+    a.len() == b.len() && a.iter().all(|(key, val)| match b.get(key) {
+        Some(other) => bson_equivalent(val, other),
+        None => false,
+    })
+}
+
+/// Whether the given values are equal, regardless of the order of fields within any documents.
+pub(crate) fn bson_equivalent(a: &Bson, b: &Bson) -> bool {
+    match (a, b) {
+        (&Bson::Document(ref a), &Bson::Document(ref b)) => documents_equivalent(a, b),
+        (&Bson::Array(ref a), &Bson::Array(ref b)) => a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| bson_equivalent(a, b)),
+        _ => a == b,
+    }
+}
+
+/// Build the `$nearSphere` filter on the given field for the given point & distance bounds.
+fn near_sphere_filter(field: &str, lon: f64, lat: f64, min_meters: Option<f64>, max_meters: Option<f64>) -> Document {
+    let mut near = doc!{"$geometry": {"type": "Point", "coordinates": [lon, lat]}};
+    if let Some(min) = min_meters {
+        near.insert("$minDistance", min);
+    }
+    if let Some(max) = max_meters {
+        near.insert("$maxDistance", max);
+    }
+    let mut filter = Document::new();
+    filter.insert(field, doc!{"$nearSphere": near});
+    filter
+}
+
+/// A stable hash of the content of the given serialized instance, excluding the given hash field.
+///
+/// See `Model::content_hash` for the other fields excluded.
+fn document_content_hash(doc: &Document, hash_field: Option<&str>) -> Result<u64> {
+    let mut content = canonical_document(doc);
+    for field in &["_id", CREATED_AT_FIELD, UPDATED_AT_FIELD] {
+        content.remove(field);
+    }
+    if let Some(field) = hash_field {
+        content.remove(field);
+    }
+    let mut buf = vec![];
+    bson::encode_document(&mut buf, &content)?;
+    Ok(fnv1a64(&buf))
+}
+
+/// Get the hash keying the results of the given query in the query cache.
+///
+/// Only the top level fields of the filter are sorted, as the server treats an exact match on an
+/// embedded document as order sensitive.
+fn query_cache_hash(filter: Option<&Document>, options: Option<&FindOptions>) -> Result<u64> {
+    let mut fields = Document::new();
+    if let Some(filter) = filter {
+        let mut keys: Vec<&String> = filter.keys().collect();
+        keys.sort();
+        for key in keys {
+            if let Some(val) = filter.get(key) {
+                fields.insert_bson(key.clone(), val.clone());
+            }
+        }
+    }
+    let mut key = doc!{"filter": fields};
+    if let Some(options) = options {
+        let optional = |doc: &Option<Document>| doc.clone().map(Bson::Document).unwrap_or(Bson::Null);
+        key.insert("sort", optional(&options.sort));
+        key.insert("projection", optional(&options.projection));
+        key.insert("modifiers", optional(&options.modifiers));
+        key.insert("skip", options.skip.map(Bson::from).unwrap_or(Bson::Null));
+        key.insert("limit", options.limit.map(Bson::from).unwrap_or(Bson::Null));
+        key.insert("allow_partial_results", options.allow_partial_results);
+    }
+    let mut buf = vec![];
+    bson::encode_document(&mut buf, &key)?;
+    Ok(fnv1a64(&buf))
+}
+
+/// Get a copy of the given document with the fields of it & all of its embedded documents
+/// sorted by name.
+fn canonical_document(doc: &Document) -> Document {
+    let mut keys: Vec<&String> = doc.keys().collect();
+    keys.sort();
+    let mut canonical = Document::new();
+    for key in keys {
+        if let Some(val) = doc.get(key) {
+            canonical.insert_bson(key.clone(), canonical_bson(val));
+        }
+    }
+    canonical
+}
+
+/// Get a copy of the given value with the fields of all of its documents sorted by name.
+fn canonical_bson(val: &Bson) -> Bson {
+    match val {
+        &Bson::Document(ref doc) => Bson::Document(canonical_document(doc)),
+        &Bson::Array(ref vals) => Bson::Array(vals.iter().map(canonical_bson).collect()),
+        _ => val.clone(),
+    }
+}
+
+/// Get the TTL index needed for the given soft delete configuration, if any.
+fn soft_delete_index(soft_delete: &SoftDelete) -> Result<Option<IndexModel>> {
+    let ttl = match soft_delete.ttl_seconds {
+        Some(ttl) => ttl,
+        None => return Ok(None),
+    };
+    if ttl <= 0 {
+        return Err(ArgumentError(format!("Soft delete TTL must be positive, got {}.", ttl)).into());
+    }
+    let mut keys = doc!{};
+    keys.insert(SOFT_DELETE_FIELD, 1);
+    let name = format!("{}-ttl", SOFT_DELETE_FIELD);
+    Ok(Some(IndexModel{keys, options: basic_index_options(&name, true, None, Some(ttl), None)}))
+}
+
+/// Get the name of the index of those given which best matches the fields of the given filter.
+///
+/// See `Model::best_index_for` for the ranking heuristic.
+fn best_index(indexes: &[IndexModel], filter: &Document) -> Option<String> {
+    let fields = filter_fields(filter);
+    let mut best: Option<((usize, bool, i64), String)> = None;
+    for index in indexes.iter() {
+        if index.keys.values().any(|val| val == &Bson::from("text")) {
+            continue;
+        }
+        let prefix_len = index.keys.keys().take_while(|key| fields.contains(*key)).count();
+        if prefix_len == 0 {
+            continue;
+        }
+
+        // Ties are broken by uniqueness, then by fewer keys, then by declaration order.
+        let rank = (prefix_len, index.options.unique.unwrap_or(false), -(index.keys.len() as i64));
+        let better = match best {
+            Some((ref best_rank, _)) => rank > *best_rank,
+            None => true,
+        };
+        if better {
+            best = Some((rank, index_name(index)));
+        }
+    }
+    best.map(|(_, name)| name)
+}
+
+/// Get the name of the given index, defaulting to the name MongoDB generates from its keys.
+fn index_name(index: &IndexModel) -> String {
+    match index.options.name {
+        Some(ref name) => name.clone(),
+        None => index.keys.iter()
+            .map(|(key, val)| format!("{}_{}", key, match val {
+                &Bson::String(ref val) => val.clone(),
+                val => bson_as_i64(val).map(|val| val.to_string()).unwrap_or_default(),
+            }))
+            .collect::<Vec<String>>()
+            .join("_"),
+    }
+}
+
+/// Get the queried fields of the given filter, including those of a top level `$and`.
+fn filter_fields(filter: &Document) -> Vec<String> {
+    let mut fields = vec![];
+    for (key, val) in filter.iter() {
+        if key == "$and" {
+            if let &Bson::Array(ref clauses) = val {
+                for clause in clauses {
+                    if let &Bson::Document(ref clause) = clause {
+                        fields.extend(filter_fields(clause));
+                    }
+                }
+            }
+        } else if !key.starts_with('$') {
+            fields.push(key.clone());
+        }
+    }
+    fields
+}
+
+/// Get the value at the given dotted path of the given document, if any.
+fn document_path<'d>(doc: &'d Document, path: &str) -> Option<&'d Bson> {
+    let mut parts = path.split('.');
+    let mut value = doc.get(parts.next()?)?;
+    for part in parts {
+        value = match value {
+            &Bson::Document(ref doc) => doc.get(part)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Constrain the given `Model::delete_many` filter to documents not already soft deleted, unless
+/// the filter itself constrains `deleted_at`.
+fn soft_delete_many_filter(mut filter: Document) -> Document {
+    if !filter.contains_key(SOFT_DELETE_FIELD) {
+        // A null matches a missing field as well, such as that of a `None` not skipped by serde.
+        filter.insert(SOFT_DELETE_FIELD, Bson::Null);
+    }
+    filter
+}
+
+/// Get the update document which soft deletes a document as of now.
+fn soft_delete_update() -> Document {
+    let mut set = doc!{};
+    set.insert(SOFT_DELETE_FIELD, Bson::UtcDatetime(Utc::now()));
+    doc!{"$set": set}
+}
+
+/// The name of the index supporting a model's shard key, when not declared by the model.
+const SHARD_KEY_INDEX: &str = "shard-key";
+
+/// Get the index supporting the given shard key.
+fn shard_key_index(shard_key: &Document) -> IndexModel {
+    IndexModel{keys: shard_key.clone(), options: basic_index_options(SHARD_KEY_INDEX, true, None, None, None)}
+}
+
+/// Get the privileges, as collection & action pairs, which syncing the given model needs given
+/// the current state of its collection: only those of the actions the sync will perform.
+fn sync_privileges<'a, M: Model<'a>>(coll: &Collection) -> Result<Vec<(String, &'static str)>> {
+    let name = coll.name();
+    let options = collection_options(coll)?;
+    let mut privileges = vec![];
+    if options.is_none() && (M::timeseries().is_some() || M::validator().is_some()) {
+        privileges.push((name.clone(), "createCollection"));
+    }
+    if let (Some(options), Some(validator)) = (options.as_ref(), M::validator()) {
+        if !validator_matches(options, &validator, M::validation_level(), M::validation_action()) {
+            privileges.push((name.clone(), "collMod"));
+        }
+    }
+    let current = if options.is_some() {
+        privileges.push((name.clone(), "listIndexes"));
+        current_indexes(coll)?
+    } else {
+        vec![]
+    };
+    let (indexes_to_create, indexes_to_remove) = index_changes(current, synced_indexes::<M>(coll)?)?;
+    if !indexes_to_create.is_empty() {
+        privileges.push((name.clone(), "createIndex"));
+    }
+    if !indexes_to_remove.is_empty() {
+        privileges.push((name.clone(), "dropIndex"));
+    }
+    let environment = M::environment();
+    if M::migrations().iter().any(|migration| runs_in_environment(migration.environments(), environment.as_ref().map(String::as_str))) {
+        privileges.extend(vec![(name.clone(), "find"), (name.clone(), "update")]);
+        privileges.extend(migration_privileges(migration_audit::<M>()));
+    }
+    if !M::seeds().is_empty() {
+        privileges.extend(vec![(name.clone(), "insert"), (name.clone(), "update")]);
+    }
+    privileges.sort();
+    privileges.dedup();
+    Ok(privileges)
+}
+
+/// Get the privileges needed on the collections which track, lock & audit migrations, auditing
+/// to the given collection, if any.
+fn migration_privileges(audit: Option<&str>) -> Vec<(String, &'static str)> {
+    let mut privileges = vec![];
+    for collection in &[MIGRATIONS_COLLECTION, MIGRATION_LOCKS_COLLECTION] {
+        for action in &["find", "insert", "update", "remove"] {
+            privileges.push((collection.to_string(), *action));
+        }
+    }
+    if let Some(audit) = audit {
+        privileges.push((audit.to_owned(), "insert"));
+    }
+    privileges
+}
+
+/// Ensure the connection behind the given collection is privileged to perform the given actions
+/// on it, as reported by `connectionStatus`.
+fn check_sync_privileges<'a>(coll: &'a Collection, privileges_needed: &[(String, &str)]) -> Result<()> {
+    let res = match coll.db.command(doc!{"connectionStatus": 1, "showPrivileges": true}, CommandType::Suppressed, None) {
+        Ok(res) => res,
+        Err(err) => {
+            warn!("Skipping privilege check of '{}', as the server did not report privileges: {}", coll.namespace, err);
+            return Ok(());
+        },
+    };
+    let auth_info = res.get_document("authInfo").ok();
+    let authenticated = auth_info.and_then(|info| info.get_array("authenticatedUsers").ok()).map(|users| !users.is_empty()).unwrap_or(false);
+    if !authenticated {
+        return Ok(());
+    }
+    let privileges = match auth_info.and_then(|info| info.get_array("authenticatedUserPrivileges").ok()) {
+        Some(privileges) => privileges,
+        None => {
+            warn!("Skipping privilege check of '{}', as the server did not report privileges.", coll.namespace);
+            return Ok(());
+        },
+    };
+    let missing: Vec<String> = privileges_needed.iter()
+        .filter(|&&(ref collection, action)| !privileges_grant(privileges, &coll.db.name, collection, action))
+        .map(|&(ref collection, action)| format!("{} on {}", action, collection))
+        .collect();
+    if !missing.is_empty() {
+        return Err(DefaultError(format!("Sync of '{}' requires privileges which the connection lacks: {}.", coll.namespace, missing.join(", "))).into());
+    }
+    Ok(())
+}
+
+/// Whether any of the given privileges, as reported by `connectionStatus`, grants the given action
+/// on the given collection.
+fn privileges_grant(privileges: &[Bson], db: &str, collection: &str, action: &str) -> bool {
+    privileges.iter().any(|privilege| {
+        let privilege = match privilege {
+            &Bson::Document(ref privilege) => privilege,
+            _ => return false,
+        };
+        let grants_action = privilege.get_array("actions")
+            .map(|actions| actions.iter().any(|granted| granted.as_str() == Some(action)))
+            .unwrap_or(false);
+        let resource = match privilege.get_document("resource") {
+            Ok(resource) => resource,
+            Err(_) => return false,
+        };
+        // An empty `db` or `collection` matches any; `anyResource` matches everything.
+        let matches = |field: &str, name: &str| resource.get_str(field).map(|val| val.is_empty() || val == name).unwrap_or(false);
+        let covers = resource.get_bool("anyResource").unwrap_or(false) || (matches("db", db) && matches("collection", collection));
+        grants_action && covers
+    })
+}
+
+/// Ensure the given collection is sharded with the given key, when running against a sharded cluster.
+fn sync_model_shard_key<'a>(coll: &'a Collection, shard_key: Document) -> Result<()> {
+    let admin = coll.db.client.db("admin");
+
+    // Only a `mongos` reports itself as `isdbgrid`.
+    let res = admin.command(doc!{"isMaster": 1}, CommandType::Suppressed, None)?;
+    if res.get_str("msg").ok() != Some("isdbgrid") {
+        warn!("Not connected to a sharded cluster, skipping sharding of '{}'.", coll.namespace);
+        return Ok(());
+    }
+
+    // Check whether the collection is already sharded.
+    let config = coll.db.client.db("config").collection("collections");
+    let filter = doc!{"_id": coll.namespace.clone(), "dropped": {"$ne": true}};
+    if let Some(existing) = config.find_one(Some(filter), None)? {
+        match existing.get_document("key") {
+            Ok(key) if key == &shard_key => debug!("Collection '{}' is already sharded.", coll.namespace),
+            Ok(key) => warn!("Collection '{}' is already sharded by {}, which differs from the model's shard key {}. Shard keys can not be changed.", coll.namespace, key, shard_key),
+            Err(_) => warn!("Collection '{}' is already sharded by an unknown key.", coll.namespace),
+        }
+        return Ok(());
+    }
+
+    // Enable sharding on the database, which fails harmlessly if already enabled.
+    if let Err(err) = admin.command(doc!{"enableSharding": coll.db.name.clone()}, CommandType::Suppressed, None) {
+        debug!("Enabling sharding on '{}' returned: {}", coll.db.name, err.description());
+    }
+    info!("Sharding collection '{}' with key {}.", coll.namespace, shard_key);
+    admin.command(doc!{"shardCollection": coll.namespace.clone(), "key": shard_key}, CommandType::Suppressed, None)?;
+    Ok(())
+}
+
+/// The base delay, in milliseconds, between retries of an operation which hit a write conflict.
+const WRITE_CONFLICT_BACKOFF_MS: u64 = 10;
+
+/// Execute the given operation, retrying up to `retries` times if it fails due to a write conflict.
+fn retry_on_write_conflict<T, F>(retries: u32, mut op: F) -> Result<T> where F: FnMut() -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(ref err) if err.is_write_conflict() && attempt < retries => {
+                let backoff = WRITE_CONFLICT_BACKOFF_MS << attempt.min(16);
+                warn!("Write conflict encountered, retrying. Attempt {} of {}.", attempt + 1, retries);
+                thread::sleep(Duration::from_millis(backoff + jitter_ms(WRITE_CONFLICT_BACKOFF_MS)));
+                attempt += 1;
+            },
+            res => return res,
+        }
+    }
+}
+
+/// A pseudo-random number of milliseconds in `[0, max)`, used to jitter retries.
+fn jitter_ms(max: u64) -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|dur| dur.subsec_nanos()).unwrap_or(0);
+    (nanos as u64) % max.max(1)
+}
+
+/// Log a warning for the given slow query, explaining it in order to report index usage.
+#[cfg(feature = "slow-query-log")]
+fn log_slow_query<'a>(coll: &'a Collection, filter: Document, elapsed: Duration) {
+    let millis = duration_ms(elapsed);
+    let shape = query_shape(&filter);
+    let spec = doc!{"explain": {"find": coll.name(), "filter": filter}, "verbosity": "queryPlanner"};
+    let index_used = match coll.db.command(spec, CommandType::Suppressed, None) {
+        Ok(res) => res.get_document("queryPlanner").ok()
+            .and_then(|planner| planner.get_document("winningPlan").ok())
+            .map(|plan| plan_uses_index(plan)),
+        Err(err) => {
+            warn!("Failed to explain slow query on '{}': {}", coll.namespace, err.description());
+            None
+        },
+    };
+    let index_used = index_used.map(|used| used.to_string()).unwrap_or_else(|| "unknown".to_owned());
+    warn!("Slow query on '{}' took {}ms (index used: {}): {}", coll.namespace, millis, index_used, shape);
+}
+
+/// Get the shape of the given query, with all values elided.
+#[cfg(feature = "slow-query-log")]
+fn query_shape(query: &Document) -> Document {
+    let mut shape = Document::new();
+    for (key, val) in query.iter() {
+        shape.insert_bson(key.clone(), value_shape(val));
+    }
+    shape
+}
+
+#[cfg(feature = "slow-query-log")]
+fn value_shape(value: &Bson) -> Bson {
+    match value {
+        &Bson::Document(ref doc) => Bson::Document(query_shape(doc)),
+        &Bson::Array(ref vals) => Bson::Array(vals.iter().map(value_shape).collect()),
+        _ => Bson::from("?"),
+    }
+}
+
+/// Whether the given query plan stage, or any of its input stages, scans an index.
+#[cfg(feature = "slow-query-log")]
+fn plan_uses_index(stage: &Document) -> bool {
+    if stage.get_str("stage").ok() == Some("IXSCAN") {
+        return true;
+    }
+    let inputs = stage.get_document("inputStage").ok().into_iter()
+        .chain(stage.get_array("inputStages").ok().into_iter()
+            .flat_map(|inputs| inputs.iter())
+            .filter_map(|input| match input {
+                &Bson::Document(ref doc) => Some(doc),
+                _ => None,
+            }));
+    for input in inputs {
+        if plan_uses_index(input) {
+            return true;
+        }
+    }
+    false
+}
+
+/// The base delay, in milliseconds, between retries of a bulk write batch which hit a transient
+/// error.
+const TRANSIENT_RETRY_BACKOFF_MS: u64 = 100;
+
+/// Insert the given documents into the given collection in order, returning their IDs.
+///
+/// Each batch is retried up to `retries` times upon a transient error, skipping the documents
+/// which were already inserted.
+fn insert_documents<'a>(coll: &'a Collection, mut docs: Vec<Document>, chunk_size: Option<usize>, write_concern: WriteConcern, retries: u32, generate_id: fn() -> Result<ObjectId>) -> Result<Vec<ObjectId>> {
+    // Assign IDs up front, so that a retried batch can tell which of its documents were inserted.
+    let mut ids = vec![];
+    for doc in docs.iter_mut() {
+        if !doc.contains_key("_id") {
+            doc.insert("_id", generate_id()?);
+        }
+        match doc.get("_id") {
+            Some(&Bson::ObjectId(ref oid)) => ids.push(oid.clone()),
+            _ => return Err(ArgumentError("Inserted documents must have ObjectId IDs.".to_owned()).into()),
+        }
+    }
+
+    // Insert each chunk in order.
+    for chunk in chunk_documents(docs, chunk_size.unwrap_or(DEFAULT_INSERT_CHUNK_SIZE))? {
+        let mut attempt = 0;
+        loop {
+            let pending = if attempt == 0 { Ok(chunk.clone()) } else { uninserted_documents(coll, &chunk) };
+            match pending.and_then(|pending| insert_chunk(coll, pending, write_concern.clone())) {
+                Ok(()) => break,
+                Err(ref err) if err.is_transient() && attempt < retries => {
+                    warn!("Transient error inserting into '{}', retrying. Attempt {} of {}: {}", coll.namespace, attempt + 1, retries, err);
+                    thread::sleep(Duration::from_millis(TRANSIENT_RETRY_BACKOFF_MS << attempt.min(16)));
+                    attempt += 1;
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// Insert the given chunk of documents in order, as a single batch.
+fn insert_chunk<'a>(coll: &'a Collection, chunk: Vec<Document>, write_concern: WriteConcern) -> Result<()> {
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    let opts = InsertManyOptions{ordered: Some(true), write_concern: Some(write_concern)};
+    let res = coll.insert_many(chunk, Some(opts))?;
+    if let Some(err) = res.bulk_write_exception {
+        return Err(BulkWriteError(err).into());
+    }
+    Ok(())
+}
+
+/// The documents of the given chunk whose IDs do not yet exist in the given collection.
+fn uninserted_documents<'a>(coll: &'a Collection, chunk: &[Document]) -> Result<Vec<Document>> {
+    let ids: Vec<Bson> = chunk.iter().filter_map(|doc| doc.get("_id").cloned()).collect();
+    let mut options = FindOptions::new();
+    options.projection = Some(doc!{"_id": 1});
+    let mut existing = vec![];
+    for doc in coll.find(Some(doc!{"_id": {"$in": ids}}), Some(options))? {
+        if let Some(id) = doc?.get("_id") {
+            existing.push(id.clone());
+        }
+    }
+    Ok(chunk.iter().filter(|doc| doc.get("_id").map(|id| !existing.contains(id)).unwrap_or(true)).cloned().collect())
+}
+
+/// Split the given documents into chunks respecting the given chunk size & MongoDB's batch size limit.
+fn chunk_documents(docs: Vec<Document>, chunk_size: usize) -> Result<Vec<Vec<Document>>> {
+    if chunk_size == 0 {
+        return Err(ArgumentError("`chunk_size` must be greater than zero.".to_owned()).into());
+    }
+
+    let mut chunks = vec![];
+    let mut chunk = vec![];
+    let mut chunk_bytes = 0;
+    for doc in docs {
+        let mut buf = vec![];
+        bson::encode_document(&mut buf, &doc)?;
+
+        // Start a new chunk if this document would exceed either limit.
+        if !chunk.is_empty() && (chunk.len() >= chunk_size || chunk_bytes + buf.len() > MAX_INSERT_CHUNK_BYTES) {
+            chunks.push(chunk);
+            chunk = vec![];
+            chunk_bytes = 0;
+        }
+        chunk_bytes += buf.len();
+        chunk.push(doc);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    Ok(chunks)
+}
+
+/// Fetch the index documents of the given collection.
+fn current_indexes<'a>(coll: &'a Collection) -> Result<Vec<Document>> {
+    let indices = coll.list_indexes()
+        .map_err(|err| DefaultError(format!("Error while fetching current indexes for '{}': {:?}", coll.namespace, err.description())))?;
+    Ok(indices.filter_map(|doc_res| doc_res.ok()).collect())
+}
+
+/// Determine which of the given target indexes must be created, & which of the given current
+/// index documents must be dropped. Both are ordered by index name.
+fn index_changes(current: Vec<Document>, indexes: Vec<IndexModel>) -> Result<(Vec<IndexModel>, Vec<Document>)> {
+    let mut current_indexes_map: HashMap<String, Document> = HashMap::new();
+    for doc in current {
+        let idx_keys = doc.get_document("key")
+            .map_err(|err| DefaultError(format!("Error extracting 'key' of index document: {:?}", err.description())))?;
+        let key = idx_keys.keys().fold(String::from(""), |acc, bkey| acc + bkey);
+        current_indexes_map.insert(key, doc.clone());
+    }
+
+    // Fetch target indexes for this model.
+    let mut target_indexes_map: HashMap<String, IndexModel> = HashMap::new();
+    for model in indexes.into_iter() {
+        // Populate the 'target' indexes map for easy comparison later.
+        let key = model.keys.keys().fold(String::from(""), |acc, bkey| acc + bkey);
+        target_indexes_map.insert(key, model);
+    }
+
+    // Determine which indexes must be created on the collection.
+    let mut indexes_to_create = vec![];
+    for (key, index_model) in target_indexes_map.iter() {
+        // Check if key already exists.
+        if !current_indexes_map.contains_key(key) {
+            indexes_to_create.push(index_model.clone())
+        }
+    }
+
+    // Determine which indexes to remove.
+    let mut indexes_to_remove = vec![];
+    for (key, index_doc) in current_indexes_map {
+        // Don't attempt to remove the default index.
+        if &key == DEFAULT_INDEX {
+            continue
+        }
+
+        // Check if key is not present in target indexes map. This means the index needs removal.
+        if !target_indexes_map.contains_key(&key) {
+            indexes_to_remove.push(index_doc);
+        }
+    }
+
+    indexes_to_create.sort_by_key(index_name);
+    indexes_to_remove.sort_by(|a, b| a.get_str("name").unwrap_or("").cmp(b.get_str("name").unwrap_or("")));
+    Ok((indexes_to_create, indexes_to_remove))
+}
+
+/// Synchronize the given indexes with the given collection, returning the names of the indexes
+/// which were created & dropped.
+fn sync_model_indexes<'a>(coll: &'a Collection, indexes: Vec<IndexModel>, progress: Option<&Fn(&IndexBuildProgress)>) -> Result<(Vec<String>, Vec<String>)> {
+    info!("Synchronizing indexes for '{}'.", coll.namespace);
+
+    // Fetch current indexes.
+    let _ = coll.db.create_collection(coll.name().as_str(), None); // NOTE: NB: this is account for the mongodb driver bug: #251.
+    let current = current_indexes(coll)?;
+    let (indexes_to_create, indexes_to_remove) = index_changes(current, indexes)?;
+
+    // Create needed indexes, polling for their build progress if requested.
+    let created_names: Vec<String> = indexes_to_create.iter().map(|model| index_name(model)).collect();
+    match progress {
+        Some(progress) if !indexes_to_create.is_empty() => create_indexes_with_progress(coll, indexes_to_create, progress)?,
+        _ => create_indexes(coll, indexes_to_create)?,
+    }
+
+    // Remove old indexes.
+    let mut dropped_names = vec![];
+    for doc in indexes_to_remove {
+        let index_name = String::from(
+            doc.get_str("name").map_err(|err| DefaultError(format!("Failed to get index name: {:?}", err.description())))?
+        );
+        coll.drop_index_string(index_name.clone())
+            .map_err(|err| DefaultError(format!("Failed to remove index: {}", err.description())))?;
+        dropped_names.push(index_name);
+    }
+
+    info!("Finished synchronizing indexes for '{}'.", coll.namespace);
+    Ok((created_names, dropped_names))
+}
+
+/// Create the given indexes on the given collection.
+///
+/// All indexes are created with a single `createIndexes` command, which allows the server to
+/// build them together. If that fails, each index is created individually so that the error
+/// identifies the offending index.
+fn create_indexes<'a>(coll: &'a Collection, models: Vec<IndexModel>) -> Result<()> {
+    if models.is_empty() {
+        return Ok(());
+    }
+
+    // NOTE: this wraps the native MongoDB `createIndexes` command. Will not fail if an index already exists.
+    let err = match coll.create_indexes(models.clone()) {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+    warn!("Failed to create indexes for '{}' together, creating individually: {}", coll.namespace, err.description());
+    for model in models {
+        let name = model.options.name.clone().unwrap_or_default();
+        coll.create_index_model(model)
+            .map_err(|err| DefaultError(format!("Failed to create index '{}': {}", name, err.description())))?;
+    }
+    Ok(())
+}
+
+/// Create the given indexes on the given collection as by `create_indexes`, meanwhile polling the
+/// server for the progress of their builds & calling `progress` for each index being built.
+///
+/// The indexes are created on another thread, so that `progress` is called on this one.
+fn create_indexes_with_progress<'a>(coll: &'a Collection, models: Vec<IndexModel>, progress: &Fn(&IndexBuildProgress)) -> Result<()> {
+    let (done, created) = mpsc::channel();
+    let builder = coll.db.collection(&coll.name());
+    thread::spawn(move || {
+        let _ = done.send(create_indexes(&builder, models));
+    });
+
+    let admin = coll.db.client.db("admin");
+    let name = coll.name();
+    let mut polling = true;
+    loop {
+        match created.recv_timeout(Duration::from_millis(INDEX_PROGRESS_POLL_INTERVAL_MS)) {
+            Ok(res) => return res,
+            Err(RecvTimeoutError::Disconnected) => return Err(DefaultError(format!("Index creation for '{}' ended without reporting its outcome.", coll.namespace)).into()),
+            Err(RecvTimeoutError::Timeout) if polling => match index_build_ops(&admin, &name) {
+                Ok(ops) => for op in ops {
+                    for report in index_build_progress(&coll.namespace, &op) {
+                        progress(&report);
+                    }
+                },
+                Err(err) => {
+                    warn!("Failed to poll index build progress for '{}': {}", coll.namespace, err.description());
+                    polling = false;
+                },
+            },
+            Err(RecvTimeoutError::Timeout) => (),
+        }
+    }
+}
+
+/// Get the `currentOp` operation documents of the index builds in flight on the named collection.
+fn index_build_ops(admin: &Database, name: &str) -> Result<Vec<Document>> {
+    // Older servers report command operations under `query`, newer under `command`.
+    let spec = doc!{
+        "currentOp": 1,
+        "$or": [{"command.createIndexes": name}, {"query.createIndexes": name}],
+    };
+    let res = admin.command(spec, CommandType::Suppressed, None)?;
+    let ops = res.get_array("inprog").map(|ops| ops.clone()).unwrap_or_default();
+    Ok(ops.into_iter().filter_map(|op| match op {
+        Bson::Document(op) => Some(op),
+        _ => None,
+    }).collect())
+}
+
+/// Wait until none of the named indexes of the given collection are being built, failing if any
+/// are still being built once the given timeout has elapsed.
+fn wait_for_index_builds<'a>(coll: &'a Collection, names: &[String], timeout: Duration) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let admin = coll.db.client.db("admin");
+    let started = Instant::now();
+    loop {
+        let ops = match index_build_ops(&admin, &coll.name()) {
+            Ok(ops) => ops,
+            Err(ref err) if is_unauthorized(err) => {
+                warn!("Not waiting for indexes on '{}' to build, as the connection is not authorized to run `currentOp`: {}", coll.namespace, err);
+                return Ok(());
+            },
+            Err(err) => return Err(err),
+        };
+        let mut pending: Vec<String> = vec![];
+        for op in ops {
+            pending.extend(index_build_names(&op).into_iter().filter(|name| names.contains(name)));
+        }
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            return Err(DefaultError(format!("Timed out after {:?} waiting for indexes on '{}' to build: {}.", timeout, coll.namespace, pending.join(", "))).into());
+        }
+        info!("Waiting for indexes on '{}' to build: {}.", coll.namespace, pending.join(", "));
+        thread::sleep(Duration::from_millis(INDEX_PROGRESS_POLL_INTERVAL_MS));
+    }
+}
+
+/// Whether the given error is the server refusing a command the connection is not authorized
+/// to run.
+fn is_unauthorized(err: &WitherError) -> bool {
+    match err {
+        &WitherError::Mongo(OperationError(ref msg)) => msg.contains("not authorized") || msg.contains("Unauthorized"),
+        _ => false,
+    }
+}
+
+/// Get the names of the indexes being built by the given `currentOp` operation document.
+fn index_build_names(op: &Document) -> Vec<String> {
+    op.get_document("command").or_else(|_| op.get_document("query")).ok()
+        .and_then(|cmd| cmd.get_array("indexes").ok())
+        .map(|indexes| indexes.iter().filter_map(|idx| match idx {
+            &Bson::Document(ref idx) => idx.get_str("name").ok().map(String::from),
+            _ => None,
+        }).collect())
+        .unwrap_or_default()
+}
+
+/// Extract the progress of each index being built by the given `currentOp` operation document.
+///
+/// The indexes of a single `createIndexes` command are built together, so share its progress.
+fn index_build_progress(namespace: &str, op: &Document) -> Vec<IndexBuildProgress> {
+    let progress = match op.get_document("progress") {
+        Ok(progress) => progress,
+        Err(_) => return vec![],
+    };
+    let (done, total) = match (progress.get("done").and_then(bson_as_i64), progress.get("total").and_then(bson_as_i64)) {
+        (Some(done), Some(total)) => (done, total),
+        _ => return vec![],
+    };
+    index_build_names(op).into_iter()
+        .map(|index| IndexBuildProgress{namespace: namespace.to_owned(), index, done, total})
+        .collect()
+}
+
+/// Get the given numeric bson value as an `i64`.
+fn bson_as_i64(value: &Bson) -> Option<i64> {
+    match value {
+        &Bson::I32(val) => Some(val as i64),
+        &Bson::I64(val) => Some(val),
+        &Bson::FloatingPoint(val) => Some(val as i64),
+        _ => None,
+    }
+}
+
+/// Parse an index's usage out of a document output by `$indexStats`.
+fn index_usage_from_stats(stats: &Document, declared: &[String]) -> Option<IndexUsage> {
+    let name = stats.get_str("name").ok()?.to_owned();
+    let accesses = stats.get_document("accesses").ok()?;
+    Some(IndexUsage{
+        keys: stats.get_document("key").ok()?.clone(),
+        host: stats.get_str("host").unwrap_or("").to_owned(),
+        accesses: accesses.get("ops").and_then(bson_as_i64).unwrap_or(0),
+        since: *accesses.get_utc_datetime("since").ok()?,
+        declared: declared.contains(&name),
+        name,
+    })
+}
+
+/// Fetch the options of the given collection, or `None` if it does not exist.
+fn collection_options<'a>(coll: &'a Collection) -> Result<Option<Document>> {
+    let spec = doc!{"listCollections": 1, "filter": {"name": coll.name()}};
+    let res = coll.db.command(spec, CommandType::Suppressed, None)?;
+    Ok(res.get_document("cursor").ok()
+        .and_then(|cursor| cursor.get_array("firstBatch").ok())
+        .and_then(|batch| match batch.first() {
+            Some(&Bson::Document(ref info)) => Some(info.get_document("options").map(|opts| opts.clone()).unwrap_or_default()),
+            _ => None,
+        }))
+}
+
+/// Whether the given collection exists & is capped.
+fn collection_is_capped<'a>(coll: &'a Collection) -> Result<bool> {
+    let options = collection_options(coll)?;
+    Ok(options.and_then(|opts| opts.get_bool("capped").ok()).unwrap_or(false))
+}
+
+/// Whether the given collection options already hold the given validator settings.
+fn validator_matches(options: &Document, validator: &Document, level: &str, action: &str) -> bool {
+    options.get_document("validator").ok() == Some(validator)
+        && options.get_str("validationLevel").unwrap_or("strict") == level
+        && options.get_str("validationAction").unwrap_or("error") == action
+}
+
+/// Apply the given validator to the given collection, creating the collection if needed.
+fn sync_model_validator<'a>(coll: &'a Collection, validator: Document, level: &str, action: &str) -> Result<()> {
+    let name = coll.name();
+
+    // Fetch the collection's current options, if it exists.
+    let mut spec = match collection_options(coll)? {
+        // The collection does not exist yet, so create it along with the validator.
+        None => {
+            info!("Creating collection '{}' with validator.", coll.namespace);
+            doc!{"create": name}
+        },
+        Some(options) => {
+            if validator_matches(&options, &validator, level, action) {
+                debug!("Validator of '{}' is up to date.", coll.namespace);
+                return Ok(());
+            }
+            info!("Validator of '{}' has changed, applying new validator.", coll.namespace);
+            doc!{"collMod": name}
+        },
+    };
+    spec.insert("validator", validator);
+    spec.insert("validationLevel", level);
+    spec.insert("validationAction", action);
+    coll.db.command(spec, CommandType::Suppressed, None)?;
+    Ok(())
+}
+
+/// Build the `timeseries` options of `createCollection` from the given configuration, checking
+/// its fields against each other & against the `$jsonSchema` of the given validator, if any.
+fn timeseries_options(timeseries: &Timeseries, validator: Option<&Document>) -> Result<Document> {
+    let properties = validator
+        .and_then(|validator| validator.get_document("$jsonSchema").ok())
+        .and_then(|schema| schema.get_document("properties").ok());
+    let mut fields = vec![&timeseries.time_field];
+    fields.extend(timeseries.meta_field.as_ref());
+    for field in fields.iter() {
+        if field.is_empty() || field.split('.').any(|segment| segment.is_empty() || segment.starts_with('$')) {
+            return Err(ArgumentError(format!("Time-series field '{}' must not contain empty or '$' prefixed segments.", field)).into());
+        }
+        if field.as_str() == "_id" {
+            return Err(ArgumentError("Time-series fields must not be '_id'.".to_owned()).into());
+        }
+        if let Some(properties) = properties {
+            if !properties.contains_key(field.split('.').next().unwrap_or("")) {
+                return Err(ArgumentError(format!("Time-series field '{}' is not a property of the model's validator.", field)).into());
+            }
+        }
+    }
+    if timeseries.meta_field.as_ref() == Some(&timeseries.time_field) {
+        return Err(ArgumentError(format!("Time-series time & meta fields must differ, both are '{}'.", timeseries.time_field)).into());
+    }
+
+    let mut options = doc!{"timeField": timeseries.time_field.clone()};
+    if let Some(ref meta_field) = timeseries.meta_field {
+        options.insert("metaField", meta_field.clone());
+    }
+    if let Some(granularity) = timeseries.granularity {
+        options.insert("granularity", granularity.as_str());
+    }
+    Ok(options)
+}
+
+/// Create the given collection as a time-series collection having the given options, unless it
+/// already exists.
+fn sync_model_timeseries<'a>(coll: &'a Collection, options: Document) -> Result<()> {
+    match collection_options(coll)? {
+        None => {
+            let version = server_version(&coll.db)?;
+            if version < TIMESERIES_MIN_SERVER_VERSION {
+                return Err(ArgumentError(format!("Time-series collection '{}' requires MongoDB {} but the server is {}.", coll.namespace, TIMESERIES_MIN_SERVER_VERSION, version)).into());
+            }
+            info!("Creating time-series collection '{}'.", coll.namespace);
+            let mut spec = doc!{"create": coll.name()};
+            spec.insert("timeseries", options);
+            coll.db.command(spec, CommandType::Suppressed, None)?;
+        },
+        Some(existing) => match existing.get_document("timeseries").ok() {
+            None => warn!("Collection '{}' already exists & is not a time-series collection. It must be recreated to become one.", coll.namespace),
+            // The server reports additional options, such as `bucketMaxSpanSeconds`.
+            Some(current) if options.iter().any(|(key, val)| current.get(key) != Some(val)) => {
+                warn!("Time-series options of '{}' are {}, but the model declares {}.", coll.namespace, current, options);
+            },
+            Some(_) => debug!("Time-series collection '{}' is up to date.", coll.namespace),
+        },
+    }
+    Ok(())
+}
+
+/// Describe the given migration as a statement for `Model::sync_plan`.
+fn migration_statement(spec: &MigrationSpec) -> String {
+    let mut statement = format!("RUN MIGRATION {}:", spec.name);
+    if let Some(ref set) = spec.set {
+        statement.push_str(&format!(" $set {}", set));
+    }
+    if let Some(ref unset) = spec.unset {
+        statement.push_str(&format!(" $unset {{{}}}", unset.keys().cloned().collect::<Vec<String>>().join(", ")));
+    }
+    if let Some(ref filter) = spec.filter {
+        statement.push_str(&format!(" WHERE {}", filter));
+    }
+    if spec.destructive {
+        statement.push_str(" (DESTRUCTIVE)");
+    }
+    statement
+}
+
+/// The collection to which the given model's migrations are audited, if they are to be.
+fn migration_audit<'a, M: Model<'a>>() -> Option<&'static str> {
+    if M::audit_migrations() { Some(M::migration_audit_collection()) } else { None }
+}
+
+/// Synchronize the given model with the backend, as by `Model::sync_with_progress`.
+///
+/// When `lock_held`, the caller already holds the collection's migration lock.
+fn sync_model<'a, M: Model<'a>>(db: Database, progress: Option<&Fn(&IndexBuildProgress)>, lock_held: bool) -> Result<SyncReport> {
+    let coll = M::collection(&db);
+    check_soft_delete_field::<M>()?;
+    if M::verify_sync_privileges() {
+        check_sync_privileges(&coll, &sync_privileges::<M>(&coll)?)?;
+    }
+    if let Some(ref timeseries) = M::timeseries() {
+        sync_model_timeseries(&coll, timeseries_options(timeseries, M::validator().as_ref())?)?;
+    }
+    if let Some(validator) = M::validator() {
+        sync_model_validator(&coll, validator, M::validation_level(), M::validation_action())?;
+    }
+    let indexes = synced_indexes::<M>(&coll)?;
+    let shard_key = M::shard_key();
+    let (indexes_created, indexes_dropped) = sync_model_indexes(&coll, indexes, progress)?;
+    if let Some(timeout) = M::index_ready_timeout() {
+        wait_for_index_builds(&coll, &indexes_created, timeout)?;
+    }
+    if let Some(shard_key) = shard_key {
+        sync_model_shard_key(&coll, shard_key)?;
+    }
+    let migrations = sync_model_migrations(&coll, M::migrations(), M::sync_log_level(), M::environment(), if lock_held { None } else { Some(M::migration_lock_timeout()) }, migration_audit::<M>())?;
+    let mut seeds = vec![];
+    for seed in M::seeds() {
+        seed.validate()?;
+        seeds.push(seed.document_from_instance()?);
+    }
+    let seeds_upserted = query_cache::invalidate_after(&coll.namespace, || sync_model_seeds(&coll, seeds, M::model_write_concern()))?;
+
+    let report = SyncReport{namespace: coll.namespace.clone(), indexes_created, indexes_dropped, migrations, seeds_upserted};
+    M::after_sync(db.clone(), &report)?;
+    Ok(report)
+}
+
+/// Execute the given migrations against the given collection, holding its migration lock.
+///
+/// The lock is acquired with the given timeout, or is already held by the caller if `None`.
+fn sync_model_migrations<'a>(coll: &'a Collection, migrations: Vec<Box<Migration>>, log_level: Level, environment: Option<String>, lock_timeout: Option<Duration>, audit: Option<&str>) -> Result<Vec<MigrationResult>> {
+    if migrations.is_empty() {
+        return Ok(vec![]);
+    }
+    let lock_timeout = match lock_timeout {
+        Some(lock_timeout) => lock_timeout,
+        None => return query_cache::invalidate_after(&coll.namespace, || execute_model_migrations(coll, migrations, log_level, environment, audit)),
+    };
+
+    // Hold the migration lock for the duration, releasing it even when a migration fails.
+    let token = acquire_migration_lock(coll, lock_timeout)?;
+    let results = query_cache::invalidate_after(&coll.namespace, || execute_model_migrations(coll, migrations, log_level, environment, audit));
+    release_migration_lock_or_warn(coll, &token);
+    results
+}
+
+/// Release the migration lock of the given collection held under the given token, logging any
+/// failure to do so, as the lock expires regardless.
+fn release_migration_lock_or_warn<'a>(coll: &'a Collection, token: &str) {
+    if let Err(err) = release_migration_lock(coll, token) {
+        warn!("Failed to release the migration lock of '{}', which will expire instead: {}", coll.namespace, err);
+    }
+}
+
+/// Ensure that each of the given migrations which applies to the given environment may be
+/// executed, before any is.
+fn check_migrations_executable<'a>(coll: &'a Collection, migrations: &[Box<Migration>], environment: Option<&str>) -> Result<()> {
+    // Names identify tracking records, so must be present & unique.
+    let mut names: Vec<&str> = vec![];
+    for migration in migrations {
+        let name = migration.name();
+        if name.is_empty() {
+            return Err(ArgumentError(format!("Every migration against '{}' must have a name.", coll.namespace)).into());
+        }
+        if names.contains(&name) {
+            return Err(ArgumentError(format!("Migration name '{}' is declared more than once against '{}'.", name, coll.namespace)).into());
+        }
+        names.push(name);
+    }
+    for migration in migrations.iter().filter(|migration| runs_in_environment(migration.environments(), environment)) {
+        // Refuse to execute a migration without the atomicity guarantees it has asked for.
+        if migration.transactional() {
+            return Err(DefaultError(format!("Transactional migrations are not supported by the underlying driver. Refusing to migrate '{}'.", coll.namespace)).into());
+        }
+        if migration.is_destructive() && !destructive_migrations_allowed() {
+            return Err(DefaultError(format!("Migration '{}' against '{}' is destructive. Refusing to execute it unless {}=1 is set.", migration.name(), coll.namespace, ALLOW_DESTRUCTIVE_ENV)).into());
+        }
+    }
+    Ok(())
+}
+
+fn execute_model_migrations<'a>(coll: &'a Collection, migrations: Vec<Box<Migration>>, log_level: Level, environment: Option<String>, audit: Option<&str>) -> Result<Vec<MigrationResult>> {
+    check_migrations_executable(coll, &migrations, environment.as_ref().map(String::as_str))?;
+    verify_migration_checksums(coll, &migrations)?;
+    info!("Starting migrations for '{}'.", coll.namespace);
+
+    let mut results = vec![];
+    // Execute each migration.
+    for migration in migrations {
+        // Skip migrations meant for other environments.
+        if !runs_in_environment(migration.environments(), environment.as_ref().map(String::as_str)) {
+            log!(log_level, "Skipping migration '{}' against '{}', as it does not apply to environment {:?}.", migration.name(), coll.namespace, environment);
+            continue;
+        }
+        info!("Executing migration '{}' against '{}'.", migration.name(), coll.namespace);
+        let started = Instant::now();
+        let mut res = migration.execute(coll)?;
+        res.duration = started.elapsed();
+        track_migration(coll, &*migration, &res)?;
+        if let Some(audit_collection) = audit {
+            audit_migration(coll, audit_collection, &res)?;
+        }
+
+        // Routine no-ops are logged at the model's configured level.
+        let level = if res.is_noop() { log_level } else { Level::Info };
+        log!(level, "Successfully executed migration '{}' against '{}' in {}ms. {} matched. {} modified.", res.name, coll.namespace, duration_ms(res.duration), res.matched_count, res.modified_count);
+        results.push(res);
+    }
+
+    info!("Finished migrations for '{}'.", coll.namespace);
+    Ok(results)
+}
+
+fn sync_model_seeds<'a>(coll: &'a Collection, seeds: Vec<Document>, write_concern: WriteConcern) -> Result<usize> {
+    info!("Synchronizing seeds for '{}'.", coll.namespace);
+
+    let count = seeds.len();
+    for seed in seeds {
+        // Seeds are upserted by ID, so every seed needs one.
+        let id = match seed.get("_id") {
+            Some(id) => id.clone(),
+            None => return Err(ArgumentError(format!("Every seed for '{}' must have an ID.", coll.namespace)).into()),
+        };
+        let options = UpdateOptions{upsert: Some(true), write_concern: Some(write_concern.clone())};
+        let res = coll.update_one(doc!{"_id": id}, doc!{"$set": seed}, Some(options))?;
+        if let Some(err) = res.write_exception {
+            error!("Error synchronizing seed: {:?}", err.description());
+            return Err(WriteError(err).into());
+        }
+    }
+
+    info!("Finished synchronizing seeds for '{}'.", coll.namespace);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
 
     #[test]
     fn basic_index_options_returns_expected_output() {
@@ -515,4 +4740,593 @@ mod tests {
         assert!(output.min == None);
         assert!(output.bucket_size == None);
     }
+
+    #[test]
+    fn prefix_index_prefixes_keys_and_name() {
+        let model = IndexModel{
+            keys: doc!{"city": 1, "zip": -1},
+            options: basic_index_options("city-zip", true, None, None, None),
+        };
+
+        let output = prefix_index("address", model);
+
+        assert_eq!(output.keys, doc!{"address.city": 1, "address.zip": -1});
+        assert!(output.options.name == Some("address.city-zip".to_string()));
+    }
+
+    #[test]
+    fn prefix_index_scopes_wildcards_to_the_prefix() {
+        let wildcard = IndexModel{keys: doc!{"$**": 1}, options: basic_index_options("all", true, None, None, None)};
+        let text = IndexModel{keys: doc!{"$**": "text"}, options: basic_index_options("search", true, None, None, None)};
+
+        let wildcard = prefix_index("address", wildcard);
+        let text = prefix_index("address", text);
+
+        assert_eq!(wildcard.keys, doc!{"address.$**": 1});
+        assert!(check_index_limits("users", &[wildcard]).is_ok());
+        let err = check_index_limits("users", &[text]).unwrap_err();
+        assert_eq!(err.description(), "Collection 'users' declares index 'address.search' as a wildcard text index on 'address.$**'; MongoDB only allows wildcard text indexes on '$**'.");
+    }
+
+    #[test]
+    fn read_options_apply_model_read_preference() {
+        #[derive(Serialize, Deserialize)]
+        struct Report {
+            #[serde(rename="_id", skip_serializing_if="Option::is_none")]
+            id: Option<ObjectId>,
+        }
+        impl<'a> Model<'a> for Report {
+            const COLLECTION_NAME: &'static str = "reports";
+            fn id(&self) -> Option<ObjectId> { self.id.clone() }
+            fn set_id(&mut self, oid: ObjectId) { self.id = Some(oid); }
+            fn allow_partial_results() -> bool { true }
+            fn read_preference() -> Option<ReadPreference> { Some(ReadPreference::new(ReadMode::Secondary, None)) }
+        }
+
+        let options = read_options::<Report>();
+
+        assert!(options.allow_partial_results);
+        assert!(options.read_preference.map(|pref| pref.mode) == Some(ReadMode::Secondary));
+    }
+
+    #[test]
+    fn query_cache_hash_only_ignores_top_level_field_order() {
+        let hash = |filter: Document| query_cache_hash(Some(&filter), None).unwrap();
+        let mut sorted = FindOptions::new();
+        sorted.sort = Some(doc!{"email": 1});
+
+        assert_eq!(hash(doc!{"a": 1, "b": 2}), hash(doc!{"b": 2, "a": 1}));
+        assert_ne!(hash(doc!{"a": {"x": 1, "y": 2}}), hash(doc!{"a": {"y": 2, "x": 1}}));
+        assert_ne!(query_cache_hash(None, None).unwrap(), query_cache_hash(None, Some(&sorted)).unwrap());
+    }
+
+    #[test]
+    fn unloaded_lazy_fields_are_missing_or_null() {
+        let doc = doc!{"name": "a", "blob": Bson::Null, "history": ["b"]};
+
+        assert_eq!(unloaded_lazy_fields(&doc, &["blob", "history", "notes"]), vec!["blob", "notes"]);
+        assert!(unloaded_lazy_fields(&doc, &["history"]).is_empty());
+    }
+
+    #[test]
+    fn lazy_projection_excludes_lazy_fields_not_included() {
+        assert_eq!(lazy_projection(&["blob", "history"], &["history"]), Some(doc!{"blob": 0}));
+        assert_eq!(lazy_projection(&["blob"], &["blob"]), None);
+        assert_eq!(lazy_projection(&[], &[]), None);
+    }
+
+    #[test]
+    fn check_field_ranges_rejects_values_outside_ranges() {
+        let doc = doc!{"age": 151, "balance": -0.5, "score": 10i64, "name": "test"};
+
+        assert!(check_field_ranges(&doc, &[FieldRange::at_most("score", 10.0), FieldRange::at_least("missing", 1.0)]).is_ok());
+        match check_field_ranges(&doc, &[FieldRange::between("age", 0.0, 150.0)]) {
+            Err(WitherError::OutOfRange{field, min, max, actual}) => assert_eq!((field.as_str(), min, max, actual), ("age", Some(0.0), Some(150.0), 151.0)),
+            other => panic!("Expected an out of range error, got {:?}.", other),
+        }
+        assert!(check_field_ranges(&doc, &[FieldRange::at_least("balance", 0.0)]).is_err());
+        assert!(check_field_ranges(&doc, &[FieldRange::at_least("name", 0.0)]).is_err());
+    }
+
+    #[test]
+    fn check_required_fields_rejects_empty_values() {
+        let doc = doc!{"email": "test@test.com", "name": "", "age": 0, "tags": [], "address": {"city": "Berlin"}};
+
+        assert!(check_required_fields(&doc, &["email", "address.city"]).is_ok());
+        for field in &["name", "age", "tags", "missing", "address.zip"] {
+            match check_required_fields(&doc, &[*field]) {
+                Err(WitherError::MissingRequiredField{field: output}) => assert_eq!(output, field.to_string()),
+                other => panic!("Expected a missing required field error, got {:?}.", other),
+            }
+        }
+    }
+
+    #[test]
+    fn diff_documents_reports_changed_removed_and_added_fields() {
+        let old = doc!{"_id": 1, "email": "test@test.com", "name": "Test", "address": {"city": "Berlin", "zip": "10115"}};
+        let new = doc!{"_id": 1, "email": "new@test.com", "address": {"zip": "10115", "city": "Berlin"}, "age": 30};
+
+        let output = diff_documents(&old, &new);
+
+        assert_eq!(output, vec![
+            FieldChange{field: "email".to_string(), old: Some(Bson::String("test@test.com".to_string())), new: Some(Bson::String("new@test.com".to_string()))},
+            FieldChange{field: "name".to_string(), old: Some(Bson::String("Test".to_string())), new: None},
+            FieldChange{field: "age".to_string(), old: None, new: Some(Bson::I32(30))},
+        ]);
+    }
+
+    #[test]
+    fn documents_equivalent_ignores_field_order() {
+        let stored = doc!{"_id": 1, "email": "test@test.com", "address": {"city": "Berlin", "zip": "10115"}};
+        let reordered = doc!{"address": {"zip": "10115", "city": "Berlin"}, "email": "test@test.com", "_id": 1};
+        let changed = doc!{"_id": 1, "email": "new@test.com", "address": {"city": "Berlin", "zip": "10115"}};
+
+        assert!(documents_equivalent(&stored, &reordered));
+        assert!(!documents_equivalent(&stored, &changed));
+        assert!(!documents_equivalent(&stored, &doc!{"_id": 1, "email": "test@test.com"}));
+    }
+
+    #[test]
+    fn soft_delete_many_filter_excludes_deleted_unless_constrained() {
+        let constrained = doc!{"deleted_at": {"$exists": true}};
+
+        assert_eq!(soft_delete_many_filter(doc!{"email": "test@test.com"}), doc!{"email": "test@test.com", "deleted_at": Bson::Null});
+        assert_eq!(soft_delete_many_filter(constrained.clone()), constrained);
+    }
+
+    #[test]
+    fn near_sphere_filter_applies_both_distance_bounds() {
+        let output = near_sphere_filter("location", 13.4, 52.5, Some(10.0), Some(500.0));
+
+        assert_eq!(output, doc!{"location": {"$nearSphere": {
+            "$geometry": {"type": "Point", "coordinates": [13.4, 52.5]},
+            "$minDistance": 10.0,
+            "$maxDistance": 500.0,
+        }}});
+        assert_eq!(near_sphere_filter("location", 13.4, 52.5, None, None), doc!{"location": {"$nearSphere": {
+            "$geometry": {"type": "Point", "coordinates": [13.4, 52.5]},
+        }}});
+    }
+
+    #[test]
+    fn document_content_hash_ignores_field_order_ids_and_timestamps() {
+        let first = doc!{"_id": 1, "email": "test@test.com", "address": {"city": "Berlin", "zip": "10115"}};
+        let second = doc!{"address": {"zip": "10115", "city": "Berlin"}, "updated_at": 5, "email": "test@test.com", "_id": 2};
+        let changed = doc!{"_id": 1, "email": "new@test.com", "address": {"city": "Berlin", "zip": "10115"}};
+
+        let hash = document_content_hash(&first, None).expect("Expected a hash.");
+
+        assert_eq!(hash, document_content_hash(&second, None).expect("Expected a hash."));
+        assert!(hash != document_content_hash(&changed, None).expect("Expected a hash."));
+    }
+
+    #[test]
+    fn slice_to_bson_supports_counts_and_ranges() {
+        assert_eq!(Slice::Count(5).to_bson(), Bson::I32(5));
+        assert_eq!(Slice::Count(-5).to_bson(), Bson::I32(-5));
+        assert_eq!(Slice::Range{skip: 10, limit: 5}.to_bson(), Bson::Array(vec![Bson::I32(10), Bson::I32(5)]));
+    }
+
+    #[test]
+    fn random_object_id_generates_distinct_ids() {
+        let first = random_object_id();
+        let second = random_object_id();
+
+        assert!(first != second);
+    }
+
+    #[test]
+    fn raw_path_index_indexes_given_path() {
+        let output = raw_path_index("attributes.priority", -1, basic_index_options("priority", true, None, None, None))
+            .expect("Expected a valid index path.");
+
+        assert_eq!(output.keys, doc!{"attributes.priority": -1});
+    }
+
+    #[test]
+    fn check_index_limits_counts_distinct_keys_including_id() {
+        let index = |field: &str| {
+            let mut keys = Document::new();
+            keys.insert(field, 1);
+            IndexModel{keys, options: basic_index_options(field, true, None, None, None)}
+        };
+        let mut indexes: Vec<IndexModel> = (0..63).map(|idx| index(&format!("field{}", idx))).collect();
+        indexes.push(index("_id"));
+        indexes.push(index("field0"));
+        assert!(check_index_limits("users", &indexes).is_ok());
+
+        indexes.push(index("field63"));
+        let err = check_index_limits("users", &indexes).unwrap_err();
+        assert_eq!(err.description(), "Collection 'users' declares 65 indexes; MongoDB allows 64.");
+    }
+
+    #[test]
+    fn check_index_limits_tells_indexes_apart_by_full_keys() {
+        let index = |keys: Document| IndexModel{keys, options: basic_index_options("index", true, None, None, None)};
+        let mut indexes: Vec<IndexModel> = (0..61).map(|idx| {
+            let mut keys = Document::new();
+            keys.insert(format!("field{}", idx), 1);
+            index(keys)
+        }).collect();
+        indexes.push(index(doc!{"a": 1, "bc": 1}));
+        indexes.push(index(doc!{"ab": 1, "c": 1}));
+        assert!(check_index_limits("users", &indexes).is_ok());
+
+        indexes.push(index(doc!{"field0": -1}));
+        let err = check_index_limits("users", &indexes).unwrap_err();
+        assert_eq!(err.description(), "Collection 'users' declares 65 indexes; MongoDB allows 64.");
+    }
+
+    #[test]
+    fn check_index_limits_rejects_too_many_compound_fields() {
+        let mut keys = Document::new();
+        for idx in 0..33 {
+            keys.insert(format!("field{}", idx), 1);
+        }
+        let indexes = vec![IndexModel{keys, options: basic_index_options("wide", true, None, None, None)}];
+
+        let err = check_index_limits("users", &indexes).unwrap_err();
+
+        assert_eq!(err.description(), "Collection 'users' declares index 'wide' with 33 fields; MongoDB allows 32.");
+    }
+
+    #[test]
+    fn validate_index_path_rejects_empty_and_operator_segments() {
+        assert!(validate_index_path("attributes.priority").is_ok());
+        assert!(validate_index_path("").is_err());
+        assert!(validate_index_path("attributes.").is_err());
+        assert!(validate_index_path("attributes..priority").is_err());
+        assert!(validate_index_path("attributes.$priority").is_err());
+    }
+
+    #[test]
+    fn index_usage_from_stats_extracts_usage() {
+        let since = Utc.ymd(2018, 1, 1).and_hms(0, 0, 0);
+        let stats = doc!{"name": "unique-email", "key": {"email": 1}, "host": "db:27017", "accesses": {"ops": 42i64, "since": Bson::UtcDatetime(since)}};
+
+        let output = index_usage_from_stats(&stats, &["unique-email".to_string()]);
+
+        assert_eq!(output, Some(IndexUsage{
+            name: "unique-email".to_string(),
+            keys: doc!{"email": 1},
+            host: "db:27017".to_string(),
+            accesses: 42,
+            since,
+            declared: true,
+        }));
+    }
+
+    #[test]
+    fn server_version_parses_and_orders_versions() {
+        assert_eq!(ServerVersion::parse("3.6.4"), Some(ServerVersion::new(3, 6, 4)));
+        assert_eq!(ServerVersion::parse("4.0.0-rc1"), Some(ServerVersion::new(4, 0, 0)));
+        assert_eq!(ServerVersion::parse("4.2"), Some(ServerVersion::new(4, 2, 0)));
+        assert_eq!(ServerVersion::parse("bad"), None);
+        assert!(ServerVersion::new(3, 6, 4) < ServerVersion::new(4, 2, 0));
+    }
+
+    #[test]
+    fn index_min_server_version_detects_gated_features() {
+        let wildcard = IndexModel{keys: doc!{"attributes.$**": 1}, options: basic_index_options("attributes", true, None, None, None)};
+        let plain = IndexModel{keys: doc!{"email": 1}, options: basic_index_options("email", true, None, None, None)};
+
+        assert_eq!(index_min_server_version(&wildcard), Some(ServerVersion::new(4, 2, 0)));
+        assert_eq!(index_min_server_version(&plain), None);
+    }
+
+    #[test]
+    fn index_descriptor_describes_set_options() {
+        let index = IndexModel{keys: doc!{"email": 1}, options: basic_index_options("unique-email", true, Some(true), None, None)};
+
+        assert_eq!(index_descriptor(&index), doc!{"name": "unique-email", "key": {"email": 1}, "unique": true, "background": true});
+    }
+
+    #[test]
+    fn index_descriptor_describes_text_and_geo_options() {
+        let mut options = basic_index_options("search", true, None, None, None);
+        options.weights = Some(doc!{"title": 10});
+        options.default_language = Some("english".to_string());
+        options.text_version = Some(3);
+        let text = IndexModel{keys: doc!{"title": "text"}, options};
+        let mut options = basic_index_options("location", true, None, None, None);
+        options.bits = Some(26);
+        options.min = Some(-180.0);
+        options.max = Some(180.0);
+        let geo = IndexModel{keys: doc!{"location": "2d"}, options};
+
+        assert_eq!(index_descriptor(&text), doc!{"name": "search", "key": {"title": "text"}, "background": true, "weights": {"title": 10}, "default_language": "english", "textIndexVersion": 3});
+        assert_eq!(index_descriptor(&geo), doc!{"name": "location", "key": {"location": "2d"}, "background": true, "bits": 26, "min": -180.0, "max": 180.0});
+    }
+
+    #[test]
+    fn index_changes_orders_changes_by_name() {
+        let current = vec![
+            doc!{"name": "_id_", "key": {"_id": 1}},
+            doc!{"name": "stale-b", "key": {"b": 1}},
+            doc!{"name": "stale-a", "key": {"a": 1}},
+            doc!{"name": "unique-email", "key": {"email": 1}},
+        ];
+        let indexes = vec![
+            IndexModel{keys: doc!{"email": 1}, options: basic_index_options("unique-email", true, Some(true), None, None)},
+            IndexModel{keys: doc!{"name": 1}, options: basic_index_options("name", true, None, None, None)},
+            IndexModel{keys: doc!{"city": 1}, options: basic_index_options("city", true, None, None, None)},
+        ];
+
+        let (create, remove) = index_changes(current, indexes).expect("Expected index changes.");
+
+        assert_eq!(create.iter().map(index_name).collect::<Vec<String>>(), vec!["city".to_string(), "name".to_string()]);
+        assert_eq!(remove.iter().map(|doc| doc.get_str("name").unwrap()).collect::<Vec<&str>>(), vec!["stale-a", "stale-b"]);
+    }
+
+    #[test]
+    fn migration_statement_describes_update_and_filter() {
+        let spec = MigrationSpec{
+            name: "remove-oldfield".to_string(),
+            checksum: None,
+            destructive: false,
+            environments: None,
+            threshold: None,
+            filter: Some(doc!{"oldfield": {"$exists": true}}),
+            set: None,
+            unset: Some(doc!{"oldfield": ""}),
+        };
+
+        let output = migration_statement(&spec);
+
+        assert_eq!(output, "RUN MIGRATION remove-oldfield: $unset {oldfield} WHERE { oldfield: { $exists: true } }");
+    }
+
+    #[test]
+    fn index_build_names_extracts_all_names_from_current_op() {
+        let op = doc!{
+            "query": {"createIndexes": "users", "indexes": [{"name": "unique-email"}, {"name": "city"}]},
+        };
+
+        let output = index_build_names(&op);
+
+        assert_eq!(output, vec!["unique-email".to_string(), "city".to_string()]);
+    }
+
+    #[test]
+    fn index_build_progress_extracts_progress_from_current_op() {
+        let op = doc!{
+            "command": {"createIndexes": "users", "indexes": [{"name": "unique-email"}, {"name": "name"}]},
+            "progress": {"done": 25, "total": 100},
+        };
+
+        let output = index_build_progress("db.users", &op);
+
+        assert_eq!(output.iter().map(|report| report.index.as_str()).collect::<Vec<&str>>(), vec!["unique-email", "name"]);
+        assert_eq!(output[0].percent(), 25.0);
+    }
+
+    #[test]
+    fn touch_document_timestamps_keeps_creation_time_and_field_order() {
+        let created = Utc.ymd(2018, 1, 1).and_hms(0, 0, 0);
+        let now = Utc.ymd(2018, 6, 1).and_hms(0, 0, 0);
+        let mut fresh = doc!{"_id": 1, "created_at": Bson::Null, "updated_at": Bson::Null, "title": "new"};
+        let mut stored = doc!{"_id": 1, "created_at": Bson::UtcDatetime(created), "updated_at": Bson::UtcDatetime(created), "title": "old"};
+
+        touch_document_timestamps(&mut fresh, now);
+        touch_document_timestamps(&mut stored, now);
+
+        assert_eq!(fresh, doc!{"_id": 1, "created_at": Bson::UtcDatetime(now), "updated_at": Bson::UtcDatetime(now), "title": "new"});
+        assert_eq!(stored, doc!{"_id": 1, "created_at": Bson::UtcDatetime(created), "updated_at": Bson::UtcDatetime(now), "title": "old"});
+    }
+
+    #[test]
+    fn is_unauthorized_recognizes_authorization_errors() {
+        assert!(is_unauthorized(&WitherError::Mongo(OperationError("not authorized on admin to execute command { currentOp: 1 }".to_owned()))));
+        assert!(!is_unauthorized(&WitherError::Mongo(OperationError("ns not found".to_owned()))));
+    }
+
+    #[test]
+    fn stamp_updated_at_merges_into_existing_set() {
+        let mut update = doc!{"$set": {"email": "new@test.com"}, "$inc": {"logins": 1}};
+
+        stamp_updated_at(&mut update);
+
+        let set = update.get_document("$set").expect("Expected a `$set` document.");
+        assert_eq!(set.get_str("email").unwrap(), "new@test.com");
+        assert!(set.get_utc_datetime(UPDATED_AT_FIELD).is_ok());
+        assert!(update.contains_key("$inc"));
+    }
+
+    #[test]
+    fn soft_delete_index_declares_ttl_index_on_deleted_at() {
+        let output = soft_delete_index(&SoftDelete{ttl_seconds: Some(2592000)})
+            .expect("Expected a valid soft delete configuration.")
+            .expect("Expected a TTL index.");
+
+        assert_eq!(output.keys, doc!{"deleted_at": 1});
+        assert!(output.options.expire_after_seconds == Some(2592000));
+    }
+
+    #[test]
+    fn soft_delete_index_rejects_non_positive_ttl() {
+        assert!(soft_delete_index(&SoftDelete{ttl_seconds: Some(0)}).is_err());
+        assert!(soft_delete_index(&SoftDelete{ttl_seconds: None}).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_fields_skips_known_ignored_and_null_fields() {
+        let stored = doc!{"_id": 1, "email": "test@test.com", "legacy": "old", "_v": 2, "removed": Bson::Null};
+        let known = doc!{"_id": 1, "email": "test@test.com"};
+
+        assert_eq!(unknown_fields(&stored, &known, &["_v"]), vec!["legacy".to_string()]);
+    }
+
+    #[test]
+    fn privileges_grant_matches_actions_on_covering_resources() {
+        let privileges = vec![
+            Bson::Document(doc!{"resource": {"db": "app", "collection": "users"}, "actions": ["find", "createIndex"]}),
+            Bson::Document(doc!{"resource": {"db": "app", "collection": ""}, "actions": ["listIndexes"]}),
+            Bson::Document(doc!{"resource": {"db": "", "collection": "logs"}, "actions": ["dropIndex"]}),
+        ];
+
+        assert!(privileges_grant(&privileges, "app", "users", "createIndex"));
+        assert!(privileges_grant(&privileges, "app", "users", "listIndexes"));
+        assert!(!privileges_grant(&privileges, "app", "users", "dropIndex"));
+        assert!(privileges_grant(&privileges, "other", "logs", "dropIndex"));
+        assert!(!privileges_grant(&privileges, "other", "users", "find"));
+    }
+
+    #[test]
+    fn migration_privileges_cover_tracking_lock_and_audit_collections() {
+        let privileges = migration_privileges(Some("audit"));
+
+        assert!(privileges.contains(&(MIGRATIONS_COLLECTION.to_string(), "update")));
+        assert!(privileges.contains(&(MIGRATION_LOCKS_COLLECTION.to_string(), "remove")));
+        assert!(privileges.contains(&("audit".to_string(), "insert")));
+        assert!(!migration_privileges(None).iter().any(|&(ref collection, _)| collection == "audit"));
+    }
+
+    #[test]
+    fn field_probe_records_serialized_field_names() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Probed {
+            name: String,
+            #[serde(rename="deleted_at")]
+            deleted: Option<bson::UtcDateTime>,
+        }
+        let mut probe = FieldProbe(None);
+
+        assert!(Probed::deserialize(&mut probe).is_err());
+        assert_eq!(probe.0, Some(&["name", "deleted_at"][..]));
+    }
+
+    #[test]
+    fn take_record_id_removes_record_id_from_document() {
+        let mut doc = doc!{"email": "test@test.com", "$recordId": 42i64};
+
+        assert_eq!(take_record_id(&mut doc).expect("Expected a record ID."), 42);
+        assert_eq!(doc, doc!{"email": "test@test.com"});
+        assert!(take_record_id(&mut doc).is_err());
+    }
+
+    #[test]
+    fn timeseries_options_validate_fields_against_validator() {
+        let timeseries = Timeseries{time_field: "ts".to_owned(), meta_field: Some("sensor".to_owned()), granularity: Some(TimeseriesGranularity::Minutes)};
+        let validator = doc!{"$jsonSchema": {"properties": {"ts": {"bsonType": "date"}, "sensor": {"bsonType": "string"}}}};
+
+        let output = timeseries_options(&timeseries, Some(&validator)).expect("Expected valid time-series options.");
+
+        assert_eq!(output, doc!{"timeField": "ts", "metaField": "sensor", "granularity": "minutes"});
+        assert!(timeseries_options(&Timeseries{time_field: "at".to_owned(), ..timeseries.clone()}, Some(&validator)).is_err());
+        assert!(timeseries_options(&Timeseries{time_field: "sensor".to_owned(), ..timeseries.clone()}, None).is_err());
+        assert!(timeseries_options(&Timeseries{time_field: "$ts".to_owned(), ..timeseries}, None).is_err());
+    }
+
+    #[test]
+    fn best_index_prefers_longest_key_prefix_match() {
+        let indexes = vec![
+            IndexModel{keys: doc!{"email": 1}, options: basic_index_options("email", true, None, None, None)},
+            IndexModel{keys: doc!{"status": 1, "created_at": -1}, options: basic_index_options("status-created", true, None, None, None)},
+            IndexModel{keys: doc!{"created_at": -1}, options: basic_index_options("created", true, None, None, None)},
+        ];
+
+        let output = best_index(&indexes, &doc!{"$and": [{"status": "active"}], "created_at": {"$gt": 0}});
+
+        assert_eq!(output, Some("status-created".to_string()));
+        assert_eq!(best_index(&indexes, &doc!{"name": "test"}), None);
+    }
+
+    #[test]
+    fn best_index_prefers_unique_indexes_and_skips_text_indexes() {
+        let indexes = vec![
+            IndexModel{keys: doc!{"email": "text"}, options: basic_index_options("email-text", true, None, None, None)},
+            IndexModel{keys: doc!{"email": 1, "name": 1}, options: basic_index_options("email-name", true, None, None, None)},
+            IndexModel{keys: doc!{"email": 1}, options: basic_index_options("unique-email", true, Some(true), None, None)},
+        ];
+
+        let output = best_index(&indexes, &doc!{"email": "test@test.com"});
+
+        assert_eq!(output, Some("unique-email".to_string()));
+    }
+
+    #[test]
+    fn index_name_defaults_to_generated_name() {
+        let mut options = basic_index_options("", true, None, None, None);
+        options.name = None;
+
+        assert_eq!(index_name(&IndexModel{keys: doc!{"status": 1, "created_at": -1}, options}), "status_1_created_at_-1".to_string());
+    }
+
+    #[test]
+    fn document_path_follows_dotted_paths() {
+        let doc = doc!{"country": "DE", "address": {"city": "Berlin"}};
+
+        assert_eq!(document_path(&doc, "country"), Some(&Bson::from("DE")));
+        assert_eq!(document_path(&doc, "address.city"), Some(&Bson::from("Berlin")));
+        assert_eq!(document_path(&doc, "country.city"), None);
+        assert_eq!(document_path(&doc, "missing"), None);
+    }
+
+    #[test]
+    fn shard_key_index_supports_shard_key() {
+        let output = shard_key_index(&doc!{"tenant_id": 1, "created_at": 1});
+
+        assert_eq!(output.keys, doc!{"tenant_id": 1, "created_at": 1});
+        assert!(output.options.name == Some(SHARD_KEY_INDEX.to_string()));
+    }
+
+    #[test]
+    fn retry_on_write_conflict_does_not_retry_other_errors() {
+        let mut calls = 0;
+
+        let res: Result<()> = retry_on_write_conflict(3, || {
+            calls += 1;
+            Err(ArgumentError("not a conflict".to_owned()).into())
+        });
+
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn retry_on_write_conflict_retries_up_to_bound() {
+        let mut calls = 0;
+
+        let res: Result<()> = retry_on_write_conflict(2, || {
+            calls += 1;
+            Err(OperationError("WriteConflict".to_owned()).into())
+        });
+
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn chunk_documents_respects_chunk_size() {
+        let docs = (0..5).map(|idx| doc!{"idx": idx}).collect();
+
+        let chunks = chunk_documents(docs, 2).expect("Expected documents to be chunked.");
+
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(chunks[2][0], doc!{"idx": 4});
+    }
+
+    #[test]
+    fn chunk_documents_rejects_zero_chunk_size() {
+        assert!(chunk_documents(vec![doc!{}], 0).is_err());
+    }
+
+    #[cfg(feature = "slow-query-log")]
+    #[test]
+    fn query_shape_elides_all_values() {
+        let output = query_shape(&doc!{"email": "test@test.com", "age": {"$gt": 21}, "$or": [{"a": 1}]});
+
+        assert_eq!(output, doc!{"email": "?", "age": {"$gt": "?"}, "$or": [{"a": "?"}]});
+    }
+
+    #[cfg(feature = "slow-query-log")]
+    #[test]
+    fn plan_uses_index_finds_nested_index_scans() {
+        let indexed = doc!{"stage": "FETCH", "inputStage": {"stage": "IXSCAN"}};
+        let scanned = doc!{"stage": "COLLSCAN"};
+
+        assert!(plan_uses_index(&indexed));
+        assert!(!plan_uses_index(&scanned));
+    }
 }
@@ -0,0 +1,64 @@
+//! Accepting new model instances from untrusted input, without an `_id`.
+//!
+//! Create endpoints should not allow clients to choose the ID of the document they create.
+//! Rather than maintaining a separate create struct mirroring the model minus its `id`, accept a
+//! [`NewModel`](./struct.NewModel.html) of the model, which discards any `_id` given in the input
+//! before deserializing the model itself:
+//!
+//! ```rust,ignore
+//! let new_user: NewModel<User> = serde_json::from_str(&body)?;
+//! let mut user = new_user.into_model(); // `user.id` is always `None`.
+//! user.save(db.clone(), None)?;
+//! ```
+//!
+//! The model's ID field must be optional & serialized as `_id` — as is conventional, via
+//! `#[serde(rename = "_id")]` — so that it deserializes as `None` once `_id` is discarded.
+
+use bson::{self, Bson, Document};
+use serde::{Deserialize, Deserializer};
+use serde::de::{DeserializeOwned, Error};
+
+/// A new instance of the model `M`, deserialized from input having any `_id` discarded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewModel<M> {
+    model: M,
+}
+
+impl<M> NewModel<M> {
+    /// Consume this value, returning the model instance, which has no ID.
+    pub fn into_model(self) -> M {
+        self.model
+    }
+}
+
+impl<'de, M: DeserializeOwned> Deserialize<'de> for NewModel<M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<NewModel<M>, D::Error> {
+        let mut document = Document::deserialize(deserializer)?;
+        document.remove("_id");
+        let model = bson::from_bson(Bson::Document(document)).map_err(D::Error::custom)?;
+        Ok(NewModel{model})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::oid::ObjectId;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Account {
+        #[serde(rename = "_id")]
+        id: Option<ObjectId>,
+        email: String,
+    }
+
+    #[test]
+    fn new_model_discards_given_id() {
+        let id = ObjectId::new().expect("Expected a new ObjectId.");
+        let input = doc!{"_id": id, "email": "test@test.com"};
+
+        let output: NewModel<Account> = bson::from_bson(Bson::Document(input)).expect("Expected deserialization.");
+
+        assert_eq!(output.into_model(), Account{id: None, email: "test@test.com".to_string()});
+    }
+}
@@ -0,0 +1,141 @@
+//! Serializing `ObjectId`s as plain hex strings, for use with serde's `with` attribute.
+//!
+//! By default an `ObjectId` serializes to JSON as `{"$oid": "..."}`, which most API consumers do
+//! not want. Fields using this module serialize as a plain hex string instead:
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize)]
+//! pub struct UserResponse {
+//!     #[serde(rename = "id", with = "wither::oid_as_string")]
+//!     pub id: ObjectId,
+//!
+//!     // Use the `option` submodule for optional IDs.
+//!     #[serde(with = "wither::oid_as_string::option")]
+//!     pub referrer_id: Option<ObjectId>,
+//! }
+//! ```
+//!
+//! Deserialization accepts a hex string, a `{"$oid": "..."}` map & a native BSON `ObjectId`, so
+//! API input in either form is understood.
+//!
+//! **NOTE:** serde gives no means of telling the BSON encoder apart from a JSON serializer, so a
+//! field using this module is also *stored* as a string. Keep model fields — `_id` especially —
+//! as plain `ObjectId`s, & use this module on the types built from models for API responses.
+
+use std::fmt;
+
+use bson::oid::ObjectId;
+use serde::{Deserializer, Serializer};
+use serde::de::{self, MapAccess, Visitor};
+
+/// Serialize the given `ObjectId` as its hex string.
+pub fn serialize<S: Serializer>(oid: &ObjectId, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&oid.to_hex())
+}
+
+/// Deserialize an `ObjectId` from its hex string, a `{"$oid": "..."}` map or a BSON `ObjectId`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ObjectId, D::Error> {
+    deserializer.deserialize_any(ObjectIdVisitor)
+}
+
+struct ObjectIdVisitor;
+
+impl<'de> Visitor<'de> for ObjectIdVisitor {
+    type Value = ObjectId;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an ObjectId or its hex string")
+    }
+
+    fn visit_str<E: de::Error>(self, hex: &str) -> Result<ObjectId, E> {
+        ObjectId::with_string(hex).map_err(|_| E::custom(format!("invalid ObjectId hex string '{}'", hex)))
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<ObjectId, M::Error> {
+        let hex: String = match map.next_key::<String>()? {
+            Some(ref key) if key == "$oid" => map.next_value()?,
+            _ => return Err(de::Error::custom("expected an `$oid` key")),
+        };
+        self.visit_str(&hex)
+    }
+}
+
+/// Serializing `Option<ObjectId>`s as optional hex strings.
+pub mod option {
+    use std::fmt;
+
+    use bson::oid::ObjectId;
+    use serde::{Deserializer, Serializer};
+    use serde::de::{self, Visitor};
+
+    /// Serialize the given optional `ObjectId` as its hex string, or as none.
+    pub fn serialize<S: Serializer>(oid: &Option<ObjectId>, serializer: S) -> Result<S::Ok, S::Error> {
+        match oid {
+            &Some(ref oid) => serializer.serialize_some(&oid.to_hex()),
+            &None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserialize an optional `ObjectId` in any of the forms accepted by the parent module.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ObjectId>, D::Error> {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<ObjectId>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an optional ObjectId or its hex string")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Option<ObjectId>, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Option<ObjectId>, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<ObjectId>, D::Error> {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson;
+    use bson::Bson;
+    use bson::oid::ObjectId;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Response {
+        #[serde(with = "super")]
+        id: ObjectId,
+
+        #[serde(with = "super::option")]
+        referrer_id: Option<ObjectId>,
+    }
+
+    fn oid() -> ObjectId {
+        ObjectId::with_string("5a1d7ddf8d1b43e5a1b2c3d4").expect("Expected a valid ObjectId.")
+    }
+
+    #[test]
+    fn oid_as_string_serializes_as_hex_string() {
+        let output = bson::to_bson(&Response{id: oid(), referrer_id: None}).expect("Expected serialization.");
+
+        assert_eq!(output, Bson::Document(doc!{"id": "5a1d7ddf8d1b43e5a1b2c3d4", "referrer_id": Bson::Null}));
+    }
+
+    #[test]
+    fn oid_as_string_deserializes_hex_strings_and_object_ids() {
+        let input = doc!{"id": "5a1d7ddf8d1b43e5a1b2c3d4", "referrer_id": oid()};
+
+        let output: Response = bson::from_bson(Bson::Document(input)).expect("Expected deserialization.");
+
+        assert_eq!(output, Response{id: oid(), referrer_id: Some(oid())});
+    }
+}
@@ -0,0 +1,163 @@
+//! Helpers for building aggregation pipelines.
+//!
+//! A [`Pipeline`](./struct.Pipeline.html) builds the `Vec<Document>` expected by MongoDB's
+//! `aggregate` command, one stage at a time. Stages which the builder does not cover may be added
+//! as raw documents via [`Pipeline::stage`](./struct.Pipeline.html#method.stage), and raw
+//! `Vec<Document>` pipelines are accepted everywhere a `Pipeline` is.
+//!
+//! ```rust,ignore
+//! let pipeline = Pipeline::new()
+//!     .match_(doc!{"active": true})
+//!     .group(Bson::from("$country"), doc!{"count": {"$sum": 1}})
+//!     .sort(doc!{"count": -1})
+//!     .limit(10);
+//! let cursor = User::aggregate(db.clone(), pipeline, None)?;
+//! ```
+
+use bson::{Bson, Document};
+
+/// A builder for aggregation pipelines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    stages: Vec<Document>,
+}
+
+impl Pipeline {
+    /// Create a new empty pipeline.
+    pub fn new() -> Pipeline {
+        Pipeline{stages: vec![]}
+    }
+
+    /// Append a `$match` stage, filtering documents by the given filter.
+    pub fn match_<F: Into<Document>>(self, filter: F) -> Pipeline {
+        self.stage(doc!{"$match": filter.into()})
+    }
+
+    /// Append a `$group` stage, grouping documents by the given `_id` expression & computing the
+    /// given accumulator fields for each group.
+    pub fn group(self, id: Bson, accumulators: Document) -> Pipeline {
+        let mut group = doc!{"_id": id};
+        for (key, val) in accumulators {
+            group.insert_bson(key, val);
+        }
+        self.stage(doc!{"$group": group})
+    }
+
+    /// Append a `$sort` stage.
+    pub fn sort(self, sort: Document) -> Pipeline {
+        self.stage(doc!{"$sort": sort})
+    }
+
+    /// Append a `$skip` stage.
+    pub fn skip(self, skip: i64) -> Pipeline {
+        self.stage(doc!{"$skip": skip})
+    }
+
+    /// Append a `$limit` stage.
+    pub fn limit(self, limit: i64) -> Pipeline {
+        self.stage(doc!{"$limit": limit})
+    }
+
+    /// Append a `$project` stage.
+    pub fn project(self, projection: Document) -> Pipeline {
+        self.stage(doc!{"$project": projection})
+    }
+
+    /// Append a `$lookup` stage, joining documents of the collection `from` where `foreign_field`
+    /// equals this pipeline's `local_field`, as an array stored under `as_field`.
+    pub fn lookup(self, from: &str, local_field: &str, foreign_field: &str, as_field: &str) -> Pipeline {
+        self.stage(doc!{"$lookup": {
+            "from": from,
+            "localField": local_field,
+            "foreignField": foreign_field,
+            "as": as_field,
+        }})
+    }
+
+    /// Append a raw stage document, for stages not covered by this builder.
+    pub fn stage(mut self, stage: Document) -> Pipeline {
+        self.stages.push(stage);
+        self
+    }
+
+    /// Consume this pipeline, returning its stage documents.
+    pub fn into_stages(self) -> Vec<Document> {
+        self.stages
+    }
+}
+
+/// The stage used to write the output of an aggregation into another collection.
+///
+/// See [`Model::aggregate_into_model`](../model/trait.Model.html#method.aggregate_into_model).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputStage {
+    /// `$out`, atomically replacing the target collection with the output. The target must be in
+    /// the same database.
+    Out,
+
+    /// `$merge`, merging each output document into the target collection by `_id`, inserting
+    /// those which do not match. Requires MongoDB 4.2+.
+    Merge,
+}
+
+impl OutputStage {
+    /// The stage document writing into the given collection of the given database.
+    pub(crate) fn to_stage(&self, db: &str, coll: &str) -> Document {
+        match self {
+            &OutputStage::Out => doc!{"$out": coll},
+            &OutputStage::Merge => doc!{"$merge": {"into": {"db": db, "coll": coll}}},
+        }
+    }
+}
+
+impl From<Vec<Document>> for Pipeline {
+    fn from(stages: Vec<Document>) -> Pipeline {
+        Pipeline{stages}
+    }
+}
+
+impl From<Pipeline> for Vec<Document> {
+    fn from(pipeline: Pipeline) -> Vec<Document> {
+        pipeline.stages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_builds_expected_stages_in_order() {
+        let stages: Vec<Document> = Pipeline::new()
+            .match_(doc!{"active": true})
+            .group(Bson::from("$country"), doc!{"count": {"$sum": 1}})
+            .sort(doc!{"count": -1})
+            .limit(10)
+            .into();
+
+        assert_eq!(stages, vec![
+            doc!{"$match": {"active": true}},
+            doc!{"$group": {"_id": "$country", "count": {"$sum": 1}}},
+            doc!{"$sort": {"count": -1}},
+            doc!{"$limit": 10i64},
+        ]);
+    }
+
+    #[test]
+    fn pipeline_builds_lookup_stage() {
+        let stages = Pipeline::new().lookup("orders", "_id", "user_id", "orders").into_stages();
+
+        assert_eq!(stages, vec![doc!{"$lookup": {
+            "from": "orders",
+            "localField": "_id",
+            "foreignField": "user_id",
+            "as": "orders",
+        }}]);
+    }
+
+    #[test]
+    fn output_stage_targets_given_collection() {
+        assert_eq!(OutputStage::Out.to_stage("db", "rollups"), doc!{"$out": "rollups"});
+        assert_eq!(OutputStage::Merge.to_stage("db", "rollups"), doc!{"$merge": {"into": {"db": "db", "coll": "rollups"}}});
+    }
+}
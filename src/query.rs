@@ -0,0 +1,327 @@
+//! Helpers for building query documents.
+//!
+//! A [`Filter`](./struct.Filter.html) is a thin layer over a bson `Document`, and may be used
+//! anywhere a filter document is accepted by converting it with `Document::from` or `.into()`.
+//!
+//! ```rust,ignore
+//! let filter = Filter::starts_with("email", user_input).and(Filter::eq("active", true));
+//! let users = User::find(db.clone(), Some(filter.into()), None)?;
+//! ```
+//!
+//! A [`Sort`](./struct.Sort.html) builds sort documents in the same way, keeping its fields in
+//! the order they are given.
+
+use bson::{Bson, Document};
+
+/// A builder for filter documents.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    doc: Document,
+}
+
+impl Filter {
+    /// Create a new empty filter, which will match all documents.
+    pub fn new() -> Filter {
+        Filter{doc: Document::new()}
+    }
+
+    /// Create a filter matching documents where `field` equals the given value.
+    pub fn eq<V: Into<Bson>>(field: &str, value: V) -> Filter {
+        let mut doc = Document::new();
+        doc.insert(field, value.into());
+        Filter{doc}
+    }
+
+    /// Create a filter matching documents where the string `field` starts with the given prefix.
+    ///
+    /// Any regex metacharacters in `prefix` are escaped, so it is safe to use with user input.
+    /// The generated `$regex` is anchored, which allows MongoDB to use an index on `field`.
+    pub fn starts_with(field: &str, prefix: &str) -> Filter {
+        Filter::regex(field, format!("^{}", escape_regex(prefix)))
+    }
+
+    /// Create a filter matching documents where the string `field` contains the given substring.
+    ///
+    /// Any regex metacharacters in `substring` are escaped, so it is safe to use with user input.
+    /// As the generated `$regex` is unanchored, MongoDB can not use an index efficiently for this
+    /// query. Prefer `Filter::starts_with` where possible.
+    pub fn contains(field: &str, substring: &str) -> Filter {
+        Filter::regex(field, escape_regex(substring))
+    }
+
+    /// Create a filter matching documents where at least one element of the array `field`
+    /// matches all conditions of the `inner` filter.
+    ///
+    /// Separate conditions on an array of subdocuments — such as `{"items.qty": {"$gt": 5}}` &
+    /// `{"items.sku": "abc"}` — may each be satisfied by a different element. Wrapping them in
+    /// an `$elemMatch` ensures they all apply to the same element.
+    pub fn elem_match(field: &str, inner: Filter) -> Filter {
+        let mut doc = Document::new();
+        doc.insert(field, doc!{"$elemMatch": inner.doc});
+        Filter{doc}
+    }
+
+    /// Combine this filter with another. Both filters must match.
+    ///
+    /// Conditions of `other` on a field already constrained by this filter are combined with
+    /// `$and`, so that neither condition is overwritten.
+    pub fn and(mut self, other: Filter) -> Filter {
+        let mut conflicts = vec![];
+        for (key, val) in other.doc {
+            if self.doc.contains_key(&key) {
+                let mut conflict = Document::new();
+                conflict.insert_bson(key, val);
+                conflicts.push(Bson::Document(conflict));
+            } else {
+                self.doc.insert_bson(key, val);
+            }
+        }
+        if !conflicts.is_empty() {
+            let mut clauses = match self.doc.remove("$and") {
+                Some(Bson::Array(clauses)) => clauses,
+                Some(clause) => vec![clause],
+                None => vec![],
+            };
+            clauses.extend(conflicts);
+            self.doc.insert("$and", clauses);
+        }
+        self
+    }
+
+    /// Consume this filter, returning the underlying filter document.
+    pub fn into_document(self) -> Document {
+        self.doc
+    }
+
+    fn regex(field: &str, pattern: String) -> Filter {
+        let mut doc = Document::new();
+        doc.insert(field, doc!{"$regex": pattern});
+        Filter{doc}
+    }
+}
+
+impl From<Filter> for Document {
+    fn from(filter: Filter) -> Document {
+        filter.doc
+    }
+}
+
+/// A builder for sort documents.
+///
+/// Documents are ordered by each field in turn, so later fields only break ties of earlier ones:
+///
+/// ```rust,ignore
+/// let sort = Sort::new().text_score("score").desc("created_at");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sort {
+    doc: Document,
+}
+
+impl Sort {
+    /// Create a new empty sort, which leaves documents in their natural order.
+    pub fn new() -> Sort {
+        Sort{doc: Document::new()}
+    }
+
+    /// Sort by `field` in ascending order.
+    pub fn asc(self, field: &str) -> Sort {
+        self.field(field, Bson::I32(1))
+    }
+
+    /// Sort by `field` in descending order.
+    pub fn desc(self, field: &str) -> Sort {
+        self.field(field, Bson::I32(-1))
+    }
+
+    /// Sort by text score, most relevant first, for use with a `$text` query.
+    ///
+    /// `field` names the projected score, which the server requires to also be projected as
+    /// `{field: {"$meta": "textScore"}}`, such as under
+    /// [`TEXT_SCORE_FIELD`](../model/constant.TEXT_SCORE_FIELD.html).
+    pub fn text_score(self, field: &str) -> Sort {
+        self.field(field, Bson::Document(doc!{"$meta": "textScore"}))
+    }
+
+    /// Consume this sort, returning the underlying sort document.
+    pub fn into_document(self) -> Document {
+        self.doc
+    }
+
+    /// Sort by `field` as given. A field already sorted by keeps its position, but takes the
+    /// given order.
+    fn field(mut self, field: &str, order: Bson) -> Sort {
+        self.doc.insert(field, order);
+        self
+    }
+}
+
+impl From<Sort> for Document {
+    fn from(sort: Sort) -> Document {
+        sort.doc
+    }
+}
+
+/// Escape all regex metacharacters in the given string so that it matches literally.
+pub fn escape_regex(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for ch in input.chars() {
+        match ch {
+            '\\' | '^' | '$' | '.' | '|' | '?' | '*' | '+' | '(' | ')' | '[' | ']' | '{' | '}' | '-' | '/' => {
+                escaped.push('\\');
+            },
+            _ => (),
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Implement `From<$type> for Bson` using the type's serde serialization.
+///
+/// This is intended for enums stored as strings, so that query values stay in sync with stored
+/// values: `doc!{"status": UserStatus::Active}` will produce exactly what serde writes when the
+/// model is saved. Invoke this once per enum alongside its serde derives.
+///
+/// ```rust,ignore
+/// #[derive(Serialize, Deserialize, Clone, Copy)]
+/// pub enum UserStatus { Active, Disabled }
+///
+/// bson_value!(UserStatus);
+///
+/// let users = User::find(db.clone(), Some(doc!{"status": UserStatus::Active}), None)?;
+/// ```
+///
+/// Panics if the value can not be serialized to BSON, which would also prevent the model from
+/// being saved.
+#[macro_export]
+macro_rules! bson_value {
+    ($type:ty) => {
+        impl From<$type> for $crate::bson::Bson {
+            fn from(value: $type) -> $crate::bson::Bson {
+                $crate::bson::to_bson(&value).expect("Expected value to be serializable to BSON.")
+            }
+        }
+    };
+}
+
+/// Build a `Filter` of comparisons on fields of the given model, which the compiler checks
+/// against the model's struct.
+///
+/// Each predicate compares a field of the model with a value, via `==`, `!=`, `>`, `>=`, `<` or
+/// `<=`, which become `$eq`, `$ne`, `$gt`, `$gte`, `$lt` & `$lte` respectively. Predicates are
+/// combined as by `Filter::and`, so all must match. A misspelled field, or a value which can not
+/// be compared with the field's type — such as a string for an `i32` field — fails to compile:
+///
+/// ```rust,ignore
+/// let filter = model_query!(User where email == input_email, age >= 18, active == true);
+/// let users = User::find(db.clone(), Some(filter.into()), None)?;
+/// ```
+///
+/// The check compiles a closure, which is never called, comparing the field of an instance with
+/// the value. Each field is queried by its Rust name, so use `Filter` directly for fields renamed
+/// by serde, such as `id` stored as `_id`. Values are converted via `Bson::from`, & each is
+/// evaluated once.
+#[macro_export]
+macro_rules! model_query {
+    (@op ==) => { "$eq" };
+    (@op !=) => { "$ne" };
+    (@op >) => { "$gt" };
+    (@op >=) => { "$gte" };
+    (@op <) => { "$lt" };
+    (@op <=) => { "$lte" };
+    ($model:ty where $($field:ident $op:tt $value:expr),+ $(,)*) => {
+        $crate::query::Filter::new()
+            $(.and({
+                let _ = |model: &$model| model.$field $op $value;
+                let mut condition = $crate::bson::Document::new();
+                condition.insert(model_query!(@op $op), $crate::bson::Bson::from($value));
+                $crate::query::Filter::eq(stringify!($field), condition)
+            }))+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_regex_escapes_metacharacters() {
+        assert_eq!(escape_regex("a.b*(c)"), "a\\.b\\*\\(c\\)");
+        assert_eq!(escape_regex("plain"), "plain");
+    }
+
+    #[test]
+    fn starts_with_produces_anchored_escaped_regex() {
+        let filter: Document = Filter::starts_with("email", "a+b").into();
+
+        assert_eq!(filter, doc!{"email": doc!{"$regex": "^a\\+b"}});
+    }
+
+    #[test]
+    fn contains_produces_unanchored_escaped_regex() {
+        let filter: Document = Filter::contains("name", "(x)").into();
+
+        assert_eq!(filter, doc!{"name": doc!{"$regex": "\\(x\\)"}});
+    }
+
+    #[test]
+    fn elem_match_applies_all_inner_conditions_to_one_element() {
+        let inner = Filter::eq("sku", "abc").and(Filter::eq("qty", doc!{"$gt": 5}));
+
+        let filter: Document = Filter::elem_match("items", inner).into();
+
+        assert_eq!(filter, doc!{"items": doc!{"$elemMatch": doc!{"sku": "abc", "qty": doc!{"$gt": 5}}}});
+    }
+
+    #[test]
+    fn and_combines_conditions_on_the_same_field() {
+        let filter: Document = Filter::starts_with("name", "a").and(Filter::contains("name", "b")).into();
+
+        assert_eq!(filter, doc!{
+            "name": doc!{"$regex": "^a"},
+            "$and": [doc!{"name": doc!{"$regex": "b"}}],
+        });
+    }
+
+    #[test]
+    fn sort_combines_text_score_with_secondary_fields_in_order() {
+        let sort: Document = Sort::new().text_score("score").desc("created_at").asc("_id").into();
+
+        assert_eq!(sort, doc!{"score": doc!{"$meta": "textScore"}, "created_at": -1, "_id": 1});
+        assert_eq!(sort.keys().collect::<Vec<_>>(), vec!["score", "created_at", "_id"]);
+    }
+
+    struct Person {
+        name: String,
+        age: i32,
+    }
+
+    #[test]
+    fn model_query_builds_checked_comparisons() {
+        let person = Person{name: "a".to_owned(), age: 42};
+
+        let filter: Document = model_query!(Person where name == person.name, age >= 18, age < 65).into();
+
+        assert_eq!(filter, doc!{
+            "name": doc!{"$eq": "a"},
+            "age": doc!{"$gte": 18},
+            "$and": [doc!{"age": doc!{"$lt": 65}}],
+        });
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy)]
+    enum Status {
+        Active,
+        #[serde(rename = "gone")]
+        Disabled,
+    }
+
+    bson_value!(Status);
+
+    #[test]
+    fn bson_value_matches_serde_serialization() {
+        assert_eq!(doc!{"status": Status::Active}, doc!{"status": "Active"});
+        assert_eq!(Filter::eq("status", Status::Disabled), Filter::eq("status", "gone"));
+    }
+}
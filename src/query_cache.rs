@@ -0,0 +1,103 @@
+//! An in-process cache of query results, backing [`Model::find_cached`](../model/trait.Model.html#method.find_cached).
+//!
+//! Entries are keyed by the namespace of the queried collection together with a hash of the
+//! query, & hold the deserialized instances until their TTL elapses. Every write made through a
+//! `Model` — including the migrations & seeds applied by `Model::sync` — invalidates all entries
+//! of the written collection once the write has been made, but writes made by other processes,
+//! or directly through the driver, are not seen, & a query racing with a write may still cache
+//! the result from before the write: **a cached result may be stale for up to its TTL**. Call
+//! [`invalidate`](./fn.invalidate.html) after writing through the driver.
+//!
+//! The cache holds at most [`CAPACITY`](./constant.CAPACITY.html) entries across all models.
+//! When full, expired entries are evicted, & then the entry closest to expiring.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::result;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The maximum number of entries held by the cache.
+pub const CAPACITY: usize = 1024;
+
+/// A cached query result.
+struct Entry {
+    value: Box<Any + Send>,
+    expires_at: Instant,
+}
+
+/// The key of a cached query result: its namespace, the type it was cached as, & its query hash.
+type Key = (String, TypeId, u64);
+
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<Key, Entry>> = Mutex::new(HashMap::new());
+}
+
+/// Get a clone of the unexpired value cached under the given namespace & query hash, if any.
+pub(crate) fn get<T: Any + Clone>(namespace: &str, hash: u64) -> Option<T> {
+    let cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    let key = (namespace.to_owned(), TypeId::of::<T>(), hash);
+    cache.get(&key)
+        .filter(|entry| entry.expires_at > Instant::now())
+        .and_then(|entry| entry.value.downcast_ref::<T>())
+        .cloned()
+}
+
+/// Cache the given value under the given namespace & query hash for the given TTL.
+pub(crate) fn put<T: Any + Send>(namespace: &str, hash: u64, value: T, ttl: Duration) {
+    let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    let now = Instant::now();
+    if cache.len() >= CAPACITY {
+        cache.retain(|_, entry| entry.expires_at > now);
+    }
+    if cache.len() >= CAPACITY {
+        let soonest = cache.iter().min_by_key(|&(_, entry)| entry.expires_at).map(|(key, _)| key.clone());
+        if let Some(key) = soonest {
+            cache.remove(&key);
+        }
+    }
+    let key = (namespace.to_owned(), TypeId::of::<T>(), hash);
+    cache.insert(key, Entry{value: Box::new(value), expires_at: now + ttl});
+}
+
+/// Invalidate all cached results of the collection having the given namespace, e.g. `db.users`.
+pub fn invalidate(namespace: &str) {
+    let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    cache.retain(|key, _| key.0 != namespace);
+}
+
+/// Make the given write to the collection having the given namespace, then invalidate all of
+/// its cached results, whether or not the write succeeded.
+///
+/// Invalidating before the write would let a query made during the write cache the result from
+/// before it for the whole TTL.
+pub(crate) fn invalidate_after<T, E, F: FnOnce() -> result::Result<T, E>>(namespace: &str, write: F) -> result::Result<T, E> {
+    let res = write();
+    invalidate(namespace);
+    res
+}
+
+/// Invalidate all cached results.
+pub fn clear() {
+    let mut cache = CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    cache.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_values_are_returned_until_invalidated() {
+        put("query_cache_test.users", 1, vec![1, 2, 3], Duration::from_secs(60));
+        put("query_cache_test.users", 2, vec![4], Duration::from_secs(0));
+
+        assert_eq!(get::<Vec<i32>>("query_cache_test.users", 1), Some(vec![1, 2, 3]));
+        assert_eq!(get::<Vec<i32>>("query_cache_test.users", 2), None);
+        assert_eq!(get::<Vec<u8>>("query_cache_test.users", 1), None);
+
+        invalidate("query_cache_test.users");
+
+        assert_eq!(get::<Vec<i32>>("query_cache_test.users", 1), None);
+    }
+}
@@ -0,0 +1,65 @@
+//! A registry of named clients, for routing model groups to separate connection pools.
+//!
+//! Each `Client` holds its own connection pool, so models with different connection needs —
+//! heavy analytics reads versus latency sensitive transactional writes, say — may be kept from
+//! starving one another by giving each group its own client. Register each client under a key
+//! once at boottime, then return that key from the [`client_key`](../model/trait.Model.html#method.client_key)
+//! of each model in the group:
+//!
+//! ```rust,ignore
+//! let analytics = Client::with_uri_and_options(&uri, analytics_options)?;
+//! wither::registry::register_client("analytics", analytics);
+//!
+//! impl<'a> Model<'a> for PageView {
+//!     fn client_key() -> Option<&'static str> {
+//!         Some("analytics")
+//!     }
+//!     // snip ...
+//! }
+//!
+//! // Routed to the `analytics` client's database of the same name.
+//! let views = PageView::find(db.clone(), None, None)?;
+//! ```
+//!
+//! Models are still passed a `Database` as usual; a routed model uses the database of the same
+//! name on its registered client instead. Models without a client key use the given database.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use mongodb::{Client, ThreadedClient};
+use mongodb::db::Database;
+
+lazy_static! {
+    static ref CLIENTS: RwLock<HashMap<String, Client>> = RwLock::new(HashMap::new());
+}
+
+/// Register the given client under the given key, replacing any client already registered.
+pub fn register_client(key: &str, client: Client) {
+    let mut clients = CLIENTS.write().unwrap_or_else(|err| err.into_inner());
+    clients.insert(key.to_owned(), client);
+}
+
+/// Get the client registered under the given key, if any.
+pub fn registered_client(key: &str) -> Option<Client> {
+    let clients = CLIENTS.read().unwrap_or_else(|err| err.into_inner());
+    clients.get(key).cloned()
+}
+
+/// Route the given database to the client registered under the given key, if any.
+///
+/// A key having no registered client is a misconfiguration, so a warning is logged & the given
+/// database is used.
+pub(crate) fn route_database(db: Database, key: Option<&str>) -> Database {
+    let key = match key {
+        Some(key) => key,
+        None => return db,
+    };
+    match registered_client(key) {
+        Some(client) => client.db(&db.name),
+        None => {
+            warn!("No client registered under '{}', using the given database '{}'.", key, db.name);
+            db
+        },
+    }
+}
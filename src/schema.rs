@@ -0,0 +1,200 @@
+//! Generating `$jsonSchema` validators from Rust types.
+//!
+//! A [`JsonSchema`](./struct.JsonSchema.html) builds a validator for use as a model's
+//! [`Model::validator`](../model/trait.Model.html#method.validator), one field at a time, with the
+//! `bsonType` of each field & whether it is required derived from the field's Rust type. This
+//! keeps the server side schema in step with the Rust model.
+//!
+//! ```rust,ignore
+//! fn validator() -> Option<Document> {
+//!     Some(JsonSchema::new()
+//!         .field::<Option<ObjectId>>("_id")
+//!         .field::<String>("email")
+//!         .field::<Option<i32>>("age")
+//!         .field::<Vec<String>>("tags")
+//!         .into_validator())
+//! }
+//! ```
+//!
+//! ### type mapping
+//! Rust types are mapped onto BSON types via the [`BsonSchema`](./trait.BsonSchema.html) trait.
+//!
+//! | Rust type | BSON type |
+//! |-----------|-----------|
+//! | `String` | `string` |
+//! | `bool` | `bool` |
+//! | `i32` | `int` |
+//! | `i64` | `long` |
+//! | `f64` | `double` |
+//! | `ObjectId` | `objectId` |
+//! | `UtcDateTime` | `date` |
+//! | `DateTime<Utc>` | `string`, as serialized by chrono |
+//! | `Document` | `object` |
+//! | `Vec<T>` | `array`, having items of `T` |
+//! | `Option<T>` | `T` or `null`, not required |
+//!
+//! Embedded types may implement `BsonSchema` themselves, typically via
+//! [`JsonSchema::into_json_schema`](./struct.JsonSchema.html#method.into_json_schema).
+//!
+//! ### limitations
+//! The schema describes the Rust type, not its serde representation. Fields using custom serde
+//! representations — such as `#[serde(with = "...")]`, `flatten`, or enums — must be described
+//! with the BSON type they actually serialize to, via
+//! [`JsonSchema::field_schema`](./struct.JsonSchema.html#method.field_schema). Renamed fields
+//! must be declared by their serialized name. Fields which are skipped when `None` are not
+//! required, which `Option<T>` already accounts for.
+
+use bson::{Bson, Document};
+use bson::oid::ObjectId;
+use bson::UtcDateTime;
+use chrono::{DateTime, Utc};
+
+/// A trait for types having a known `$jsonSchema` representation.
+pub trait BsonSchema {
+    /// The `$jsonSchema` of this type.
+    fn bson_schema() -> Document;
+
+    /// Whether a field of this type must be present.
+    fn required() -> bool {
+        true
+    }
+}
+
+macro_rules! bson_schema_type {
+    ($ty:ty, $bson_type:expr) => {
+        impl BsonSchema for $ty {
+            fn bson_schema() -> Document {
+                doc!{"bsonType": $bson_type}
+            }
+        }
+    };
+}
+
+bson_schema_type!(String, "string");
+bson_schema_type!(bool, "bool");
+bson_schema_type!(i32, "int");
+bson_schema_type!(i64, "long");
+bson_schema_type!(f64, "double");
+bson_schema_type!(ObjectId, "objectId");
+bson_schema_type!(UtcDateTime, "date");
+bson_schema_type!(DateTime<Utc>, "string");
+bson_schema_type!(Document, "object");
+
+impl<T: BsonSchema> BsonSchema for Vec<T> {
+    fn bson_schema() -> Document {
+        doc!{"bsonType": "array", "items": T::bson_schema()}
+    }
+}
+
+impl<T: BsonSchema> BsonSchema for Option<T> {
+    fn bson_schema() -> Document {
+        let mut schema = T::bson_schema();
+        let nullable = match schema.get("bsonType") {
+            Some(&Bson::String(ref bson_type)) => Some(Bson::Array(vec![Bson::from(bson_type.clone()), Bson::from("null")])),
+            _ => None,
+        };
+        if let Some(nullable) = nullable {
+            schema.insert("bsonType", nullable);
+        }
+        schema
+    }
+
+    fn required() -> bool {
+        false
+    }
+}
+
+/// A builder for `$jsonSchema` validators.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonSchema {
+    properties: Document,
+    required: Vec<String>,
+}
+
+impl JsonSchema {
+    /// Create a new schema having no fields.
+    pub fn new() -> JsonSchema {
+        JsonSchema{properties: Document::new(), required: vec![]}
+    }
+
+    /// Declare a field having the schema of the Rust type `T`.
+    pub fn field<T: BsonSchema>(self, name: &str) -> JsonSchema {
+        self.field_schema(name, T::bson_schema(), T::required())
+    }
+
+    /// Declare a field having the given raw schema, for types having a custom serde
+    /// representation.
+    pub fn field_schema(mut self, name: &str, schema: Document, required: bool) -> JsonSchema {
+        self.properties.insert(name, schema);
+        if required {
+            self.required.push(name.to_owned());
+        }
+        self
+    }
+
+    /// Consume this builder, returning the schema of an object having the declared fields.
+    pub fn into_json_schema(self) -> Document {
+        let mut schema = doc!{"bsonType": "object"};
+        if !self.required.is_empty() {
+            let required: Vec<Bson> = self.required.into_iter().map(Bson::from).collect();
+            schema.insert("required", required);
+        }
+        schema.insert("properties", self.properties);
+        schema
+    }
+
+    /// Consume this builder, returning a `$jsonSchema` validator document.
+    pub fn into_validator(self) -> Document {
+        doc!{"$jsonSchema": self.into_json_schema()}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_schema_maps_field_types_and_required_fields() {
+        let output = JsonSchema::new()
+            .field::<Option<ObjectId>>("_id")
+            .field::<String>("email")
+            .field::<Vec<i64>>("scores")
+            .into_validator();
+
+        assert_eq!(output, doc!{"$jsonSchema": {
+            "bsonType": "object",
+            "required": ["email", "scores"],
+            "properties": {
+                "_id": {"bsonType": ["objectId", "null"]},
+                "email": {"bsonType": "string"},
+                "scores": {"bsonType": "array", "items": {"bsonType": "long"}},
+            },
+        }});
+    }
+
+    #[test]
+    fn json_schema_maps_datetimes_by_their_serialized_type() {
+        let output = JsonSchema::new()
+            .field::<Option<UtcDateTime>>("deleted_at")
+            .field::<DateTime<Utc>>("published")
+            .into_json_schema();
+
+        assert_eq!(output.get_document("properties").unwrap(), &doc!{
+            "deleted_at": {"bsonType": ["date", "null"]},
+            "published": {"bsonType": "string"},
+        });
+    }
+
+    #[test]
+    fn json_schema_accepts_raw_field_schemas() {
+        let output = JsonSchema::new()
+            .field_schema("status", doc!{"bsonType": "int", "enum": [1, 2]}, true)
+            .into_json_schema();
+
+        assert_eq!(output, doc!{
+            "bsonType": "object",
+            "required": ["status"],
+            "properties": {"status": {"bsonType": "int", "enum": [1, 2]}},
+        });
+    }
+}
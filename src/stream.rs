@@ -0,0 +1,137 @@
+//! Streams of model instances, for use within `futures` based pipelines.
+//!
+//! Only available with the `async` feature. The underlying driver is synchronous, so polling a
+//! [`ModelStream`](./struct.ModelStream.html) blocks while the next batch of documents is
+//! fetched from the server. Poll it from a thread which may block, such as that of a blocking
+//! thread pool, rather than from a reactor thread.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use bson;
+use bson::{Bson, Document};
+use futures::{Async, Poll, Stream};
+use mongodb::CommandType;
+use mongodb::db::{Database, ThreadedDatabase};
+use mongodb::error::Error::{DecoderError, ResponseError};
+use serde::de::DeserializeOwned;
+
+use cursor::ModelCursor;
+use error::Result;
+use model::Model;
+
+/// A stream which lazily deserializes each document of a cursor into an instance of `T`, &
+/// transforms it into an `R` via `F`.
+///
+/// Each instance is transformed as it is polled, so nothing is collected. Each item is a
+/// `Result`, & the stream itself never errors: an error — whether from the cursor,
+/// deserialization or the transform — is yielded as the `Err` of that item alone, so that
+/// combinators such as `for_each` carry on to the following items.
+pub struct ModelStream<T, R, F> {
+    cursor: ModelCursor<T>,
+    transform: F,
+    marker: PhantomData<R>,
+}
+
+impl<T, R, F> ModelStream<T, R, F> {
+    /// Stream the instances of the given cursor, transformed by the given function.
+    pub fn new(cursor: ModelCursor<T>, transform: F) -> ModelStream<T, R, F> {
+        ModelStream{cursor, transform, marker: PhantomData}
+    }
+}
+
+impl<'a, T, R, F> Stream for ModelStream<T, R, F> where T: Model<'a>, F: Fn(T) -> Result<R> {
+    type Item = Result<R>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Result<R>>, ()> {
+        match self.cursor.next() {
+            Some(Ok(instance)) => Ok(Async::Ready(Some((self.transform)(instance)))),
+            Some(Err(err)) => Ok(Async::Ready(Some(Err(err)))),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+/// A stream which lazily deserializes each output document of an aggregation into an `R`.
+///
+/// The aggregation's server side cursor is driven directly via `getMore`, so only the current
+/// batch is held in memory, & is killed via `killCursors` when the stream is dropped before
+/// being exhausted. As with a `ModelStream`, each item is a `Result` & the stream itself never
+/// errors. An error deserializing a document is yielded for that item alone, while an error
+/// fetching a batch is yielded as the final item of the stream.
+pub struct AggregateStream<R> {
+    db: Database,
+    collection: String,
+    cursor_id: i64,
+    batch: VecDeque<Bson>,
+    marker: PhantomData<R>,
+}
+
+impl<R> AggregateStream<R> {
+    /// Execute the given pipeline against the named collection of the given database, with
+    /// `allowDiskUse` set as given, & stream its output.
+    pub fn new(db: Database, collection: &str, pipeline: Vec<Document>, allow_disk_use: bool) -> Result<AggregateStream<R>> {
+        let stages: Vec<Bson> = pipeline.into_iter().map(Bson::Document).collect();
+        let spec = doc!{
+            "aggregate": collection,
+            "pipeline": stages,
+            "allowDiskUse": allow_disk_use,
+            "cursor": {},
+        };
+        let res = db.command(spec, CommandType::Suppressed, None)?;
+        let mut stream = AggregateStream{db, collection: collection.to_owned(), cursor_id: 0, batch: VecDeque::new(), marker: PhantomData};
+        stream.read_batch(res, "firstBatch")?;
+        Ok(stream)
+    }
+
+    /// Take the cursor ID & the named batch of documents from the given cursor response.
+    fn read_batch(&mut self, mut res: Document, field: &str) -> Result<()> {
+        let mut cursor = match res.remove("cursor") {
+            Some(Bson::Document(cursor)) => cursor,
+            _ => return Err(ResponseError("Server failed to return an aggregation cursor.".to_owned()).into()),
+        };
+        self.cursor_id = cursor.get_i64("id").unwrap_or(0);
+        if let Some(Bson::Array(batch)) = cursor.remove(field) {
+            self.batch.extend(batch);
+        }
+        Ok(())
+    }
+}
+
+impl<R: DeserializeOwned> Stream for AggregateStream<R> {
+    type Item = Result<R>;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Result<R>>, ()> {
+        while self.batch.is_empty() && self.cursor_id != 0 {
+            let spec = doc!{"getMore": self.cursor_id, "collection": self.collection.clone()};
+            let read = match self.db.command(spec, CommandType::Suppressed, None) {
+                Ok(res) => self.read_batch(res, "nextBatch"),
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = read {
+                // The server kills a cursor upon an error, so don't try to kill it on drop.
+                self.cursor_id = 0;
+                return Ok(Async::Ready(Some(Err(err))));
+            }
+        }
+        match self.batch.pop_front() {
+            Some(doc) => Ok(Async::Ready(Some(bson::from_bson(doc).map_err(|err| DecoderError(err).into())))),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+impl<R> Drop for AggregateStream<R> {
+    fn drop(&mut self) {
+        if self.cursor_id == 0 {
+            return;
+        }
+        let mut spec = doc!{"killCursors": self.collection.clone()};
+        spec.insert("cursors", vec![Bson::I64(self.cursor_id)]);
+        if let Err(err) = self.db.command(spec, CommandType::Suppressed, None) {
+            warn!("Failed to kill aggregation cursor {} of '{}': {}", self.cursor_id, self.collection, err);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Routing models to per-tenant collections, for multi-tenant apps which keep each tenant's
+//! documents in a collection of its own.
+//!
+//! A [`TenantScoped`](./struct.TenantScoped.html) handle — given by
+//! [`Model::for_tenant`](../model/trait.Model.html#method.for_tenant) — runs model operations
+//! against the tenant's collection, named `{COLLECTION_NAME}_{tenant}`, rather than the model's
+//! own collection:
+//!
+//! ```rust,ignore
+//! let orders = Order::for_tenant("tenant123")?;
+//! orders.sync(db.clone())?; // Syncs indexes & migrations of `orders_tenant123`.
+//! orders.save(&mut order, db.clone(), None)?;
+//! let pending = orders.find(db.clone(), Some(doc!{"status": "pending"}), None)?;
+//!
+//! // Any other operation may be scoped to the tenant as well.
+//! let count = orders.scope(|| Order::count(db.clone(), None, None))?;
+//! ```
+//!
+//! Tenant IDs are validated, so that user input can not inject into the collection name. While
+//! an operation is scoped, [`Model::collection`](../model/trait.Model.html#method.collection)
+//! resolves to the tenant's collection on the current thread; scopes do not follow work onto
+//! other threads, such as that of a `ModelStream`. Operations on the model's collections made
+//! via the driver directly are not routed.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+use bson::Document;
+use mongodb::coll::Collection;
+use mongodb::coll::options::FindOptions;
+use mongodb::db::Database;
+use mongodb::error::Error::ArgumentError;
+
+use error::Result;
+use model::{Model, SyncReport};
+
+/// The maximum length of a tenant ID.
+pub const MAX_TENANT_ID_LEN: usize = 64;
+
+thread_local! {
+    /// The tenant scopes active on this thread, innermost last, as collection & tenant ID pairs.
+    static SCOPES: RefCell<Vec<(&'static str, String)>> = RefCell::new(vec![]);
+}
+
+/// A handle running operations of a model against the collection of a single tenant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenantScoped<M> {
+    tenant: String,
+    marker: PhantomData<M>,
+}
+
+impl<'a, M: Model<'a>> TenantScoped<M> {
+    /// Create a handle for the given tenant.
+    ///
+    /// Returns an `ArgumentError` unless the tenant ID is non-empty, at most
+    /// [`MAX_TENANT_ID_LEN`](./constant.MAX_TENANT_ID_LEN.html) long, & made only of ASCII
+    /// letters, digits, `_` & `-`.
+    pub fn new(tenant: &str) -> Result<TenantScoped<M>> {
+        validate_tenant_id(tenant)?;
+        Ok(TenantScoped{tenant: tenant.to_owned(), marker: PhantomData})
+    }
+
+    /// The ID of this handle's tenant.
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    /// The name of the tenant's collection.
+    pub fn collection_name(&self) -> String {
+        tenant_collection_name(M::COLLECTION_NAME, &self.tenant)
+    }
+
+    /// Get the tenant's collection, within the model's routed database.
+    pub fn collection(&self, db: &Database) -> Collection {
+        self.scope(|| M::collection(db))
+    }
+
+    /// Run the given operation with this model's collection resolving to the tenant's collection.
+    pub fn scope<T, F: FnOnce() -> T>(&self, operation: F) -> T {
+        SCOPES.with(|scopes| scopes.borrow_mut().push((M::COLLECTION_NAME, self.tenant.clone())));
+        let _guard = ScopeGuard;
+        operation()
+    }
+
+    /// Find all instances of this model in the tenant's collection, as by `Model::find`.
+    pub fn find(&self, db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Vec<M>> {
+        self.scope(|| M::find(db, filter, options))
+    }
+
+    /// Find one instance of this model in the tenant's collection, as by `Model::find_one`.
+    pub fn find_one(&self, db: Database, filter: Option<Document>, options: Option<FindOptions>) -> Result<Option<M>> {
+        self.scope(|| M::find_one(db, filter, options))
+    }
+
+    /// Save the given instance to the tenant's collection, as by `Model.save`.
+    pub fn save(&self, model: &mut M, db: Database, filter: Option<Document>) -> Result<()> {
+        self.scope(|| model.save(db, filter))
+    }
+
+    /// Synchronize the tenant's collection with the model's configuration, as by `Model::sync`.
+    ///
+    /// Each tenant's collection has its own indexes, migrations & seeds, so each must be synced
+    /// separately, such as when a tenant is onboarded.
+    pub fn sync(&self, db: Database) -> Result<SyncReport> {
+        self.scope(|| M::sync(db))
+    }
+}
+
+/// Pops the innermost tenant scope when dropped, including when its operation panics.
+struct ScopeGuard;
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        SCOPES.with(|scopes| scopes.borrow_mut().pop());
+    }
+}
+
+/// Get the name of the collection which the given collection resolves to on this thread: that
+/// of the innermost tenant scope for it, if any.
+pub(crate) fn scoped_collection_name(collection: &'static str) -> String {
+    SCOPES.with(|scopes| scopes.borrow().iter().rev()
+        .find(|&&(scoped, _)| scoped == collection)
+        .map(|&(_, ref tenant)| tenant_collection_name(collection, tenant))
+        .unwrap_or_else(|| collection.to_owned()))
+}
+
+/// Get the name of the given tenant's copy of the given collection.
+fn tenant_collection_name(collection: &str, tenant: &str) -> String {
+    format!("{}_{}", collection, tenant)
+}
+
+/// Ensure the given tenant ID is safe for use within a collection name.
+fn validate_tenant_id(tenant: &str) -> Result<()> {
+    if tenant.is_empty() || tenant.len() > MAX_TENANT_ID_LEN {
+        return Err(ArgumentError(format!("Tenant ID must be 1 to {} characters long.", MAX_TENANT_ID_LEN)).into());
+    }
+    if !tenant.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-') {
+        return Err(ArgumentError(format!("Tenant ID '{}' may only contain ASCII letters, digits, '_' & '-'.", tenant)).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_tenant_id_rejects_unsafe_ids() {
+        assert!(validate_tenant_id("tenant-123_a").is_ok());
+        assert!(validate_tenant_id("").is_err());
+        assert!(validate_tenant_id("a.b").is_err());
+        assert!(validate_tenant_id("a$b").is_err());
+        assert!(validate_tenant_id(&"a".repeat(MAX_TENANT_ID_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn scoped_collection_name_resolves_innermost_scope_for_collection() {
+        assert_eq!(scoped_collection_name("orders"), "orders");
+
+        SCOPES.with(|scopes| scopes.borrow_mut().extend(vec![("orders", "a".to_owned()), ("users", "b".to_owned()), ("orders", "c".to_owned())]));
+        assert_eq!(scoped_collection_name("orders"), "orders_c");
+        assert_eq!(scoped_collection_name("users"), "users_b");
+        assert_eq!(scoped_collection_name("places"), "places");
+        SCOPES.with(|scopes| scopes.borrow_mut().clear());
+    }
+}
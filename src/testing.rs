@@ -0,0 +1,40 @@
+//! Plumbing for generating model instances in tests.
+//!
+//! Only available with the `testing` feature, which is typically enabled only for
+//! `dev-dependencies`. Implement [`Fake`](./trait.Fake.html) for a model, providing realistic
+//! field values in `fake`; this module provides distinct ID generation & batch helpers on top.
+//!
+//! ```rust,ignore
+//! impl<'a> Fake<'a> for User {
+//!     fn fake() -> User {
+//!         User{id: None, email: format!("user-{}@test.com", rand::random::<u32>())}
+//!     }
+//! }
+//!
+//! let users = User::insert_fakes(db.clone(), 10)?;
+//! ```
+
+use mongodb::db::Database;
+
+use error::Result;
+use model::Model;
+
+/// A trait for models which can generate fake instances of themselves for tests.
+pub trait Fake<'a>: Model<'a> {
+    /// Generate a single fake instance of this model. Need not set an ID.
+    fn fake() -> Self;
+
+    /// Generate `n` fake instances of this model, each having a distinct, new ID.
+    fn fake_many(n: usize) -> Vec<Self> {
+        (0..n).map(|_| {
+            let mut instance = Self::fake();
+            instance.set_id(Self::generate_id().expect("Expected to generate a new ObjectId."));
+            instance
+        }).collect()
+    }
+
+    /// Generate & insert `n` fake instances of this model, returning them.
+    fn insert_fakes(db: Database, n: usize) -> Result<Vec<Self>> {
+        Self::insert_many_with_ids(db, Self::fake_many(n), None)
+    }
+}
@@ -0,0 +1,174 @@
+//! Field level change tracking for model instances.
+//!
+//! `Model.save` replaces the whole document, which sends every field over the wire & may
+//! clobber fields changed concurrently by another writer. A [`Tracked`](./struct.Tracked.html)
+//! model instead remembers the document it was loaded as, & its
+//! [`save`](./struct.Tracked.html#method.save) issues an update touching only the fields which
+//! have changed since — a `$set` of changed fields & an `$unset` of removed fields.
+//!
+//! ```rust,ignore
+//! let mut user = Tracked::new(User::get_by_id(db.clone(), id)?)?;
+//! user.email = String::from("new@test.com");
+//! assert_eq!(user.dirty_fields()?, vec!["email"]);
+//! user.save(db.clone())?; // Issues `{"$set": {"email": "new@test.com"}}`.
+//! ```
+//!
+//! Changes are detected by comparing top level fields of the serialized documents, so a change
+//! within an embedded document or array sets that whole field.
+
+use std::ops::{Deref, DerefMut};
+
+use bson::Document;
+use chrono::Utc;
+use mongodb::coll::options::UpdateOptions;
+use mongodb::db::Database;
+use mongodb::error::Error::{ArgumentError, WriteError};
+
+use error::Result;
+use model::{bson_equivalent, touch_document_timestamps, touch_timestamps_at, Model};
+use query_cache;
+
+/// A model instance which tracks the fields changed since it was loaded.
+///
+/// Dereferences to the model itself, so that it may be read & mutated as usual.
+#[derive(Debug, Clone)]
+pub struct Tracked<M> {
+    model: M,
+    snapshot: Document,
+}
+
+impl<'a, M: Model<'a>> Tracked<M> {
+    /// Begin tracking changes to the given model instance, as it currently stands.
+    pub fn new(model: M) -> Result<Tracked<M>> {
+        let snapshot = model.document_from_instance()?;
+        Ok(Tracked{model, snapshot})
+    }
+
+    /// The names of the top level fields which have changed since the instance was loaded, or
+    /// last saved.
+    pub fn dirty_fields(&self) -> Result<Vec<String>> {
+        let current = self.model.document_from_instance()?;
+        let mut fields: Vec<String> = current.iter()
+            .filter(|&(key, val)| !self.snapshot.get(key).map_or(false, |old| bson_equivalent(old, val)))
+            .map(|(key, _)| key.clone())
+            .collect();
+        fields.extend(self.snapshot.keys().filter(|key| !current.contains_key(key)).cloned());
+        Ok(fields)
+    }
+
+    /// Save only the fields of this instance which have changed, by ID.
+    ///
+    /// If the model manages [`timestamps`](../model/trait.Model.html#method.timestamps) they are
+    /// touched, so `updated_at` is always among the changes, though the instance's timestamps are
+    /// only updated once the write succeeds. Does nothing if no fields have changed. Field order
+    /// within embedded documents is not significant. The instance must have an ID.
+    pub fn save(&mut self, db: Database) -> Result<()> {
+        let id = match self.model.id() {
+            Some(id) => id,
+            None => return Err(ArgumentError("Model must have an ObjectId for this operation.".to_owned()).into()),
+        };
+        self.model.validate()?;
+
+        // Touch the written document, leaving the instance as it is should the write fail.
+        let now = Utc::now();
+        let timestamped = self.model.timestamps().is_some();
+        let mut current = self.model.document_from_instance()?;
+        if timestamped {
+            touch_document_timestamps(&mut current, now);
+        }
+        let update = match document_changes(&self.snapshot, &current) {
+            Some(update) => update,
+            None => return Ok(()),
+        };
+
+        let coll = M::collection(&db);
+        let options = UpdateOptions{upsert: Some(false), write_concern: Some(M::model_write_concern())};
+        let res = query_cache::invalidate_after(&coll.namespace, || coll.update_one(doc!{"_id": id.clone()}, update, Some(options)))?;
+        if let Some(err) = res.write_exception {
+            return Err(WriteError(err).into());
+        }
+        M::cache_invalidate(&id);
+        if let Some(timestamps) = self.model.timestamps() {
+            touch_timestamps_at(timestamps, now);
+        }
+        self.snapshot = current;
+        Ok(())
+    }
+
+    /// Stop tracking changes, returning the model instance.
+    pub fn into_inner(self) -> M {
+        self.model
+    }
+}
+
+impl<M> Deref for Tracked<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<M> DerefMut for Tracked<M> {
+    fn deref_mut(&mut self) -> &mut M {
+        &mut self.model
+    }
+}
+
+/// Get the update document which changes `before` into `after`, if they differ.
+fn document_changes(before: &Document, after: &Document) -> Option<Document> {
+    let mut set = Document::new();
+    for (key, val) in after.iter() {
+        if !before.get(key).map_or(false, |old| bson_equivalent(old, val)) {
+            set.insert_bson(key.clone(), val.clone());
+        }
+    }
+    let mut unset = Document::new();
+    for key in before.keys() {
+        if !after.contains_key(key) {
+            unset.insert(key.clone(), "");
+        }
+    }
+
+    let mut update = Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    if update.is_empty() { None } else { Some(update) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_changes_sets_changed_and_unsets_removed_fields() {
+        let before = doc!{"_id": 1, "email": "old@test.com", "nickname": "old", "age": 30};
+        let after = doc!{"_id": 1, "email": "new@test.com", "age": 30, "city": "Berlin"};
+
+        let output = document_changes(&before, &after);
+
+        assert_eq!(output, Some(doc!{
+            "$set": {"email": "new@test.com", "city": "Berlin"},
+            "$unset": {"nickname": ""},
+        }));
+    }
+
+    #[test]
+    fn document_changes_is_none_without_changes() {
+        let doc = doc!{"_id": 1, "email": "test@test.com"};
+
+        assert_eq!(document_changes(&doc, &doc.clone()), None);
+    }
+
+    #[test]
+    fn document_changes_ignores_embedded_field_order() {
+        let before = doc!{"_id": 1, "address": {"city": "Berlin", "zip": "10115"}};
+        let after = doc!{"_id": 1, "address": {"zip": "10115", "city": "Berlin"}};
+
+        assert_eq!(document_changes(&before, &after), None);
+    }
+}
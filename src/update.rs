@@ -0,0 +1,185 @@
+//! Helpers for building update documents.
+//!
+//! An [`Update`](./struct.Update.html) is a thin layer over a bson `Document`, complementing the
+//! [`Filter`](../query/struct.Filter.html) builder. Unlike a filter, an update may be invalid: MongoDB
+//! rejects updates which apply more than one operator to the same field, or to overlapping paths
+//! such as `address` & `address.city`. Such conflicts are detected as the update is built, & are
+//! returned as an error by `Update::into_document`.
+//!
+//! ```rust,ignore
+//! let update = Update::new().set("email", new_email).inc("logins", 1).unset("reset_token");
+//! let user = user.update(db.clone(), update.into_document()?, None)?;
+//! ```
+
+use std::mem;
+
+use bson::{Bson, Document};
+use mongodb::error::Error::ArgumentError;
+
+use error::Result;
+
+/// A builder for update documents.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Update {
+    doc: Document,
+    conflict: Option<String>,
+}
+
+impl Update {
+    /// Create a new empty update.
+    pub fn new() -> Update {
+        Update{doc: Document::new(), conflict: None}
+    }
+
+    /// Set `field` to the given value, via `$set`.
+    pub fn set<V: Into<Bson>>(self, field: &str, value: V) -> Update {
+        self.operator("$set", field, value.into())
+    }
+
+    /// Remove `field`, via `$unset`.
+    pub fn unset(self, field: &str) -> Update {
+        self.operator("$unset", field, Bson::String(String::new()))
+    }
+
+    /// Increment the numeric `field` by the given amount, via `$inc`.
+    pub fn inc<V: Into<Bson>>(self, field: &str, amount: V) -> Update {
+        self.operator("$inc", field, amount.into())
+    }
+
+    /// Append the given value to the array `field`, via `$push`.
+    pub fn push<V: Into<Bson>>(self, field: &str, value: V) -> Update {
+        self.operator("$push", field, value.into())
+    }
+
+    /// Append the given value to the array `field` unless already present, via `$addToSet`.
+    pub fn add_to_set<V: Into<Bson>>(self, field: &str, value: V) -> Update {
+        self.operator("$addToSet", field, value.into())
+    }
+
+    /// Consume this update, returning the underlying update document.
+    ///
+    /// Returns an `ArgumentError` if the update is empty, or if any of its fields conflict.
+    pub fn into_document(self) -> Result<Document> {
+        if let Some(conflict) = self.conflict {
+            return Err(ArgumentError(conflict).into());
+        }
+        if self.doc.is_empty() {
+            return Err(ArgumentError("An update must apply at least one operator.".to_owned()).into());
+        }
+        Ok(self.doc)
+    }
+
+    /// Apply `operator` to `field`, recording the first conflict with any field already updated.
+    ///
+    /// Applying `$set` to the same field again replaces its previous value, & applying `$unset`
+    /// again has no further effect. Values given to `$push` & `$addToSet` for the same field are
+    /// collected into an `$each`, so that all are appended. Incrementing the same field twice is
+    /// a conflict.
+    fn operator(mut self, operator: &str, field: &str, value: Bson) -> Update {
+        if self.conflict.is_none() {
+            self.conflict = self.find_conflict(operator, field);
+        }
+        if !self.doc.contains_key(operator) {
+            self.doc.insert(operator, Document::new());
+        }
+        let collected = operator == "$push" || operator == "$addToSet";
+        if let Some(&mut Bson::Document(ref mut fields)) = self.doc.get_mut(operator) {
+            if collected && fields.contains_key(field) {
+                // Replace the value in place, as reinserting a key moves it last.
+                if let Some(existing) = fields.get_mut(field) {
+                    let previous = mem::replace(existing, Bson::Null);
+                    *existing = append_each(previous, value);
+                }
+            } else {
+                fields.insert(field, value);
+            }
+        }
+        self
+    }
+
+    /// Find a conflict between `operator` on `field` & the fields already updated, if any.
+    fn find_conflict(&self, operator: &str, field: &str) -> Option<String> {
+        for (existing_operator, fields) in self.doc.iter() {
+            let fields = match fields {
+                &Bson::Document(ref fields) => fields,
+                _ => continue,
+            };
+            for existing in fields.keys() {
+                if existing == field && existing_operator != operator {
+                    return Some(format!("Field '{}' is updated by both '{}' & '{}'.", field, existing_operator, operator));
+                }
+                if existing == field && operator == "$inc" {
+                    return Some(format!("Field '{}' is incremented more than once.", field));
+                }
+                if paths_overlap(existing, field) {
+                    return Some(format!("Updating '{}' would conflict with updating '{}'.", field, existing));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Append `value` to the `$each` of the existing value of a `$push` or `$addToSet`, converting
+/// the existing value to an `$each` if it is a single value.
+fn append_each(existing: Bson, value: Bson) -> Bson {
+    let mut each = match existing {
+        Bson::Document(mut modifiers) => match modifiers.remove("$each") {
+            Some(Bson::Array(mut values)) => {
+                values.push(value);
+                modifiers.insert("$each", values);
+                return Bson::Document(modifiers);
+            },
+            Some(each) => vec![each],
+            None => vec![Bson::Document(modifiers)],
+        },
+        existing => vec![existing],
+    };
+    each.push(value);
+    Bson::Document(doc!{"$each": each})
+}
+
+/// Check if one of the given distinct paths is a prefix of the other, e.g. `a` & `a.b`.
+fn paths_overlap(a: &str, b: &str) -> bool {
+    let (shorter, longer) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    longer.len() > shorter.len() && longer.starts_with(shorter) && longer[shorter.len()..].starts_with('.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_are_grouped_into_one_document() {
+        let update = Update::new().set("email", "a@b.c").inc("logins", 1).set("active", true).unset("token");
+
+        assert_eq!(update.into_document().unwrap(), doc!{
+            "$set": doc!{"email": "a@b.c", "active": true},
+            "$inc": doc!{"logins": 1},
+            "$unset": doc!{"token": ""}
+        });
+    }
+
+    #[test]
+    fn conflicting_operators_are_rejected() {
+        assert!(Update::new().set("count", 1).inc("count", 1).into_document().is_err());
+        assert!(Update::new().set("address", doc!{}).unset("address.city").into_document().is_err());
+        assert!(Update::new().inc("count", 1).inc("count", 2).into_document().is_err());
+        assert!(Update::new().set("address", 1).set("addresses.city", 1).into_document().is_ok());
+    }
+
+    #[test]
+    fn repeated_pushes_are_collected_into_each() {
+        let update = Update::new().push("tags", "a").push("tags", "b").add_to_set("ids", 1).add_to_set("ids", 2).add_to_set("ids", 3);
+
+        assert_eq!(update.into_document().unwrap(), doc!{
+            "$push": doc!{"tags": doc!{"$each": ["a", "b"]}},
+            "$addToSet": doc!{"ids": doc!{"$each": [1, 2, 3]}}
+        });
+    }
+
+    #[test]
+    fn empty_update_is_rejected() {
+        assert!(Update::new().into_document().is_err());
+    }
+}
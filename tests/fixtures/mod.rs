@@ -9,6 +9,7 @@ use std::env;
 use bson;
 use chrono::TimeZone;
 use mongodb::coll::options::IndexModel;
+use mongodb::common::{ReadMode, ReadPreference};
 use mongodb::db::{Database, ThreadedDatabase};
 use mongodb::ThreadedClient;
 use wither::Model;
@@ -59,13 +60,11 @@ impl<'a> Model<'a> for User {
     fn migrations() -> Vec<Box<wither::Migration>> {
         vec![
             // This migration doesn't really do much. Just exercises the system.
-            Box::new(wither::IntervalMigration{
-                name: String::from("test-migration"),
-                threshold: chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0),
-                filter: doc!{"email": doc!{"$exists": true}},
-                set: Some(doc!{"testfield": "test"}),
-                unset: None,
-            }),
+            Box::new(wither::IntervalMigration::new(
+                "test-migration",
+                chrono::Utc.ymd(2100, 1, 1).and_hms(1, 0, 0),
+                doc!{"email": doc!{"$exists": true}},
+            ).with_set(doc!{"testfield": "test"})),
         ]
     }
 }
@@ -110,15 +109,140 @@ impl<'a> Model<'a> for UserModelBadMigrations {
                 filter: doc!{"email": doc!{"$exists": true}},
                 set: None,
                 unset: None,
+                shard_range: None,
+                sample_fraction: None,
+                environments: None,
             }),
         ]
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Place {
+    /// The place's unique ID.
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<bson::oid::ObjectId>,
+
+    /// The place's name.
+    pub name: String,
+
+    /// The place's location, as a GeoJSON point.
+    pub location: bson::Document,
+}
+
+impl Place {
+    pub fn at(name: &str, lon: f64, lat: f64) -> Place {
+        Place{id: None, name: name.to_string(), location: doc!{"type": "Point", "coordinates": [lon, lat]}}
+    }
+}
+
+impl<'a> Model<'a> for Place {
+
+    const COLLECTION_NAME: &'static str = "places";
+
+    fn id(&self) -> Option<bson::oid::ObjectId> {
+        return self.id.clone();
+    }
+
+    fn set_id(&mut self, oid: bson::oid::ObjectId) {
+        self.id = Some(oid);
+    }
+
+    fn indexes() -> Vec<IndexModel> {
+        return vec![
+            IndexModel{
+                keys: doc!{"location" => "2dsphere"},
+                options: wither::basic_index_options("location", true, None, None, None),
+            },
+        ];
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Article {
+    /// The article's unique ID.
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<bson::oid::ObjectId>,
+
+    /// The article's title.
+    pub title: String,
+
+    /// The time at which the article was first saved.
+    pub created_at: Option<bson::UtcDateTime>,
+
+    /// The time at which the article was last saved.
+    pub updated_at: Option<bson::UtcDateTime>,
+}
+
+impl wither::Timestamps for Article {
+    fn created_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.created_at.as_ref().map(|at| at.0)
+    }
+
+    fn set_created_at(&mut self, at: chrono::DateTime<chrono::Utc>) {
+        self.created_at = Some(bson::UtcDateTime(at));
+    }
+
+    fn updated_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.updated_at.as_ref().map(|at| at.0)
+    }
+
+    fn set_updated_at(&mut self, at: chrono::DateTime<chrono::Utc>) {
+        self.updated_at = Some(bson::UtcDateTime(at));
+    }
+}
+
+impl<'a> Model<'a> for Article {
+
+    const COLLECTION_NAME: &'static str = "articles";
+
+    fn id(&self) -> Option<bson::oid::ObjectId> {
+        return self.id.clone();
+    }
+
+    fn set_id(&mut self, oid: bson::oid::ObjectId) {
+        self.id = Some(oid);
+    }
+
+    fn timestamps(&mut self) -> Option<&mut wither::Timestamps> {
+        Some(self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Report {
+    /// The report's unique ID.
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<bson::oid::ObjectId>,
+
+    /// The report's name.
+    pub name: String,
+}
+
+impl<'a> Model<'a> for Report {
+
+    const COLLECTION_NAME: &'static str = "reports";
+
+    fn id(&self) -> Option<bson::oid::ObjectId> {
+        return self.id.clone();
+    }
+
+    fn set_id(&mut self, oid: bson::oid::ObjectId) {
+        self.id = Some(oid);
+    }
+
+    fn read_preference() -> Option<ReadPreference> {
+        Some(ReadPreference::new(ReadMode::SecondaryPreferred, None))
+    }
+}
+
 pub fn setup() -> Database {
     // Delete any records in the collection for respective models.
     User::delete_many(DB.clone(), doc!{}).expect("Expected to successfully delete all records for test fixture.");
     UserModelBadMigrations::delete_many(DB.clone(), doc!{}).expect("Expected to successfully delete all records for test fixture.");
+    Place::delete_many(DB.clone(), doc!{}).expect("Expected to successfully delete all records for test fixture.");
+    Article::delete_many(DB.clone(), doc!{}).expect("Expected to successfully delete all records for test fixture.");
+    Report::delete_many(DB.clone(), doc!{}).expect("Expected to successfully delete all records for test fixture.");
 
     // Clean up any indices.
     let users_coll = DB.clone().collection(User::COLLECTION_NAME);
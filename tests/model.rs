@@ -13,11 +13,11 @@ mod fixtures;
 
 use std::error::Error;
 
-use mongodb::coll::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::coll::options::{FindOneAndUpdateOptions, IndexModel, ReturnDocument};
 use mongodb::db::ThreadedDatabase;
-use wither::Model;
+use wither::{Model, UpsertOutcome, WitherError};
 
-use fixtures::{setup, User, UserModelBadMigrations};
+use fixtures::{setup, Article, Place, Report, User, UserModelBadMigrations};
 
 //////////////////
 // Model::count //
@@ -48,6 +48,40 @@ fn model_save_should_save_model_instance_and_add_id() {
     assert!(user.id != None)
 }
 
+#[test]
+fn model_save_should_return_duplicate_key_error_on_unique_index_violation() {
+    let db = setup();
+    User::sync(db.clone()).expect("Expected a successful sync operation.");
+    let mut user = User{id: None, email: String::from("test@test.com")};
+    user.save(db.clone(), None).expect("Expected a successful save operation.");
+
+    let mut duplicate = User{id: None, email: String::from("test@test.com")};
+    let err = duplicate.save(db.clone(), None).expect_err("Expected a duplicate key error.");
+
+    match err {
+        WitherError::DuplicateKey{index, ..} => assert_eq!(index, "unique-email".to_string()),
+        other => panic!("Expected a duplicate key error, got {:?}.", other),
+    }
+}
+
+/////////////////////
+// Model::distinct //
+
+#[test]
+fn model_distinct_should_return_typed_distinct_values() {
+    let db = setup();
+    for email in vec!["a@test.com", "b@test.com"] {
+        let mut user = User{id: None, email: email.to_string()};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let mut emails = User::distinct::<String>(db.clone(), "email", None)
+        .expect("Expected a successful distinct operation.");
+    emails.sort();
+
+    assert_eq!(emails, vec!["a@test.com".to_string(), "b@test.com".to_string()]);
+}
+
 /////////////////
 // Model::find //
 
@@ -79,6 +113,40 @@ fn model_find_should_find_instances_of_model_matching_filter() {
     assert_eq!(&users_from_db[0].email, &user.email);
 }
 
+#[test]
+fn model_find_should_apply_default_sort_when_no_sort_given() {
+    let db = setup();
+    for email in vec!["c@test.com", "a@test.com", "b@test.com"] {
+        let mut user = User{id: None, email: email.to_string()};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let users_from_db = User::find(db.clone(), None, None)
+        .expect("Expected a successful lookup.");
+    let emails: Vec<&str> = users_from_db.iter().map(|user| user.email.as_str()).collect();
+
+    assert_eq!(emails, vec!["c@test.com", "a@test.com", "b@test.com"]);
+}
+
+/////////////////////////////////
+// Model::find_page_with_total //
+
+#[test]
+fn model_find_page_with_total_should_return_page_and_total() {
+    let db = setup();
+    for email in vec!["a@test.com", "b@test.com", "c@test.com"] {
+        let mut user = User{id: None, email: email.to_string()};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let page = User::find_page_with_total(db.clone(), None, 1, 2, Some(doc!{"email": 1}))
+        .expect("Expected a successful paginated lookup.");
+
+    assert_eq!(page.total, 3);
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(&page.items[0].email, "c@test.com");
+}
+
 /////////////////////
 // Model::find_one //
 
@@ -98,6 +166,347 @@ fn model_find_one_should_fetch_the_model_instance_matching_given_filter() {
     assert_eq!(&user_from_db.email, &user.email);
 }
 
+//////////////////////
+// Model::get_by_id //
+
+#[test]
+fn model_get_by_id_should_fetch_the_model_instance_having_given_id() {
+    let db = setup();
+    let mut user = User{id: None, email: "test@test.com".to_string()};
+    user.save(db.clone(), None).expect("Expected a successful save operation.");
+
+    let user_from_db = User::get_by_id(db.clone(), user.id.clone().unwrap())
+        .expect("Expected a successful lookup.");
+
+    assert_eq!(&user_from_db.id, &user.id);
+}
+
+#[test]
+fn model_get_by_id_should_return_not_found_error_when_absent() {
+    let db = setup();
+    let id = bson::oid::ObjectId::new().expect("Expected a new ObjectId.");
+
+    let err = User::get_by_id(db.clone(), id.clone())
+        .expect_err("Expected a not found error.");
+
+    match err {
+        WitherError::NotFound{collection, id: missing_id} => {
+            assert_eq!(collection, User::COLLECTION_NAME);
+            assert_eq!(missing_id, id);
+        },
+        _ => panic!("Expected a `WitherError::NotFound`, got: {:?}", err),
+    }
+}
+
+/////////////////////////////////
+// Model::insert_many_with_ids //
+
+#[test]
+fn model_insert_many_with_ids_should_keep_preallocated_ids_and_populate_missing_ones() {
+    let db = setup();
+    let mut first = User{id: None, email: "first@test.com".to_string()};
+    let allocated = first.allocate_id().expect("Expected an ID to be allocated.");
+    let second = User{id: None, email: "second@test.com".to_string()};
+
+    let users = User::insert_many_with_ids(db.clone(), vec![first, second], None)
+        .expect("Expected a successful insert operation.");
+
+    assert_eq!(users[0].id, Some(allocated));
+    assert!(users[1].id.is_some());
+    let user_from_db = User::get_by_id(db.clone(), users[1].id.clone().unwrap())
+        .expect("Expected a successful lookup.");
+    assert_eq!(&user_from_db.email, &users[1].email);
+}
+
+////////////////////////////////
+// Model::find_one_and_upsert //
+
+#[test]
+fn model_find_one_and_upsert_should_distinguish_insert_from_update() {
+    let db = setup();
+    let filter = doc!{"email": "upsert@test.com"};
+    let update = doc!{"$set": {"email": "upsert@test.com"}};
+
+    let (inserted, first) = User::find_one_and_upsert(db.clone(), filter.clone(), update.clone(), None)
+        .expect("Expected a successful upsert.");
+    let (_, second) = User::find_one_and_upsert(db.clone(), filter, update, None)
+        .expect("Expected a successful upsert.");
+
+    assert_eq!(first, UpsertOutcome::Upserted(inserted.id.clone().unwrap()));
+    assert_eq!(second, UpsertOutcome::Matched);
+}
+
+///////////////////////////////
+// Model::text_search_scored //
+
+#[test]
+fn model_text_search_scored_should_return_matches_in_descending_score_order() {
+    let db = setup();
+    let coll = db.collection(User::COLLECTION_NAME);
+    let text_index = IndexModel{keys: doc!{"email": "text"}, options: wither::basic_index_options("email-text", false, None, None, None)};
+    coll.create_index_model(text_index.clone()).expect("Expected to create a text index.");
+    for email in vec!["rust@test.com", "rust.rust.rust@test.com", "other@test.com"] {
+        let mut user = User{id: None, email: email.to_string()};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let results = User::text_search_scored(db.clone(), "rust", 0.0)
+        .expect("Expected a successful text search.");
+    let _ = coll.drop_index_model(text_index);
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(&results[0].0.email, "rust.rust.rust@test.com");
+    assert!(results[0].1 >= results[1].1);
+}
+
+//////////////////////////////////
+// Model::find_streaming_capped //
+
+#[test]
+fn model_find_streaming_capped_should_yield_bounded_chunks() {
+    let db = setup();
+    for idx in 0..5 {
+        let mut user = User{id: None, email: format!("test-{}@test.com", idx)};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let chunks: Vec<Vec<User>> = User::find_streaming_capped(db.clone(), None, 2)
+        .expect("Expected a successful find operation.")
+        .collect::<Result<_, _>>()
+        .expect("Expected each chunk to load.");
+
+    let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.len()).collect();
+    assert_eq!(sizes, vec![2, 2, 1]);
+}
+
+//////////////////////////
+// Model::export_stream //
+
+#[test]
+fn model_export_stream_should_yield_projected_raw_documents() {
+    let db = setup();
+    for idx in 0..3 {
+        let mut user = User{id: None, email: format!("export-{}@test.com", idx)};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let docs: Vec<bson::Document> = User::export_stream(db.clone(), None, Some(doc!{"_id": 0, "email": 1}))
+        .expect("Expected a successful export operation.")
+        .collect::<Result<_, _>>()
+        .expect("Expected each document to load.");
+
+    let mut emails: Vec<&str> = docs.iter().map(|doc| doc.get_str("email").expect("Expected an email.")).collect();
+    emails.sort();
+    assert_eq!(emails, vec!["export-0@test.com", "export-1@test.com", "export-2@test.com"]);
+    assert!(docs.iter().all(|doc| !doc.contains_key("_id")));
+}
+
+#[test]
+fn model_export_stream_should_read_per_model_read_preference() {
+    let db = setup();
+    let mut report = Report{id: None, name: "daily".to_string()};
+    report.save(db.clone(), None).expect("Expected a successful save operation.");
+
+    let docs: Vec<bson::Document> = Report::export_stream(db.clone(), None, Some(doc!{"_id": 0, "name": 1}))
+        .expect("Expected a successful export operation.")
+        .collect::<Result<_, _>>()
+        .expect("Expected each document to load.");
+
+    assert_eq!(docs, vec![doc!{"name": "daily"}]);
+}
+
+/////////////////
+// Model::tail //
+
+#[test]
+fn model_tail_should_error_on_uncapped_collection() {
+    let db = setup();
+    User::sync(db.clone()).expect("Expected a successful sync operation.");
+
+    let err = User::tail(db.clone(), None, None).err().expect("Expected an uncapped collection error.");
+
+    assert_eq!(err.description(), "Collection 'witherTestDB.users' must be capped to be tailed.");
+}
+
+//////////////////////////////
+// Model::find_one_and_lock //
+
+#[test]
+fn model_find_one_and_lock_should_exclude_other_owners_until_unlocked() {
+    let db = setup();
+    let mut user = User{id: None, email: String::from("test@test.com")};
+    user.save(db.clone(), None).expect("Expected a successful save operation.");
+    let ttl = std::time::Duration::from_secs(60);
+
+    let locked = User::find_one_and_lock(db.clone(), doc!{}, "lock", "worker-a", ttl).expect("Expected a successful lock operation.");
+    let contended = User::find_one_and_lock(db.clone(), doc!{}, "lock", "worker-b", ttl).expect("Expected a successful lock operation.");
+    let released = user.unlock(db.clone(), "lock", "worker-a").expect("Expected a successful unlock operation.");
+    let relocked = User::find_one_and_lock(db.clone(), doc!{}, "lock", "worker-b", ttl).expect("Expected a successful lock operation.");
+
+    assert!(locked.is_some());
+    assert!(contended.is_none());
+    assert!(released);
+    assert!(relocked.is_some());
+}
+
+////////////////////////////////
+// Model::find_by_ids_ordered //
+
+#[test]
+fn model_find_by_ids_ordered_should_return_instances_in_given_order() {
+    let db = setup();
+    let mut ids = vec![];
+    for idx in 0..3 {
+        let mut user = User{id: None, email: format!("ordered-{}@test.com", idx)};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+        ids.push(user.id.clone().expect("Expected the saved user to have an ID."));
+    }
+    let missing = bson::oid::ObjectId::new().expect("Expected a new ObjectId.");
+
+    let users = User::find_by_ids_ordered(db.clone(), &[ids[2].clone(), missing, ids[0].clone(), ids[1].clone()])
+        .expect("Expected a successful find operation.");
+
+    let emails: Vec<&str> = users.iter().map(|user| user.email.as_str()).collect();
+    assert_eq!(emails, vec!["ordered-2@test.com", "ordered-0@test.com", "ordered-1@test.com"]);
+}
+
+////////////////////////////////
+// Model::find_with_record_id //
+
+#[test]
+fn model_find_with_record_id_should_pair_instances_with_record_ids() {
+    let db = setup();
+    let mut user = User{id: None, email: String::from("record-id@test.com")};
+    user.save(db.clone(), None).expect("Expected a successful save operation.");
+
+    let found = User::find_with_record_id(db.clone(), Some(doc!{"email": "record-id@test.com"}))
+        .expect("Expected a successful find operation.");
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0.id, user.id);
+    assert!(found[0].1 > 0);
+}
+
+///////////////////////
+// Model::for_tenant //
+
+#[test]
+fn model_for_tenant_should_route_operations_to_tenant_collection() {
+    let db = setup();
+    let tenant = User::for_tenant("tenant123").expect("Expected a valid tenant ID.");
+    let mut user = User{id: None, email: String::from("tenant@test.com")};
+    tenant.save(&mut user, db.clone(), None).expect("Expected a successful save operation.");
+
+    let found = tenant.find(db.clone(), Some(doc!{"email": "tenant@test.com"}), None).expect("Expected a successful find operation.");
+    let untenanted = User::find(db.clone(), Some(doc!{"email": "tenant@test.com"}), None).expect("Expected a successful find operation.");
+
+    assert_eq!(tenant.collection_name(), "users_tenant123");
+    assert_eq!(found.len(), 1);
+    assert_eq!(untenanted.len(), 0);
+    assert!(User::for_tenant("tenant.123").is_err());
+    db.collection("users_tenant123").drop().expect("Expected the tenant collection to be dropped.");
+}
+
+///////////////////////////////
+// Model::find_changed_since //
+
+#[test]
+fn model_find_changed_since_should_return_instances_updated_after_given_time() {
+    let db = setup();
+    let coll = db.collection(Article::COLLECTION_NAME);
+    let now = chrono::Utc::now();
+    for (title, age_in_hours) in vec![("old", 48), ("recent", 2), ("fresh", 0)] {
+        let mut article = Article{id: None, title: title.to_string(), created_at: None, updated_at: None};
+        article.save(db.clone(), None).expect("Expected a successful save operation.");
+        let updated_at = bson::Bson::UtcDatetime(now - chrono::Duration::hours(age_in_hours));
+        coll.update_one(doc!{"_id": article.id.clone().unwrap()}, doc!{"$set": {"updated_at": updated_at}}, None)
+            .expect("Expected a successful update operation.");
+    }
+
+    let changed = Article::find_changed_since(db.clone(), now - chrono::Duration::hours(24), None)
+        .expect("Expected a successful find operation.");
+
+    let titles: Vec<&str> = changed.iter().map(|article| article.title.as_str()).collect();
+    assert_eq!(titles, vec!["recent", "fresh"]);
+}
+
+////////////////////
+// Model::builder //
+
+#[test]
+fn model_builder_should_build_instances_and_reject_missing_fields() {
+    let user = User::builder().field("email", "builder@test.com").build()
+        .expect("Expected a valid instance.");
+    let errors = User::builder().build().expect_err("Expected the missing email to be rejected.");
+
+    assert_eq!(&user.email, "builder@test.com");
+    assert!(user.id.is_none());
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("email"));
+}
+
+/////////////////////////
+// Model::force_resync //
+
+#[test]
+fn model_force_resync_should_require_confirmation_and_recreate_indexes() {
+    let db = setup();
+    User::sync(db.clone()).expect("Expected a successful sync operation.");
+
+    assert!(User::force_resync(db.clone(), "places").is_err());
+    let report = User::force_resync(db.clone(), User::COLLECTION_NAME).expect("Expected a successful resync operation.");
+
+    assert_eq!(report.indexes_created, vec!["unique-email".to_string()]);
+    assert_eq!(report.migrations.len(), 1);
+}
+
+///////////////////////////////////
+// Model::verify_read_preference //
+
+#[test]
+fn model_verify_read_preference_should_succeed_on_empty_collection() {
+    let db = setup();
+
+    User::verify_read_preference(db.clone()).expect("Expected the read preference to be satisfied.");
+}
+
+//////////////////////////////////
+// Model::find_limited_has_more //
+
+#[test]
+fn model_find_limited_has_more_should_report_whether_more_exist() {
+    let db = setup();
+    for idx in 0..3 {
+        let mut user = User{id: None, email: format!("test-{}@test.com", idx)};
+        user.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let (items, has_more) = User::find_limited_has_more(db.clone(), None, 2, None).expect("Expected a successful find operation.");
+    let (all_items, all_has_more) = User::find_limited_has_more(db.clone(), None, 3, None).expect("Expected a successful find operation.");
+
+    assert_eq!((items.len(), has_more), (2, true));
+    assert_eq!((all_items.len(), all_has_more), (3, false));
+}
+
+////////////////////////
+// Model::near_sphere //
+
+#[test]
+fn model_near_sphere_should_respect_min_and_max_distance() {
+    let db = setup();
+    Place::sync(db.clone()).expect("Expected a successful sync operation.");
+    // Along the equator, 0.001 degrees of longitude is roughly 111 meters.
+    for mut place in vec![Place::at("here", 0.0, 0.0), Place::at("nearby", 0.001, 0.0), Place::at("far", 0.02, 0.0)] {
+        place.save(db.clone(), None).expect("Expected a successful save operation.");
+    }
+
+    let output = Place::near_sphere(db.clone(), "location", 0.0, 0.0, Some(10.0), Some(1000.0))
+        .expect("Expected a successful find operation.");
+
+    let names: Vec<String> = output.into_iter().map(|place| place.name).collect();
+    assert_eq!(names, vec!["nearby".to_string()]);
+}
+
 //////////////////
 // Model.update //
 
@@ -181,6 +590,46 @@ fn model_sync_should_execute_expected_migrations_against_collection() {
     });
 }
 
+#[test]
+fn model_run_migrations_should_error_on_unknown_names() {
+    let db = setup();
+
+    let err = User::run_migrations(db.clone(), &["test-migration", "missing-migration"]).expect_err("Expected an unknown migration error.");
+
+    assert_eq!(err.description(), "Unknown migrations for 'users': missing-migration.");
+}
+
+#[test]
+fn model_force_unlock_migrations_should_clear_a_stuck_lock() {
+    let db = setup();
+    let locks = db.collection(wither::migration::MIGRATION_LOCKS_COLLECTION);
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+    locks.delete_many(doc!{}, None).expect("Expected to successfully clear migration locks.");
+    locks.insert_one(doc!{"_id": "witherTestDB.users", "token": "crashed", "expires_at": bson::Bson::UtcDatetime(expires_at)}, None)
+        .expect("Expected to successfully insert a stuck migration lock.");
+
+    User::sync(db.clone()).expect_err("Expected sync to fail while migrations are locked.");
+    let cleared = User::force_unlock_migrations(db.clone()).expect("Expected to successfully force unlock migrations.");
+    User::sync(db.clone()).expect("Expected sync to succeed once migrations are unlocked.");
+
+    assert!(cleared);
+    assert_eq!(locks.count(Some(doc!{}), None).expect("Expected to successfully count migration locks."), 0);
+}
+
+#[test]
+fn model_sync_should_wait_for_a_held_migration_lock_to_expire() {
+    let db = setup();
+    let locks = db.collection(wither::migration::MIGRATION_LOCKS_COLLECTION);
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(2);
+    locks.delete_many(doc!{}, None).expect("Expected to successfully clear migration locks.");
+    locks.insert_one(doc!{"_id": "witherTestDB.users", "token": "other", "expires_at": bson::Bson::UtcDatetime(expires_at)}, None)
+        .expect("Expected to successfully insert a held migration lock.");
+
+    User::sync(db.clone()).expect("Expected sync to succeed once the migration lock expires.");
+
+    assert_eq!(locks.count(Some(doc!{}), None).expect("Expected to successfully count migration locks."), 0);
+}
+
 #[test]
 fn model_sync_should_error_if_migration_with_no_set_and_no_unset_given() {
     let db = setup();
@@ -191,3 +640,56 @@ fn model_sync_should_error_if_migration_with_no_set_and_no_unset_given() {
 
     assert_eq!(err.description(), "One of '$set' or '$unset' must be specified.");
 }
+
+//////////////////////////////
+// BatchedMigration.execute //
+
+#[test]
+fn batched_migration_should_resume_after_a_matching_checkpoint() {
+    let db = setup();
+    let coll = db.collection("batched_migration_checkpoints");
+    let tracking = db.collection(wither::migration::MIGRATIONS_COLLECTION);
+    coll.delete_many(doc!{}, None).expect("Expected to successfully clear the collection.");
+    let ids: Vec<bson::Bson> = (0..3).map(|_| bson::Bson::ObjectId(bson::oid::ObjectId::new().expect("Expected a new ObjectId."))).collect();
+    for id in ids.iter() {
+        coll.insert_one(doc!{"_id": id.clone()}, None).expect("Expected to successfully insert a document.");
+    }
+    let threshold = chrono::Utc::now() + chrono::Duration::days(1);
+    let migration = wither::BatchedMigration::new("checkpointed", threshold, doc!{}, 1).with_set(doc!{"migrated": true});
+    let tracking_id = doc!{"namespace": coll.namespace.clone(), "name": "checkpointed"};
+    tracking.delete_many(doc!{"_id": tracking_id.clone()}, None).expect("Expected to successfully clear migration tracking.");
+    tracking.insert_one(doc!{"_id": tracking_id.clone(), "checkpoint": {"last_id": ids[0].clone(), "checksum": wither::Migration::checksum(&migration).expect("Expected a checksum.")}}, None)
+        .expect("Expected to successfully seed a checkpoint.");
+
+    let res = wither::Migration::execute(&migration, &coll).expect("Expected the migration to succeed.");
+
+    assert_eq!(res.modified_count, 2);
+    assert_eq!(coll.count(Some(doc!{"migrated": true}), None).expect("Expected a successful count."), 2);
+    assert_eq!(coll.count(Some(doc!{"_id": ids[0].clone(), "migrated": true}), None).expect("Expected a successful count."), 0);
+    let record = tracking.find_one(Some(doc!{"_id": tracking_id}), None)
+        .expect("Expected a successful lookup.").expect("Expected a tracking record.");
+    assert!(!record.contains_key("checkpoint"));
+}
+
+#[test]
+fn batched_migration_should_discard_a_checkpoint_with_a_mismatched_checksum() {
+    let db = setup();
+    let coll = db.collection("batched_migration_stale_checkpoints");
+    let tracking = db.collection(wither::migration::MIGRATIONS_COLLECTION);
+    coll.delete_many(doc!{}, None).expect("Expected to successfully clear the collection.");
+    let ids: Vec<bson::Bson> = (0..3).map(|_| bson::Bson::ObjectId(bson::oid::ObjectId::new().expect("Expected a new ObjectId."))).collect();
+    for id in ids.iter() {
+        coll.insert_one(doc!{"_id": id.clone()}, None).expect("Expected to successfully insert a document.");
+    }
+    let threshold = chrono::Utc::now() + chrono::Duration::days(1);
+    let migration = wither::BatchedMigration::new("checkpointed", threshold, doc!{}, 1).with_set(doc!{"migrated": true});
+    let tracking_id = doc!{"namespace": coll.namespace.clone(), "name": "checkpointed"};
+    tracking.delete_many(doc!{"_id": tracking_id.clone()}, None).expect("Expected to successfully clear migration tracking.");
+    tracking.insert_one(doc!{"_id": tracking_id, "checkpoint": {"last_id": ids[2].clone(), "checksum": "stale"}}, None)
+        .expect("Expected to successfully seed a checkpoint.");
+
+    let res = wither::Migration::execute(&migration, &coll).expect("Expected the migration to succeed.");
+
+    assert_eq!(res.modified_count, 3);
+    assert_eq!(coll.count(Some(doc!{"migrated": true}), None).expect("Expected a successful count."), 3);
+}